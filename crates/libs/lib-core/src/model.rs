@@ -26,6 +26,7 @@ pub mod agent;
 pub mod conv;
 pub mod conv_msg;
 pub mod conv_user;
+pub mod etl_run;
 pub mod modql_utils;
 pub mod user;
 
@@ -0,0 +1,332 @@
+use crate::ctx::Ctx;
+use crate::generate_common_bmc_fns;
+use crate::model::base::{self, prep_fields_for_update, DbBmc};
+use crate::model::modql_utils::time_to_sea_value;
+use crate::model::ModelManager;
+use crate::model::Result;
+use lib_utils::time::{now_utc, Rfc3339};
+use modql::field::{Fields, HasSeaFields, SeaFieldValue};
+use modql::filter::{FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::{Alias, Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+
+// region:    --- EtlRun Types
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    sqlx::Type,
+    SeaFieldValue,
+    derive_more::Display,
+    Deserialize,
+    Serialize,
+)]
+#[sqlx(type_name = "etl_run_status")]
+pub enum EtlRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl EtlRunStatus {
+    /// A finished run is never updated again.
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, EtlRunStatus::Running)
+    }
+}
+
+/// One execution of an ETL pipeline.
+/// `cid` is the user who triggered the run, `mid`/`mtime` the last progress update.
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct EtlRun {
+    pub id: i64,
+
+    // -- Properties
+    pub pipeline_name: String,
+    /// SHA-256 of the configuration the pipeline ran with.
+    pub config_hash: String,
+    pub status: EtlRunStatus,
+    #[serde_as(as = "Rfc3339")]
+    pub start_time: OffsetDateTime,
+    #[serde_as(as = "Option<Rfc3339>")]
+    pub end_time: Option<OffsetDateTime>,
+    pub rows_read: i64,
+    pub rows_written: i64,
+    pub rows_rejected: i64,
+    pub error_msg: Option<String>,
+
+    // -- Timestamps
+    pub cid: i64,
+    #[serde_as(as = "Rfc3339")]
+    pub ctime: OffsetDateTime,
+    pub mid: i64,
+    #[serde_as(as = "Rfc3339")]
+    pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields, Deserialize)]
+pub struct EtlRunForCreate {
+    pub pipeline_name: String,
+    pub config_hash: String,
+}
+
+#[derive(Fields, Deserialize, Default)]
+pub struct EtlRunForUpdate {
+    #[field(cast_as = "etl_run_status")]
+    pub status: Option<EtlRunStatus>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub end_time: Option<OffsetDateTime>,
+    pub rows_read: Option<i64>,
+    pub rows_written: Option<i64>,
+    pub rows_rejected: Option<i64>,
+    pub error_msg: Option<String>,
+}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct EtlRunFilter {
+    pub id: Option<OpValsInt64>,
+
+    pub pipeline_name: Option<OpValsString>,
+    pub config_hash: Option<OpValsString>,
+    #[modql(cast_as = "etl_run_status")]
+    pub status: Option<OpValsString>,
+    #[modql(to_sea_value_fn = "time_to_sea_value")]
+    pub start_time: Option<OpValsValue>,
+    #[modql(to_sea_value_fn = "time_to_sea_value")]
+    pub end_time: Option<OpValsValue>,
+
+    pub cid: Option<OpValsInt64>,
+    #[modql(to_sea_value_fn = "time_to_sea_value")]
+    pub ctime: Option<OpValsValue>,
+    pub mid: Option<OpValsInt64>,
+    #[modql(to_sea_value_fn = "time_to_sea_value")]
+    pub mtime: Option<OpValsValue>,
+}
+
+// endregion: --- EtlRun Types
+
+// region:    --- EtlRunBmc
+
+#[derive(Iden)]
+enum EtlRunIden {
+    Id,
+    Status,
+}
+
+pub struct EtlRunBmc;
+
+impl DbBmc for EtlRunBmc {
+    const TABLE: &'static str = "etl_run";
+}
+
+generate_common_bmc_fns!(
+    Bmc: EtlRunBmc,
+    Entity: EtlRun,
+    ForCreate: EtlRunForCreate,
+    ForUpdate: EtlRunForUpdate,
+    Filter: EtlRunFilter,
+);
+
+impl EtlRunBmc {
+    /// Records the row counts of a running pipeline.
+    pub async fn update_counts(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+        rows_read: i64,
+        rows_written: i64,
+        rows_rejected: i64,
+    ) -> Result<()> {
+        let run_u = EtlRunForUpdate {
+            rows_read: Some(rows_read),
+            rows_written: Some(rows_written),
+            rows_rejected: Some(rows_rejected),
+            ..Default::default()
+        };
+        Self::update(ctx, mm, id, run_u).await
+    }
+
+    /// Sets the final status and the end time of the run, only while it is `Running`.
+    /// Returns false, leaving the run as it is, when it is already finished, e.g. cancelled
+    /// while its pipeline was ending, or when it does not exist.
+    pub async fn finish(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        id: i64,
+        status: EtlRunStatus,
+        error_msg: Option<String>,
+    ) -> Result<bool> {
+        let run_u = EtlRunForUpdate {
+            status: Some(status),
+            end_time: Some(now_utc()),
+            error_msg,
+            ..Default::default()
+        };
+        let mut fields = run_u.not_none_sea_fields();
+        prep_fields_for_update::<Self>(&mut fields, ctx.user_id());
+
+        // -- Build query
+        let mut query = Query::update();
+        query
+            .table(Self::table_ref())
+            .values(fields.for_sea_update())
+            .and_where(Expr::col(EtlRunIden::Id).eq(id))
+            .and_where(Expr::col(EtlRunIden::Status).eq(
+                Expr::val(EtlRunStatus::Running.to_string()).as_enum(Alias::new("etl_run_status")),
+            ));
+
+        // -- Exec query
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+        let sqlx_query = sqlx::query_with(&sql, values);
+        let count = mm.dbx().execute(sqlx_query).await?;
+
+        Ok(count > 0)
+    }
+}
+
+// endregion: --- EtlRunBmc
+
+#[cfg(test)]
+mod tests {
+    type Error = Box<dyn std::error::Error>;
+    type Result<T> = core::result::Result<T, Error>; // For tests.
+
+    use super::*;
+    use crate::_dev_utils;
+    use modql::filter::OpValString;
+    use serial_test::serial;
+
+    #[serial]
+    #[tokio::test]
+    async fn test_create_and_finish_ok() -> Result<()> {
+        // -- Setup & Fixtures
+        let mm = _dev_utils::init_test().await;
+        let ctx = Ctx::root_ctx();
+        let fx_pipeline = "test_create_and_finish_ok jdd_normalisation";
+
+        // -- Exec
+        let run_id = EtlRunBmc::create(
+            &ctx,
+            mm,
+            EtlRunForCreate {
+                pipeline_name: fx_pipeline.to_string(),
+                config_hash: "0123abcd".to_string(),
+            },
+        )
+        .await?;
+        let running = EtlRunBmc::get(&ctx, mm, run_id).await?;
+        EtlRunBmc::update_counts(&ctx, mm, run_id, 57, 55, 2).await?;
+        EtlRunBmc::finish(&ctx, mm, run_id, EtlRunStatus::Succeeded, None).await?;
+
+        // -- Check
+        let run = EtlRunBmc::get(&ctx, mm, run_id).await?;
+        assert_eq!(running.status, EtlRunStatus::Running);
+        assert!(running.end_time.is_none());
+        assert_eq!(run.status, EtlRunStatus::Succeeded);
+        assert_eq!(
+            (run.rows_read, run.rows_written, run.rows_rejected),
+            (57, 55, 2)
+        );
+        assert!(run.end_time.ok_or("run should have an end time")? >= run.start_time);
+
+        // -- Clean
+        EtlRunBmc::delete(&ctx, mm, run_id).await?;
+
+        Ok(())
+    }
+
+    #[serial]
+    #[tokio::test]
+    async fn test_finish_finished_run_unchanged() -> Result<()> {
+        // -- Setup & Fixtures
+        let mm = _dev_utils::init_test().await;
+        let ctx = Ctx::root_ctx();
+        let fx_pipeline = "test_finish_finished_run_unchanged hdd_deduplication";
+        let run_id = EtlRunBmc::create(
+            &ctx,
+            mm,
+            EtlRunForCreate {
+                pipeline_name: fx_pipeline.to_string(),
+                config_hash: "0123abcd".to_string(),
+            },
+        )
+        .await?;
+
+        // -- Exec
+        let cancelled = EtlRunBmc::finish(&ctx, mm, run_id, EtlRunStatus::Cancelled, None).await?;
+        let succeeded = EtlRunBmc::finish(&ctx, mm, run_id, EtlRunStatus::Succeeded, None).await?;
+
+        // -- Check
+        let run = EtlRunBmc::get(&ctx, mm, run_id).await?;
+        assert!(cancelled);
+        assert!(!succeeded);
+        assert_eq!(run.status, EtlRunStatus::Cancelled);
+
+        // -- Clean
+        EtlRunBmc::delete(&ctx, mm, run_id).await?;
+
+        Ok(())
+    }
+
+    #[serial]
+    #[tokio::test]
+    async fn test_list_by_status_ok() -> Result<()> {
+        // -- Setup & Fixtures
+        let mm = _dev_utils::init_test().await;
+        let ctx = Ctx::root_ctx();
+        let fx_pipeline = "test_list_by_status_ok hdd_deduplication";
+        let mut run_ids = Vec::new();
+        for _ in 0..3 {
+            let run_id = EtlRunBmc::create(
+                &ctx,
+                mm,
+                EtlRunForCreate {
+                    pipeline_name: fx_pipeline.to_string(),
+                    config_hash: "0123abcd".to_string(),
+                },
+            )
+            .await?;
+            run_ids.push(run_id);
+        }
+        EtlRunBmc::finish(
+            &ctx,
+            mm,
+            run_ids[0],
+            EtlRunStatus::Failed,
+            Some("connection refused".to_string()),
+        )
+        .await?;
+
+        // -- Exec
+        let failed = EtlRunBmc::list(
+            &ctx,
+            mm,
+            Some(vec![EtlRunFilter {
+                pipeline_name: Some(OpValString::Eq(fx_pipeline.to_string()).into()),
+                status: Some(OpValString::In(vec!["Failed".to_string()]).into()),
+                ..Default::default()
+            }]),
+            None,
+        )
+        .await?;
+
+        // -- Check
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, run_ids[0]);
+        assert_eq!(failed[0].error_msg.as_deref(), Some("connection refused"));
+
+        // -- Clean
+        EtlRunBmc::delete_many(&ctx, mm, run_ids).await?;
+
+        Ok(())
+    }
+}
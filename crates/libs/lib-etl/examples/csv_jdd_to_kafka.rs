@@ -1,6 +1,9 @@
 use lib_etl::config::{Config, IO_CONFIG_PATH};
 use lib_etl::csv::csv_to_json;
-use lib_etl::kafka::push_json_to_kafka;
+use lib_etl::kafka::registry::SchemaRegistryClient;
+use lib_etl::kafka::serializer::SchemaSerializer;
+use lib_etl::kafka::{push_json_to_kafka, push_serialized_json_to_kafka};
+use lib_etl::schemas::SchemasEnum;
 use log::{error, info};
 
 #[tokio::main]
@@ -27,7 +30,34 @@ async fn main() -> Result<(), Box<dyn core::error::Error>> {
 
     info!("CSV converted to JSON successfully");
 
-    if let Err(e) = push_json_to_kafka(&json_objects, &config.kafka, &config.csv.jdd).await {
+    let result = match &config.kafka.schema_registry {
+        Some(registry_config) => {
+            let client = SchemaRegistryClient::new(&registry_config.url);
+            let subject = format!("{}-value", config.kafka.topic);
+            let serializer = SchemaSerializer::register(
+                &client,
+                &subject,
+                SchemasEnum::Jdd,
+                registry_config.format,
+            )
+            .await?;
+            info!(
+                "Schema registered for subject {} with id {:?}",
+                subject,
+                serializer.schema_id()
+            );
+            push_serialized_json_to_kafka(
+                &json_objects,
+                &config.kafka,
+                &config.csv.jdd,
+                &serializer,
+            )
+            .await
+        }
+        None => push_json_to_kafka(&json_objects, &config.kafka, &config.csv.jdd).await,
+    };
+
+    if let Err(e) = result {
         error!("Failed to push JSON to Kafka: {}", e);
         return Err(e);
     }
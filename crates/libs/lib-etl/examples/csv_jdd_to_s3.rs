@@ -0,0 +1,30 @@
+use lib_etl::{
+    config::{Config, IO_CONFIG_PATH},
+    csv::csv_to_dataframe,
+    files::uri::{read_dataframe, write_dataframe},
+    schemas::SchemasEnum,
+};
+use log::info;
+
+const DEFAULT_URI: &str = "s3://etl/jdd/jdd.parquet";
+
+/// Uploads the JDD CSV as Parquet to an S3 bucket (MinIO from docker-compose.yml by default)
+/// and reads it back. The URI, or a local path, can be given as first argument.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn core::error::Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let uri = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_URI.to_string());
+
+    let config = Config::load(IO_CONFIG_PATH)?;
+    let mut df = csv_to_dataframe(&config.csv.jdd.file_path, SchemasEnum::Jdd)?;
+    write_dataframe(&uri, &mut df).await?;
+    info!("{} rows uploaded to {}", df.height(), uri);
+
+    let uploaded = read_dataframe(&uri, Some(SchemasEnum::Jdd)).await?;
+    info!("{} rows read back from {}", uploaded.height(), uri);
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use clap::{Arg, ArgAction, Command};
+use lib_etl::{
+    files::{
+        excel::{ExcelSource, SheetSelector},
+        parquet::ParquetSink,
+    },
+    schemas::SchemasEnum,
+};
+use log::info;
+
+/// Converts a sheet of an Excel workbook to Parquet, with the columns cast to the dataset schema.
+fn main() -> Result<(), Box<dyn core::error::Error>> {
+    env_logger::init();
+    let matches = Command::new("excel to parquet")
+        .about("Converts a sheet of an Excel workbook to Parquet")
+        .arg(
+            Arg::new("input")
+                .value_name("WORKBOOK")
+                .help("Workbook to read (.xlsx, .xlsm, .xlsb or .xls)")
+                .required(true),
+        )
+        .arg(
+            Arg::new("output")
+                .value_name("PARQUET")
+                .help("Parquet file to write")
+                .required(true),
+        )
+        .arg(
+            Arg::new("sheet")
+                .long("sheet")
+                .value_name("SHEET")
+                .help("Sheet name, defaults to the first sheet"),
+        )
+        .arg(
+            Arg::new("header-row")
+                .long("header-row")
+                .value_name("ROW")
+                .value_parser(clap::value_parser!(u32))
+                .help("Row of the column names, starting at 0"),
+        )
+        .arg(
+            Arg::new("range")
+                .long("range")
+                .value_name("RANGE")
+                .help("Cells to read, e.g. A3:AF5000"),
+        )
+        .arg(
+            Arg::new("hdd")
+                .long("hdd")
+                .action(ArgAction::SetTrue)
+                .help("Casts the columns to the HDD schema instead of the JDD one"),
+        )
+        .get_matches();
+
+    let input = matches.get_one::<String>("input").unwrap();
+    let output = matches.get_one::<String>("output").unwrap();
+    let dataset = if matches.get_flag("hdd") {
+        SchemasEnum::Hdd
+    } else {
+        SchemasEnum::Jdd
+    };
+
+    let mut source = ExcelSource::new(input).with_dataset_schema(dataset);
+    if let Some(sheet) = matches.get_one::<String>("sheet") {
+        source = source.with_sheet(SheetSelector::Name(sheet.clone()));
+    }
+    if let Some(header_row) = matches.get_one::<u32>("header-row") {
+        source = source.with_header_row(*header_row);
+    }
+    if let Some(range) = matches.get_one::<String>("range") {
+        source = source.with_cell_range(range)?;
+    }
+
+    let mut df = source.read()?;
+    ParquetSink::new(output).write(&mut df)?;
+    info!("{} rows written to {}", df.height(), output);
+
+    Ok(())
+}
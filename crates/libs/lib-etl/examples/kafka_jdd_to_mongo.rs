@@ -2,6 +2,9 @@ use std::env;
 
 use lib_etl::config::{Config, KafkaMessage, MongoConfig, IO_CONFIG_PATH};
 use lib_etl::kafka::create_kafka_base_consumer;
+use lib_etl::kafka::registry::SchemaRegistryClient;
+use lib_etl::kafka::serializer::SchemaDeserializer;
+use lib_etl::schemas::SchemasEnum;
 use log::{debug, error, info, warn};
 use mongodb::{bson, Client};
 use rdkafka::consumer::{BaseConsumer, Consumer};
@@ -21,6 +24,7 @@ async fn read_messages_from_offset_range(
     partition: i32,
     start_offset: i64,
     end_offset: i64,
+    mut deserializer: Option<SchemaDeserializer>,
 ) -> Vec<KafkaMessage> {
     let mut messages: Vec<KafkaMessage> = Vec::new();
     let mut topic_partition = TopicPartitionList::new();
@@ -34,9 +38,22 @@ async fn read_messages_from_offset_range(
 
     for message in consumer.iter() {
         let msg = message.expect("Failed to get message");
-        let payload = match msg.payload_view::<str>() {
-            Some(Ok(s)) => s.to_string(),
-            _ => {
+        let payload = match (msg.payload(), deserializer.as_mut()) {
+            (Some(bytes), Some(deserializer)) => match deserializer.deserialize(bytes).await {
+                Ok(value) => value.to_string(),
+                Err(e) => {
+                    warn!("Failed to decode payload at offset {}: {}", msg.offset(), e);
+                    continue;
+                }
+            },
+            (Some(bytes), None) => match std::str::from_utf8(bytes) {
+                Ok(s) => s.to_string(),
+                Err(_) => {
+                    warn!("Payload is not valid UTF-8");
+                    continue;
+                }
+            },
+            (None, _) => {
                 warn!("Payload not found");
                 continue;
             }
@@ -110,12 +127,23 @@ async fn main() -> Result<(), Box<dyn core::error::Error>> {
     info!("Configuration loaded successfully");
 
     let consumer = create_kafka_base_consumer(&config.kafka.bootstrap_servers, "group-jdd");
+    let deserializer = config
+        .kafka
+        .schema_registry
+        .as_ref()
+        .map(|registry_config| {
+            SchemaDeserializer::new(
+                SchemaRegistryClient::new(&registry_config.url),
+                SchemasEnum::Jdd,
+            )
+        });
     let messages = read_messages_from_offset_range(
         &consumer,
         &config.kafka.topic,
         0,
         0,
         &config.csv.jdd.number_of_rows - 1,
+        deserializer,
     )
     .await;
     let mongo_uri = format!(
@@ -0,0 +1,48 @@
+use lib_etl::{
+    bus::{kafka::KafkaBus, MessageBus},
+    config::{Config, FILES_PATH, IO_CONFIG_PATH},
+    files::{json::JsonSource, parquet::ParquetSink},
+    schemas::SchemasEnum,
+};
+use log::{error, info};
+use std::{io::Write, time::Duration};
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Dumps the JDD topic to a JSON Lines file, then reloads it with the dataset schema into Parquet.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn core::error::Error + Send + Sync>> {
+    env_logger::init();
+
+    let config = match Config::load(IO_CONFIG_PATH) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return Err(e.to_string().into());
+        }
+    };
+
+    let bus = KafkaBus::new(&config.kafka.bootstrap_servers, "ndjson-dump")?;
+    bus.subscribe(&[&config.kafka.topic])?;
+
+    let ndjson_path = format!("{}{}.jsonl", FILES_PATH, config.kafka.topic);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&ndjson_path)?);
+    let mut count = 0;
+    while let Some(message) = bus.recv(IDLE_TIMEOUT).await? {
+        file.write_all(&message.payload)?;
+        file.write_all(b"\n")?;
+        bus.commit(&message)?;
+        count += 1;
+    }
+    file.flush()?;
+    info!("{} messages dumped to {}", count, ndjson_path);
+
+    let mut df = JsonSource::new(&ndjson_path)
+        .with_dataset_schema(SchemasEnum::Jdd)
+        .read()?;
+    let parquet_path = format!("{}{}.parquet", FILES_PATH, config.kafka.topic);
+    ParquetSink::new(&parquet_path).write(&mut df)?;
+    info!("{} rows written to {}", df.height(), parquet_path);
+
+    Ok(())
+}
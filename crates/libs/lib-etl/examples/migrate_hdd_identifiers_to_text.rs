@@ -0,0 +1,40 @@
+use lib_etl::{
+    config::{Config, IO_CONFIG_PATH},
+    postgres::migration::migrate_float_columns_to_text,
+    schemas::hdd::hdd_identifiers,
+};
+use log::{error, info};
+use sea_query::Iden;
+use sqlx::PgPool;
+
+/// One-off migration of HDD tables created when identifiers were stored as `double precision`.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn core::error::Error>> {
+    env_logger::init();
+    dotenv::dotenv().ok();
+
+    let config = match Config::load(IO_CONFIG_PATH) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("Failed to load configuration: {}", e);
+            return Err(e);
+        }
+    };
+
+    let postgres_url = std::env::var("DATABASE_URL")?;
+    let pool = PgPool::connect(&postgres_url).await?;
+
+    let identifiers = hdd_identifiers()
+        .iter()
+        .map(|column| column.to_string())
+        .collect::<Vec<_>>();
+    let migrated =
+        migrate_float_columns_to_text(&pool, &config.csv.hdd.table_name, &identifiers).await?;
+
+    if migrated.is_empty() {
+        info!("Nothing to migrate, identifiers are already stored as text");
+    } else {
+        info!("Columns migrated to text: {:?}", migrated);
+    }
+    Ok(())
+}
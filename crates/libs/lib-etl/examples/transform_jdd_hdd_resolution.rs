@@ -0,0 +1,33 @@
+use lib_core::{ctx::Ctx, model::ModelManager};
+use lib_etl::config::IO_CONFIG_PATH;
+use lib_etl::pipelines::jdd_hdd_resolution;
+use lib_etl::run::{config_hash, record_run};
+use log::info;
+use sqlx::PgPool;
+use std::env;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn core::error::Error>> {
+    env_logger::init();
+    dotenv::dotenv().ok();
+    // Initialize PostgreSQL connection pool
+    let postgres_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    info!("Database URL: {}", postgres_url);
+    let pool = PgPool::connect(&postgres_url)
+        .await
+        .expect("Postgres connection failed");
+
+    // The run is recorded in the etl_run table of the app database
+    let mm = ModelManager::new().await?;
+    let config_json = std::fs::read(IO_CONFIG_PATH)?;
+    record_run(
+        &Ctx::root_ctx(),
+        &mm,
+        "jdd_hdd_resolution",
+        &config_hash(&config_json),
+        |recorder| async move { jdd_hdd_resolution::run(&pool, &recorder).await },
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use lib_core::{ctx::Ctx, model::ModelManager};
+use lib_etl::{
+    config::{Config, IO_CONFIG_PATH},
+    csv::csv_to_dataframe,
+    postgres::{create_raw_table, sink::PgSink, to_raw_table_dataframe},
+    run::{config_hash, record_run, RunStats},
+    schemas::SchemasEnum,
+    watch::DirectoryWatcher,
+};
+use log::info;
+use sqlx::PgPool;
+
+/// Loads every JDD/HDD CSV dropped in the watch directory into its raw Postgres table,
+/// as `csv_jdd_to_postgres` and `csv_hdd_to_postgres` do. Each file is recorded as a run
+/// in the etl_run table.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn core::error::Error>> {
+    dotenv::dotenv().ok();
+    env_logger::init();
+
+    let config = Config::load(IO_CONFIG_PATH)?;
+    let config_hash = config_hash(&std::fs::read(IO_CONFIG_PATH)?);
+    let watch_config = config
+        .watch
+        .as_ref()
+        .ok_or("No watch section in the configuration")?;
+    let pool = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+    let mm = ModelManager::new().await?;
+    let ctx = Ctx::root_ctx();
+
+    let mut watcher = DirectoryWatcher::new(watch_config)?;
+    watcher
+        .run(|path, dataset| {
+            let pool = pool.clone();
+            let csv_config = match dataset {
+                SchemasEnum::Jdd => &config.csv.jdd,
+                SchemasEnum::Hdd => &config.csv.hdd,
+            };
+            let pipeline_name = format!("watch_{}", dataset.name().to_lowercase());
+            let (ctx, mm, config_hash) = (&ctx, &mm, &config_hash);
+            async move {
+                let stats = record_run(ctx, mm, &pipeline_name, config_hash, |_| async {
+                    let path = path.to_str().ok_or("Non UTF-8 file path")?;
+                    let df = to_raw_table_dataframe(csv_to_dataframe(path, dataset)?, dataset)?;
+                    create_raw_table(&pool, &csv_config.table_name, dataset).await?;
+                    let rows_written = PgSink::new(&csv_config.table_name)
+                        .with_key_columns(&csv_config.natural_key)
+                        .write(&pool, &df)
+                        .await?;
+                    info!("{} rows of {} written", rows_written, path);
+                    Ok(RunStats {
+                        rows_read: df.height() as u64,
+                        rows_written,
+                        rows_rejected: 0,
+                    })
+                })
+                .await?;
+                Ok(stats.rows_written)
+            }
+        })
+        .await
+}
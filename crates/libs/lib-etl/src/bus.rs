@@ -0,0 +1,134 @@
+use crate::config::KafkaMessage;
+use core::error::Error;
+use core::future::Future;
+use log::warn;
+use std::time::Duration;
+
+pub mod kafka;
+pub mod memory;
+
+pub type BusResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+pub const ERROR_HEADER: &str = "error";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BusRecord {
+    pub topic: String,
+    pub key: Option<String>,
+    pub payload: Vec<u8>,
+    pub headers: Vec<(String, Vec<u8>)>,
+    /// Target partition, otherwise chosen from the key (or round-robin without key).
+    pub partition: Option<i32>,
+}
+
+impl BusRecord {
+    pub fn new(topic: &str, payload: impl Into<Vec<u8>>) -> Self {
+        BusRecord {
+            topic: topic.to_string(),
+            payload: payload.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    pub fn with_header(mut self, key: &str, value: impl Into<Vec<u8>>) -> Self {
+        self.headers.push((key.to_string(), value.into()));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusMessage {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    pub key: Option<String>,
+    pub payload: Vec<u8>,
+    pub headers: Vec<(String, Vec<u8>)>,
+}
+
+impl BusMessage {
+    pub fn header(&self, key: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_slice())
+    }
+}
+
+impl From<&BusMessage> for KafkaMessage {
+    fn from(message: &BusMessage) -> Self {
+        KafkaMessage {
+            key: message.key.clone().unwrap_or_default(),
+            value: String::from_utf8_lossy(&message.payload).to_string(),
+            topic: message.topic.clone(),
+            partition: message.partition,
+            offset: message.offset,
+        }
+    }
+}
+
+/// Produce/consume operations shared by the Kafka client and the in-memory bus,
+/// so pipelines can be written once and tested without a broker.
+pub trait MessageBus {
+    /// Publishes a record and returns the `(partition, offset)` it was written at.
+    fn produce(&self, record: BusRecord) -> impl Future<Output = BusResult<(i32, i64)>> + Send;
+
+    fn subscribe(&self, topics: &[&str]) -> BusResult<()>;
+
+    /// Waits at most `timeout` for the next message of the subscribed topics.
+    fn recv(&self, timeout: Duration)
+        -> impl Future<Output = BusResult<Option<BusMessage>>> + Send;
+
+    /// Commits the offset following `message` for the consumer group.
+    fn commit(&self, message: &BusMessage) -> BusResult<()>;
+
+    /// Moves the consumer position so the next message read from the partition is `offset`.
+    fn seek(&self, topic: &str, partition: i32, offset: i64) -> BusResult<()>;
+}
+
+pub enum Processed<T> {
+    Ok(T),
+    DeadLettered,
+}
+
+/// Receives the next message and runs `handler` on it.
+/// A message the handler rejects is republished to `dlq_topic` with the error in the `error` header.
+/// The message is committed in both cases, so it is never processed twice.
+pub async fn process_next<B, T, F>(
+    bus: &B,
+    timeout: Duration,
+    dlq_topic: &str,
+    handler: F,
+) -> BusResult<Option<Processed<T>>>
+where
+    B: MessageBus,
+    F: FnOnce(&BusMessage) -> BusResult<T>,
+{
+    let Some(message) = bus.recv(timeout).await? else {
+        return Ok(None);
+    };
+
+    let processed = match handler(&message) {
+        Ok(value) => Processed::Ok(value),
+        Err(e) => {
+            warn!(
+                "Sending message {}:{}:{} to {}: {}",
+                message.topic, message.partition, message.offset, dlq_topic, e
+            );
+            let mut record = BusRecord::new(dlq_topic, message.payload.clone())
+                .with_header(ERROR_HEADER, e.to_string());
+            record.key = message.key.clone();
+            record.headers.extend(message.headers.iter().cloned());
+            bus.produce(record).await?;
+            Processed::DeadLettered
+        }
+    };
+    bus.commit(&message)?;
+
+    Ok(Some(processed))
+}
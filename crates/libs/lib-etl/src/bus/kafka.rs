@@ -0,0 +1,115 @@
+use super::{BusMessage, BusRecord, BusResult, MessageBus};
+use crate::kafka::CustomContext;
+use rdkafka::{
+    config::{ClientConfig, RDKafkaLogLevel},
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    message::{Header, Headers, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    util::Timeout,
+    Message, Offset, TopicPartitionList,
+};
+use std::time::Duration;
+
+const SEEK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `MessageBus` backed by librdkafka. Offsets are committed explicitly through `commit`.
+pub struct KafkaBus {
+    producer: FutureProducer,
+    consumer: StreamConsumer<CustomContext>,
+}
+
+impl KafkaBus {
+    pub fn new(brokers: &str, group_id: &str) -> BusResult<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()?;
+        let consumer = ClientConfig::new()
+            .set("group.id", group_id)
+            .set("bootstrap.servers", brokers)
+            .set("enable.partition.eof", "false")
+            .set("session.timeout.ms", "6000")
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .set_log_level(RDKafkaLogLevel::Debug)
+            .create_with_context(CustomContext)?;
+        Ok(KafkaBus { producer, consumer })
+    }
+}
+
+impl MessageBus for KafkaBus {
+    async fn produce(&self, record: BusRecord) -> BusResult<(i32, i64)> {
+        let mut headers = OwnedHeaders::new();
+        for (key, value) in &record.headers {
+            headers = headers.insert(Header {
+                key,
+                value: Some(value),
+            });
+        }
+
+        let mut future_record = FutureRecord::to(&record.topic)
+            .payload(&record.payload)
+            .headers(headers);
+        if let Some(key) = &record.key {
+            future_record = future_record.key(key);
+        }
+        if let Some(partition) = record.partition {
+            future_record = future_record.partition(partition);
+        }
+
+        self.producer
+            .send(future_record, Timeout::Never)
+            .await
+            .map_err(|(e, _)| e.into())
+    }
+
+    fn subscribe(&self, topics: &[&str]) -> BusResult<()> {
+        Ok(self.consumer.subscribe(topics)?)
+    }
+
+    async fn recv(&self, timeout: Duration) -> BusResult<Option<BusMessage>> {
+        let Ok(message) = tokio::time::timeout(timeout, self.consumer.recv()).await else {
+            return Ok(None);
+        };
+        let message = message?;
+
+        Ok(Some(BusMessage {
+            topic: message.topic().to_string(),
+            partition: message.partition(),
+            offset: message.offset(),
+            key: message
+                .key()
+                .map(|k| String::from_utf8_lossy(k).to_string()),
+            payload: message.payload().map(<[u8]>::to_vec).unwrap_or_default(),
+            headers: message
+                .headers()
+                .map(|headers| {
+                    headers
+                        .iter()
+                        .map(|h| {
+                            (
+                                h.key.to_string(),
+                                h.value.map(<[u8]>::to_vec).unwrap_or_default(),
+                            )
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }))
+    }
+
+    fn commit(&self, message: &BusMessage) -> BusResult<()> {
+        let mut topic_partition = TopicPartitionList::new();
+        topic_partition.add_partition_offset(
+            &message.topic,
+            message.partition,
+            Offset::Offset(message.offset + 1),
+        )?;
+        Ok(self.consumer.commit(&topic_partition, CommitMode::Async)?)
+    }
+
+    fn seek(&self, topic: &str, partition: i32, offset: i64) -> BusResult<()> {
+        Ok(self
+            .consumer
+            .seek(topic, partition, Offset::Offset(offset), SEEK_TIMEOUT)?)
+    }
+}
@@ -0,0 +1,385 @@
+use super::{BusMessage, BusRecord, BusResult, MessageBus};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const DEFAULT_PARTITIONS: usize = 1;
+
+#[derive(Default)]
+struct Topic {
+    partitions: Vec<Vec<BusMessage>>,
+    next_partition: usize,
+}
+
+#[derive(Default)]
+struct Broker {
+    topics: HashMap<String, Topic>,
+    /// Committed offsets by (group, topic, partition), i.e. the next offset to read.
+    committed: HashMap<(String, String, i32), i64>,
+}
+
+impl Broker {
+    fn topic_mut(&mut self, name: &str) -> &mut Topic {
+        self.topics
+            .entry(name.to_string())
+            .or_insert_with(|| Topic {
+                partitions: vec![Vec::new(); DEFAULT_PARTITIONS],
+                next_partition: 0,
+            })
+    }
+}
+
+#[derive(Default)]
+struct ConsumerState {
+    subscriptions: Vec<String>,
+    /// Next offset to read by (topic, partition).
+    positions: HashMap<(String, i32), i64>,
+    /// Rotates the partition read first, so one busy partition does not starve the others.
+    cursor: usize,
+}
+
+/// In-process message bus keeping topics, partitions and committed offsets in memory.
+///
+/// Handles created with `with_group` share the same broker but keep their own
+/// subscriptions and positions, like separate consumers of a Kafka cluster.
+pub struct InMemoryBus {
+    broker: Arc<Mutex<Broker>>,
+    notify: Arc<Notify>,
+    group_id: String,
+    consumer: Mutex<ConsumerState>,
+}
+
+impl InMemoryBus {
+    pub fn new(group_id: &str) -> Self {
+        InMemoryBus {
+            broker: Arc::new(Mutex::new(Broker::default())),
+            notify: Arc::new(Notify::new()),
+            group_id: group_id.to_string(),
+            consumer: Mutex::new(ConsumerState::default()),
+        }
+    }
+
+    /// Returns a new consumer connected to the same broker.
+    pub fn with_group(&self, group_id: &str) -> Self {
+        InMemoryBus {
+            broker: Arc::clone(&self.broker),
+            notify: Arc::clone(&self.notify),
+            group_id: group_id.to_string(),
+            consumer: Mutex::new(ConsumerState::default()),
+        }
+    }
+
+    pub fn create_topic(&self, name: &str, partitions: usize) -> BusResult<()> {
+        if partitions == 0 {
+            return Err("A topic needs at least one partition".into());
+        }
+        let mut broker = self.lock_broker()?;
+        if broker.topics.contains_key(name) {
+            return Err(format!("Topic {} already exists", name).into());
+        }
+        broker.topics.insert(
+            name.to_string(),
+            Topic {
+                partitions: vec![Vec::new(); partitions],
+                next_partition: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// All messages of a topic, partition by partition.
+    pub fn messages(&self, topic: &str) -> BusResult<Vec<BusMessage>> {
+        let broker = self.lock_broker()?;
+        Ok(broker
+            .topics
+            .get(topic)
+            .map(|t| t.partitions.iter().flatten().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    pub fn committed_offset(&self, topic: &str, partition: i32) -> BusResult<Option<i64>> {
+        let broker = self.lock_broker()?;
+        Ok(broker
+            .committed
+            .get(&(self.group_id.clone(), topic.to_string(), partition))
+            .copied())
+    }
+
+    fn lock_broker(&self) -> BusResult<MutexGuard<'_, Broker>> {
+        self.broker
+            .lock()
+            .map_err(|_| "In-memory broker lock poisoned".into())
+    }
+
+    fn lock_consumer(&self) -> BusResult<MutexGuard<'_, ConsumerState>> {
+        self.consumer
+            .lock()
+            .map_err(|_| "In-memory consumer lock poisoned".into())
+    }
+
+    fn try_next(&self) -> BusResult<Option<BusMessage>> {
+        let mut broker = self.lock_broker()?;
+        let mut consumer = self.lock_consumer()?;
+
+        let mut assignment = Vec::new();
+        for topic in consumer.subscriptions.clone() {
+            let partitions = broker.topic_mut(&topic).partitions.len() as i32;
+            assignment.extend((0..partitions).map(|partition| (topic.clone(), partition)));
+        }
+        if assignment.is_empty() {
+            return Ok(None);
+        }
+
+        let start = consumer.cursor % assignment.len();
+        for (topic, partition) in assignment.iter().cycle().skip(start).take(assignment.len()) {
+            let key = (topic.clone(), *partition);
+            let position = match consumer.positions.get(&key) {
+                Some(position) => *position,
+                None => broker
+                    .committed
+                    .get(&(self.group_id.clone(), topic.clone(), *partition))
+                    .copied()
+                    .unwrap_or(0),
+            };
+            let log = &broker.topic_mut(topic).partitions[*partition as usize];
+            if let Some(message) = log.get(position as usize) {
+                let message = message.clone();
+                consumer.positions.insert(key, position + 1);
+                consumer.cursor = consumer.cursor.wrapping_add(1);
+                return Ok(Some(message));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl MessageBus for InMemoryBus {
+    async fn produce(&self, record: BusRecord) -> BusResult<(i32, i64)> {
+        let (partition, offset) = {
+            let mut broker = self.lock_broker()?;
+            let topic = broker.topic_mut(&record.topic);
+            let partition_count = topic.partitions.len();
+            let partition = match (record.partition, &record.key) {
+                (Some(partition), _) => {
+                    if partition < 0 || partition as usize >= partition_count {
+                        return Err(format!(
+                            "Partition {} does not exist for topic {}",
+                            partition, record.topic
+                        )
+                        .into());
+                    }
+                    partition as usize
+                }
+                (None, Some(key)) => {
+                    let mut hasher = DefaultHasher::new();
+                    key.hash(&mut hasher);
+                    (hasher.finish() % partition_count as u64) as usize
+                }
+                (None, None) => {
+                    let partition = topic.next_partition % partition_count;
+                    topic.next_partition = topic.next_partition.wrapping_add(1);
+                    partition
+                }
+            };
+
+            let log = &mut topic.partitions[partition];
+            let offset = log.len() as i64;
+            log.push(BusMessage {
+                topic: record.topic,
+                partition: partition as i32,
+                offset,
+                key: record.key,
+                payload: record.payload,
+                headers: record.headers,
+            });
+            (partition as i32, offset)
+        };
+        self.notify.notify_waiters();
+
+        Ok((partition, offset))
+    }
+
+    fn subscribe(&self, topics: &[&str]) -> BusResult<()> {
+        let mut consumer = self.lock_consumer()?;
+        consumer.subscriptions = topics.iter().map(|t| t.to_string()).collect();
+        consumer.positions.clear();
+        Ok(())
+    }
+
+    async fn recv(&self, timeout: Duration) -> BusResult<Option<BusMessage>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register interest before checking, so a message produced in between is not missed.
+            let notified = self.notify.notified();
+            if let Some(message) = self.try_next()? {
+                return Ok(Some(message));
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn commit(&self, message: &BusMessage) -> BusResult<()> {
+        let mut broker = self.lock_broker()?;
+        broker.committed.insert(
+            (
+                self.group_id.clone(),
+                message.topic.clone(),
+                message.partition,
+            ),
+            message.offset + 1,
+        );
+        Ok(())
+    }
+
+    fn seek(&self, topic: &str, partition: i32, offset: i64) -> BusResult<()> {
+        if offset < 0 {
+            return Err(format!("Cannot seek to negative offset {}", offset).into());
+        }
+        let mut consumer = self.lock_consumer()?;
+        consumer
+            .positions
+            .insert((topic.to_string(), partition), offset);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{process_next, Processed, ERROR_HEADER};
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_produce_and_recv_in_order() -> BusResult<()> {
+        let bus = InMemoryBus::new("group-test");
+        bus.subscribe(&["jdd"])?;
+
+        for i in 0..3 {
+            let (partition, offset) = bus.produce(BusRecord::new("jdd", format!("{i}"))).await?;
+            assert_eq!((partition, offset), (0, i));
+        }
+
+        for i in 0..3 {
+            let message = bus.recv(TIMEOUT).await?.ok_or("Missing message")?;
+            assert_eq!(message.offset, i);
+            assert_eq!(message.payload, format!("{i}").into_bytes());
+        }
+        assert!(bus.recv(TIMEOUT).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_same_key_same_partition() -> BusResult<()> {
+        let bus = InMemoryBus::new("group-test");
+        bus.create_topic("hdd", 4)?;
+
+        let (first, _) = bus
+            .produce(BusRecord::new("hdd", "a").with_key("42"))
+            .await?;
+        let (second, offset) = bus
+            .produce(BusRecord::new("hdd", "b").with_key("42"))
+            .await?;
+
+        assert_eq!(first, second);
+        assert_eq!(offset, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_commit_resumes_group() -> BusResult<()> {
+        let bus = InMemoryBus::new("group-test");
+        bus.subscribe(&["jdd"])?;
+        for payload in ["a", "b", "c"] {
+            bus.produce(BusRecord::new("jdd", payload)).await?;
+        }
+
+        let message = bus.recv(TIMEOUT).await?.ok_or("Missing message")?;
+        bus.commit(&message)?;
+        assert_eq!(bus.committed_offset("jdd", 0)?, Some(1));
+
+        // A new consumer of the same group starts after the committed offset
+        let restarted = bus.with_group("group-test");
+        restarted.subscribe(&["jdd"])?;
+        let message = restarted.recv(TIMEOUT).await?.ok_or("Missing message")?;
+        assert_eq!(message.payload, b"b");
+
+        // Another group reads from the beginning
+        let other = bus.with_group("group-other");
+        other.subscribe(&["jdd"])?;
+        let message = other.recv(TIMEOUT).await?.ok_or("Missing message")?;
+        assert_eq!(message.payload, b"a");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_seek() -> BusResult<()> {
+        let bus = InMemoryBus::new("group-test");
+        bus.subscribe(&["jdd"])?;
+        for payload in ["a", "b", "c"] {
+            bus.produce(BusRecord::new("jdd", payload)).await?;
+        }
+
+        bus.seek("jdd", 0, 2)?;
+        let message = bus.recv(TIMEOUT).await?.ok_or("Missing message")?;
+        assert_eq!(message.payload, b"c");
+
+        bus.seek("jdd", 0, 0)?;
+        let message = bus.recv(TIMEOUT).await?.ok_or("Missing message")?;
+        assert_eq!(message.payload, b"a");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recv_wakes_up_on_produce() -> BusResult<()> {
+        let bus = InMemoryBus::new("group-test");
+        bus.subscribe(&["jdd"])?;
+
+        let producer = bus.with_group("producer");
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            producer.produce(BusRecord::new("jdd", "late")).await
+        });
+
+        let message = bus
+            .recv(Duration::from_secs(5))
+            .await?
+            .ok_or("Missing message")?;
+        assert_eq!(message.payload, b"late");
+        handle.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_next_dead_letters_failures() -> BusResult<()> {
+        let bus = InMemoryBus::new("group-test");
+        bus.subscribe(&["jdd"])?;
+        bus.produce(BusRecord::new("jdd", "{\"SIRET\": 1}")).await?;
+        bus.produce(BusRecord::new("jdd", "not json")).await?;
+
+        let parse = |message: &BusMessage| -> BusResult<serde_json::Value> {
+            Ok(serde_json::from_slice(&message.payload)?)
+        };
+
+        let first = process_next(&bus, TIMEOUT, "jdd-dlq", parse).await?;
+        assert!(matches!(first, Some(Processed::Ok(_))));
+        let second = process_next(&bus, TIMEOUT, "jdd-dlq", parse).await?;
+        assert!(matches!(second, Some(Processed::DeadLettered)));
+        assert!(process_next(&bus, TIMEOUT, "jdd-dlq", parse)
+            .await?
+            .is_none());
+
+        let dead_letters = bus.messages("jdd-dlq")?;
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].payload, b"not json");
+        assert!(dead_letters[0].header(ERROR_HEADER).is_some());
+        assert_eq!(bus.committed_offset("jdd", 0)?, Some(2));
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::schemas::SchemasEnum;
 use core::error::Error;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
@@ -20,6 +21,24 @@ pub struct KafkaMessage {
 pub struct KafkaConfig {
     pub bootstrap_servers: String,
     pub topic: String,
+    #[serde(default)]
+    pub schema_registry: Option<SchemaRegistryConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadFormat {
+    #[default]
+    Json,
+    Avro,
+    JsonSchema,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchemaRegistryConfig {
+    pub url: String,
+    #[serde(default)]
+    pub format: PayloadFormat,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +46,36 @@ pub struct CsvConfig {
     pub file_path: String,
     pub table_name: String,
     pub number_of_rows: i64,
+    /// Postgres columns identifying a record, used to upsert instead of duplicating rows on reload.
+    #[serde(default)]
+    pub natural_key: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Truncates the table and loads the DataFrame in a single transaction.
+    FullRefresh,
+    /// Inserts new rows and updates the rows matching the natural key.
+    #[default]
+    Upsert,
+    /// Inserts the rows, without natural key.
+    Append,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PgSinkConfig {
+    pub table_name: String,
+    #[serde(default)]
+    pub mode: WriteMode,
+    #[serde(default)]
+    pub natural_key: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PgSinkListConfig {
+    pub jdd: PgSinkConfig,
+    pub hdd: PgSinkConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,11 +96,39 @@ pub struct CsvListConfig {
     pub hdd: CsvConfig,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct WatchRule {
+    /// Glob matched against the file names of the drop directory, e.g. `JDD_*.csv*`.
+    pub pattern: String,
+    pub dataset: SchemasEnum,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchConfig {
+    /// Directory the partners drop their files in. Processed files move to its `archive/`
+    /// subdirectory, failed ones to `error/`.
+    pub drop_dir: String,
+    /// Seconds a file size must stay unchanged before the file is considered fully written.
+    #[serde(default = "default_stable_for_secs")]
+    pub stable_for_secs: u64,
+    /// Waits for a `<file>.done` marker instead of a stable size.
+    #[serde(default)]
+    pub require_done_marker: bool,
+    pub rules: Vec<WatchRule>,
+}
+
+fn default_stable_for_secs() -> u64 {
+    5
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub kafka: KafkaConfig,
     pub csv: CsvListConfig,
     pub mongo: MongoListConfig,
+    pub postgres: PgSinkListConfig,
+    #[serde(default)]
+    pub watch: Option<WatchConfig>,
 }
 
 impl Config {
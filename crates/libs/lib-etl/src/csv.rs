@@ -1,25 +1,50 @@
+use crate::{
+    files::compression::{read_members, Compression},
+    schemas::SchemasEnum,
+};
 use core::error::Error;
 use csv::ReaderBuilder;
+use polars::prelude::{CsvReadOptions, DataFrame, PolarsResult, SerReader};
 use serde_json::{json, Map, Value};
+use std::{fs::File, io::Cursor, io::Read, path::Path, sync::Arc};
 
+/// Members of a zip archive read by default, the CSV files at any depth.
+pub const DEFAULT_CSV_MEMBERS: &str = "*.csv";
+
+/// Reads a `;` separated file into JSON objects, one per record.
+/// `.gz`, `.zst` and `.zip` files are decompressed, all the CSV files of an archive are read.
 pub fn csv_to_json(file_path: &str) -> Result<Vec<Value>, Box<dyn Error>> {
+    let path = Path::new(file_path);
+    let mut json_objects = Vec::new();
+    if Compression::from_path(path).is_none() {
+        let file = File::open(path).map_err(|e| format!("Failed to open CSV file: {}", e))?;
+        csv_records_to_json(file, &mut json_objects)?;
+    } else {
+        for member in read_members(path, DEFAULT_CSV_MEMBERS)? {
+            csv_records_to_json(Cursor::new(member.data), &mut json_objects)
+                .map_err(|e| format!("Failed to read {}: {}", member.name, e))?;
+        }
+    }
+    Ok(json_objects)
+}
+
+fn csv_records_to_json<R: Read>(
+    reader: R,
+    json_objects: &mut Vec<Value>,
+) -> Result<(), Box<dyn Error>> {
     let mut reader = ReaderBuilder::new()
         .has_headers(true)
         .delimiter(b';')
         .comment(Some(b'#'))
-        .from_path(file_path)
-        .map_err(|e| format!("Failed to open CSV file: {}", e))?;
+        .from_reader(reader);
 
     let headers = reader.headers()?.clone();
-    let mut json_objects = Vec::new();
-
     for result in reader.records() {
         let record = result?;
         let json_obj = csv_row_to_json_object(&headers, &record)?;
         json_objects.push(Value::Object(json_obj));
     }
-
-    Ok(json_objects)
+    Ok(())
 }
 
 fn csv_row_to_json_object(
@@ -37,3 +62,50 @@ fn csv_row_to_json_object(
     }
     Ok(json_obj)
 }
+
+/// Reads a `;` separated dataset file into a DataFrame, typed with the dataset schema.
+/// Columns unknown to the schema are kept as strings.
+///
+/// `.gz`, `.zst` and `.zip` files are decompressed, all the CSV files of an archive are stacked.
+pub fn csv_to_dataframe(file_path: &str, se: SchemasEnum) -> PolarsResult<DataFrame> {
+    csv_archive_to_dataframe(file_path, se, DEFAULT_CSV_MEMBERS)
+}
+
+/// Same as [`csv_to_dataframe`], reading only the archive members matching the `members` glob,
+/// e.g. `exports/jdd_*.csv`. The members must share the same columns.
+pub fn csv_archive_to_dataframe(
+    file_path: &str,
+    se: SchemasEnum,
+    members: &str,
+) -> PolarsResult<DataFrame> {
+    let path = Path::new(file_path);
+    if Compression::from_path(path).is_none() {
+        return csv_read_options(se)
+            .try_into_reader_with_file_path(Some(path.into()))?
+            .finish();
+    }
+
+    let mut df: Option<DataFrame> = None;
+    for member in read_members(path, members)? {
+        let member_df = csv_read_options(se)
+            .into_reader_with_file_handle(Cursor::new(member.data))
+            .finish()?;
+        match df.as_mut() {
+            Some(df) => {
+                df.vstack_mut(&member_df)?;
+            }
+            None => df = Some(member_df),
+        }
+    }
+    let mut df = df.unwrap_or_default();
+    df.as_single_chunk_par();
+    Ok(df)
+}
+
+pub(crate) fn csv_read_options(se: SchemasEnum) -> CsvReadOptions {
+    CsvReadOptions::default()
+        .with_has_header(true)
+        .with_infer_schema_length(Some(0))
+        .with_schema_overwrite(Some(Arc::new(se.polars_schema())))
+        .map_parse_options(|options| options.with_separator(b';').with_comment_prefix(Some("#")))
+}
@@ -0,0 +1,23 @@
+//! Deduplication of the records of a dataset.
+//!
+//! Match rules are declared as combinations of column comparisons, see [`rules::MatchRule`],
+//! and applied to any dataset by a [`deduplicator::Deduplicator`].
+//! Columns are compared exactly or by a string similarity, see [`similarity`].
+//!
+//! Only the records sharing a [`blocking::BlockingKey`] are compared, and the matching pairs
+//! are resolved into clusters by union-find, see [`cluster::clusters`].
+//! The value of each column of a merged record is chosen by its [`survivorship::Survivorship`].
+//!
+//! The evidence of the pairs compared, see [`deduplicator::Deduplicator::evidence`], and the
+//! clusters, see [`export`], explain the merges to the data stewards.
+//!
+//! New or changed records can be matched against the existing clusters only, see [`incremental`].
+
+pub mod blocking;
+pub mod cluster;
+pub mod deduplicator;
+pub mod export;
+pub mod incremental;
+pub mod rules;
+pub mod similarity;
+pub mod survivorship;
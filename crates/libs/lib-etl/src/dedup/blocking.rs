@@ -0,0 +1,299 @@
+use super::rules::right_column;
+use log::warn;
+use polars::prelude::*;
+use serde::Deserialize;
+
+const BLOCK: &str = "_block";
+/// Column of the blocking index holding the name of the key.
+pub const BLOCK_KEY: &str = "block_key";
+/// Column of the blocking index holding the value of the key.
+pub const BLOCK_VALUE: &str = "block_value";
+
+/// Part of a blocking key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyPart {
+    /// The whole value of the column.
+    Column(String),
+    /// The first characters of the column.
+    Prefix { column: String, length: usize },
+}
+
+impl KeyPart {
+    fn expr(&self) -> Expr {
+        match self {
+            KeyPart::Column(column) => col(column.as_str()).cast(DataType::String),
+            KeyPart::Prefix { column, length } => col(column.as_str())
+                .cast(DataType::String)
+                .str()
+                .slice(lit(0), lit(*length as u64)),
+        }
+    }
+}
+
+/// Records are only compared with the records sharing one of their blocking keys.
+/// A record with a null key part is not in any block of that key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BlockingKey {
+    pub name: String,
+    pub parts: Vec<KeyPart>,
+}
+
+impl BlockingKey {
+    pub fn new(name: &str) -> Self {
+        BlockingKey {
+            name: name.to_string(),
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn with_column(mut self, column: &str) -> Self {
+        self.parts.push(KeyPart::Column(column.to_string()));
+        self
+    }
+
+    pub fn with_prefix(mut self, column: &str, length: usize) -> Self {
+        self.parts.push(KeyPart::Prefix {
+            column: column.to_string(),
+            length,
+        });
+        self
+    }
+
+    /// Text key of a record, null when any part is null.
+    pub fn expr(&self) -> Expr {
+        concat_str(
+            self.parts.iter().map(KeyPart::expr).collect::<Vec<_>>(),
+            "|",
+            false,
+        )
+    }
+}
+
+/// Blocking index of the records of `lf`: one `(block_key, block_value, id_column)` row
+/// per key of each record, so the records sharing a block with new records can be found
+/// without computing the keys of the whole dataset again.
+pub fn blocking_index(
+    lf: LazyFrame,
+    id_column: &str,
+    keys: &[BlockingKey],
+) -> PolarsResult<LazyFrame> {
+    let lfs_index = keys
+        .iter()
+        .map(|key| {
+            lf.clone()
+                .select([
+                    lit(key.name.as_str()).alias(BLOCK_KEY),
+                    key.expr().alias(BLOCK_VALUE),
+                    col(id_column),
+                ])
+                .filter(col(BLOCK_VALUE).is_not_null())
+        })
+        .collect::<Vec<_>>();
+    concat(lfs_index, UnionArgs::default())
+}
+
+/// Candidate pairs of `lf` sharing at least one blocking key, each pair once with the left id
+/// less than the right id. The pairs hold the columns of both records, the right ones suffixed.
+///
+/// Blocks larger than `max_block_size` are skipped: their key is too common to tell records apart.
+pub fn candidate_pairs(
+    lf: LazyFrame,
+    id_column: &str,
+    keys: &[BlockingKey],
+    max_block_size: Option<usize>,
+) -> PolarsResult<LazyFrame> {
+    let mut lf = lf;
+    let schema = lf.collect_schema()?;
+    let right_id = right_column(id_column);
+
+    let lfs_key_pairs = keys
+        .iter()
+        .map(|key| {
+            let mut lf_blocked = lf
+                .clone()
+                .select([col(id_column), key.expr().alias(BLOCK)])
+                .filter(col(BLOCK).is_not_null());
+            if let Some(max_block_size) = max_block_size {
+                let lf_sized =
+                    lf_blocked.with_column(len().over([col(BLOCK)]).alias("_block_size"));
+                let oversized = lf_sized
+                    .clone()
+                    .filter(col("_block_size").gt(lit(max_block_size as u32)))
+                    .select([col(BLOCK).n_unique()])
+                    .collect()?;
+                let oversized = oversized
+                    .column(BLOCK)?
+                    .get(0)?
+                    .extract::<u32>()
+                    .unwrap_or(0);
+                if oversized > 0 {
+                    warn!(
+                        "{} blocks of key {} larger than {} records skipped",
+                        oversized, key.name, max_block_size
+                    );
+                }
+                lf_blocked = lf_sized
+                    .filter(col("_block_size").lt_eq(lit(max_block_size as u32)))
+                    .select([col(id_column), col(BLOCK)]);
+            }
+            Ok(lf_blocked
+                .clone()
+                .join(
+                    lf_blocked,
+                    [col(BLOCK)],
+                    [col(BLOCK)],
+                    JoinArgs::new(JoinType::Inner),
+                )
+                .filter(col(id_column).lt(col(right_id.as_str())))
+                .select([col(id_column), col(right_id.as_str())]))
+        })
+        .collect::<PolarsResult<Vec<_>>>()?;
+
+    let lf_pairs =
+        concat(lfs_key_pairs, UnionArgs::default())?.unique(None, UniqueKeepStrategy::First);
+    let lf_right = lf.clone().select(
+        schema
+            .iter_names()
+            .map(|name| col(name.as_str()).alias(right_column(name)))
+            .collect::<Vec<_>>(),
+    );
+    Ok(lf_pairs
+        .join(
+            lf,
+            [col(id_column)],
+            [col(id_column)],
+            JoinArgs::new(JoinType::Inner),
+        )
+        .join(
+            lf_right,
+            [col(right_id.as_str())],
+            [col(right_id.as_str())],
+            JoinArgs::new(JoinType::Inner),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    #[test]
+    fn test_blocking_key_expr() {
+        let df = df![
+            "Nom" => &[Some("SALLANDIER"), Some("SALANDIER"), None],
+            "CP" => &[Some("75011"), Some("75011"), Some("75011")],
+        ]
+        .unwrap();
+        let key = BlockingKey::new("nom_cp")
+            .with_prefix("Nom", 3)
+            .with_column("CP");
+        let keys = df
+            .lazy()
+            .select([key.expr().alias("key")])
+            .collect()
+            .unwrap();
+        let keys = keys
+            .column("key")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|key| key.map(String::from))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            keys,
+            vec![
+                Some("SAL|75011".to_string()),
+                Some("SAL|75011".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blocking_index() {
+        let df = df![
+            "ID" => &[1i64, 2],
+            "Nom" => &[Some("SALLANDIER"), None],
+            "CP" => &[Some("75011"), Some("69001")],
+        ]
+        .unwrap();
+        let keys = [
+            BlockingKey::new("nom_prefix").with_prefix("Nom", 3),
+            BlockingKey::new("cp").with_column("CP"),
+        ];
+        let df_index = blocking_index(df.lazy(), "ID", &keys)
+            .unwrap()
+            .collect()
+            .unwrap();
+
+        let values = |column: &str| {
+            df_index
+                .column(column)
+                .unwrap()
+                .str()
+                .unwrap()
+                .into_no_null_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        // The null Nom is not in any block of the prefix key
+        assert_eq!(values(BLOCK_KEY), vec!["nom_prefix", "cp", "cp"]);
+        assert_eq!(values(BLOCK_VALUE), vec!["SAL", "75011", "69001"]);
+    }
+
+    #[test]
+    fn test_candidate_pairs() {
+        let df = df![
+            "ID" => &[1i64, 2, 3, 4],
+            "Nom" => &["SALLANDIER", "SALANDIER", "DURAND", "DURANT"],
+            "Email" => &[Some("b@x.fr"), None, None, Some("b@x.fr")],
+        ]
+        .unwrap();
+        let keys = [
+            BlockingKey::new("nom_prefix").with_prefix("Nom", 3),
+            BlockingKey::new("email").with_column("Email"),
+        ];
+        let df_pairs = candidate_pairs(df.lazy(), "ID", &keys, None)
+            .unwrap()
+            .sort(["ID", "ID_right"], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        let ids = |column: &str| {
+            df_pairs
+                .column(column)
+                .unwrap()
+                .i64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("ID"), vec![1, 1, 3]);
+        assert_eq!(ids("ID_right"), vec![2, 4, 4]);
+        assert!(df_pairs.column("Nom_right").is_ok());
+    }
+
+    #[test]
+    fn test_candidate_pairs_max_block_size() {
+        let df = df![
+            "ID" => &[1i64, 2, 3, 4],
+            "Nom" => &["DURAND", "DURAND", "DURAND", "MARTIN"],
+            "Prenom" => &["ANNE", "ANNE", "PAUL", "LUC"],
+        ]
+        .unwrap();
+        let keys = [
+            BlockingKey::new("nom").with_column("Nom"),
+            BlockingKey::new("prenom").with_column("Prenom"),
+        ];
+        let df_pairs = candidate_pairs(df.lazy(), "ID", &keys, Some(2))
+            .unwrap()
+            .collect()
+            .unwrap();
+
+        // The DURAND block is too large, only the ANNE one is kept
+        assert_eq!(df_pairs.height(), 1);
+    }
+}
@@ -0,0 +1,126 @@
+use polars::prelude::*;
+use std::collections::HashMap;
+
+/// Column holding the cluster of a record: the smallest id of the records it is a duplicate of,
+/// directly or through other records, so it does not change when the rows are reordered.
+pub const CLUSTER_ID: &str = "cluster_id";
+
+/// Disjoint sets of `0..n`, the root of a set being its smallest element.
+pub struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    pub fn new(n: usize) -> Self {
+        UnionFind {
+            parents: (0..n).collect(),
+        }
+    }
+
+    pub fn find(&mut self, element: usize) -> usize {
+        let mut root = element;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        // Path compression
+        let mut current = element;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    pub fn union(&mut self, left: usize, right: usize) {
+        let left_root = self.find(left);
+        let right_root = self.find(right);
+        if left_root < right_root {
+            self.parents[right_root] = left_root;
+        } else if right_root < left_root {
+            self.parents[left_root] = right_root;
+        }
+    }
+}
+
+/// Connected components of the records linked by the `(left, right)` pairs of ids.
+/// Returns `(id_column, cluster_id)` for every non-null id of `ids`, with the dtype of the ids.
+pub fn clusters(
+    ids: &Column,
+    lefts: &Column,
+    rights: &Column,
+    id_column: &str,
+) -> PolarsResult<DataFrame> {
+    // Sorted, the position of an id gives the smallest id of the cluster as the root
+    let sorted_ids = ids
+        .as_materialized_series()
+        .drop_nulls()
+        .sort(SortOptions::default())?;
+    let sorted_text_ids = sorted_ids.cast(&DataType::String)?;
+    let positions = sorted_text_ids
+        .str()?
+        .into_no_null_iter()
+        .enumerate()
+        .map(|(position, id)| (id, position))
+        .collect::<HashMap<_, _>>();
+
+    let mut union_find = UnionFind::new(sorted_ids.len());
+    let lefts = lefts.cast(&DataType::String)?;
+    let rights = rights.cast(&DataType::String)?;
+    for (left, right) in lefts.str()?.into_iter().zip(rights.str()?) {
+        let pair = left
+            .and_then(|left| positions.get(left))
+            .zip(right.and_then(|right| positions.get(right)));
+        if let Some((&left, &right)) = pair {
+            union_find.union(left, right);
+        }
+    }
+
+    let roots = (0..sorted_ids.len())
+        .map(|position| union_find.find(position) as IdxSize)
+        .collect::<Vec<_>>();
+    let cluster_ids = sorted_ids.take(&IdxCa::from_vec(CLUSTER_ID.into(), roots))?;
+    DataFrame::new(vec![
+        sorted_ids.with_name(id_column.into()).into_column(),
+        cluster_ids.with_name(CLUSTER_ID.into()).into_column(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_union_find() {
+        let mut union_find = UnionFind::new(6);
+        union_find.union(4, 2);
+        union_find.union(2, 5);
+        union_find.union(1, 3);
+
+        let roots = (0..6)
+            .map(|element| union_find.find(element))
+            .collect::<Vec<_>>();
+        assert_eq!(roots, vec![0, 1, 2, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_clusters_transitive_and_stable() {
+        let ids = Column::new("ID".into(), [10i64, 9, 3, 42, 7]);
+        let lefts = Column::new("left_id".into(), [9i64, 3]);
+        let rights = Column::new("right_id".into(), [10i64, 10]);
+        let df_clusters = clusters(&ids, &lefts, &rights, "ID").unwrap();
+
+        let values = |column: &str| {
+            df_clusters
+                .column(column)
+                .unwrap()
+                .i64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<_>>()
+        };
+        // 3-10 and 9-10 put 3, 9 and 10 in the cluster of 3, compared as numbers
+        assert_eq!(values("ID"), vec![3, 7, 9, 10, 42]);
+        assert_eq!(values(CLUSTER_ID), vec![3, 7, 3, 3, 42]);
+    }
+}
@@ -0,0 +1,749 @@
+use super::blocking::{candidate_pairs, BlockingKey};
+use super::cluster::{clusters, CLUSTER_ID};
+use super::rules::{right_column, score_column, FieldMatch, MatchRule, RIGHT_SUFFIX};
+use super::survivorship::{provenance_expr, Survivorship};
+use log::info;
+use polars::prelude::*;
+
+/// Column of the pairs holding the id of the left record.
+pub const LEFT_ID: &str = "left_id";
+/// Column of the pairs holding the id of the right record, greater than the left one.
+pub const RIGHT_ID: &str = "right_id";
+/// Column of the pairs holding the name of the first rule the pair matched.
+pub const RULE_MATCHED: &str = "rule_matched";
+/// Column of the pairs holding the mean of the field scores of the rule matched.
+pub const SCORE: &str = "score";
+/// Column of the evidence holding [`MATCH`] or [`NO_MATCH`].
+pub const DECISION: &str = "decision";
+/// Decision of the pairs matching a rule, merged into the same cluster.
+pub const MATCH: &str = "match";
+/// Decision of the pairs compared by the rules without matching any.
+pub const NO_MATCH: &str = "no_match";
+/// Position of the rule a pair was compared by, the first rules taking precedence.
+const RULE_INDEX: &str = "_rule_index";
+
+/// Finds the records of a dataset matching each other by any of its rules, and merges them.
+pub struct Deduplicator {
+    id_column: String,
+    ids_column: String,
+    separator: String,
+    rules: Vec<MatchRule>,
+    blocking_keys: Vec<BlockingKey>,
+    max_block_size: Option<usize>,
+    survivorships: Vec<(String, Survivorship)>,
+    provenance_column: String,
+}
+
+impl Deduplicator {
+    /// `id_column` identifies the records, e.g. `ID`.
+    pub fn new(id_column: &str) -> Self {
+        Deduplicator {
+            id_column: id_column.to_string(),
+            ids_column: "IDS".to_string(),
+            separator: "/".to_string(),
+            rules: Vec::new(),
+            blocking_keys: Vec::new(),
+            max_block_size: None,
+            survivorships: Vec::new(),
+            provenance_column: "Provenance".to_string(),
+        }
+    }
+
+    /// Rules are tried in order, a pair is reported with the first one it matches.
+    pub fn with_rule(mut self, rule: MatchRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Only the records sharing a blocking key are compared.
+    /// Without blocking keys, each rule compares the records sharing its exact required fields,
+    /// or every pair of records when it has none.
+    pub fn with_blocking_key(mut self, key: BlockingKey) -> Self {
+        self.blocking_keys.push(key);
+        self
+    }
+
+    /// Skips the blocks with more records, see [`candidate_pairs`].
+    pub fn with_max_block_size(mut self, max_block_size: usize) -> Self {
+        self.max_block_size = Some(max_block_size);
+        self
+    }
+
+    /// Column of the merged records listing the ids they were merged from. Defaults to `IDS`.
+    pub fn with_ids_column(mut self, ids_column: &str) -> Self {
+        self.ids_column = ids_column.to_string();
+        self
+    }
+
+    /// Separator of the text values of the merged records. Defaults to `/`.
+    pub fn with_separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Chooses the value of `column` of the merged records by `survivorship`,
+    /// instead of joining the distinct values.
+    pub fn with_survivorship(mut self, column: &str, survivorship: Survivorship) -> Self {
+        self.survivorships.retain(|(name, _)| name != column);
+        self.survivorships.push((column.to_string(), survivorship));
+        self
+    }
+
+    /// Column of the merged records mapping the columns chosen by survivorship to the id
+    /// of the record they were taken from. Defaults to `Provenance`.
+    pub fn with_provenance_column(mut self, provenance_column: &str) -> Self {
+        self.provenance_column = provenance_column.to_string();
+        self
+    }
+
+    pub fn id_column(&self) -> &str {
+        &self.id_column
+    }
+
+    pub fn blocking_keys(&self) -> &[BlockingKey] {
+        &self.blocking_keys
+    }
+
+    pub fn separator(&self) -> &str {
+        &self.separator
+    }
+
+    fn survivorship(&self, column: &str) -> Option<&Survivorship> {
+        self.survivorships
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, survivorship)| survivorship)
+    }
+
+    /// Fields scored for every pair: the first field comparing each column, in the order of the rules.
+    fn scored_fields(&self) -> Vec<&FieldMatch> {
+        let mut fields: Vec<&FieldMatch> = Vec::new();
+        for field in self.rules.iter().flat_map(MatchRule::fields) {
+            if !fields.iter().any(|scored| scored.column == field.column) {
+                fields.push(field);
+            }
+        }
+        fields
+    }
+
+    /// Matching pairs as `(left_id, right_id, rule_matched, score)`, each pair once,
+    /// followed by the score of every compared column, e.g. `Nom_score`.
+    /// A column is scored by the comparator of the rule matched, or by its first comparator
+    /// when the rule does not compare it.
+    pub fn pairs(&self, lf: LazyFrame) -> PolarsResult<LazyFrame> {
+        self.compared_pairs(lf, false)
+    }
+
+    /// Every pair compared by the rules, as the [`Deduplicator::pairs`] columns followed by
+    /// the `decision`, so a data steward can check why records were merged or not.
+    ///
+    /// The pairs matching no rule have a null `rule_matched`, and the scores of the first rule
+    /// that compared them.
+    pub fn evidence(&self, lf: LazyFrame) -> PolarsResult<LazyFrame> {
+        Ok(self.compared_pairs(lf, true)?.with_column(
+            when(col(RULE_MATCHED).is_not_null())
+                .then(lit(MATCH))
+                .otherwise(lit(NO_MATCH))
+                .alias(DECISION),
+        ))
+    }
+
+    /// Pairs compared by the rules, only the matching ones unless `keep_no_match`.
+    fn compared_pairs(&self, lf: LazyFrame, keep_no_match: bool) -> PolarsResult<LazyFrame> {
+        if self.rules.is_empty() {
+            return Err(PolarsError::ComputeError(
+                "Deduplicator needs at least one match rule".into(),
+            ));
+        }
+        let right_id = right_column(&self.id_column);
+        let scored_fields = self.scored_fields();
+        let lf_blocked = if self.blocking_keys.is_empty() {
+            None
+        } else {
+            Some(candidate_pairs(
+                lf.clone(),
+                &self.id_column,
+                &self.blocking_keys,
+                self.max_block_size,
+            )?)
+        };
+        let lfs_rule_pairs = self
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let lf_candidates = match &lf_blocked {
+                    Some(lf_blocked) => lf_blocked.clone(),
+                    None => self.rule_candidates(lf.clone(), rule),
+                };
+                // Less than keeps each pair once, and never a record with itself
+                let mut filter = col(self.id_column.as_str()).lt(col(right_id.as_str()));
+                if !keep_no_match {
+                    filter = filter.and(rule.pair_expr());
+                }
+                lf_candidates.filter(filter).select(
+                    [
+                        col(self.id_column.as_str()).alias(LEFT_ID),
+                        col(right_id.as_str()).alias(RIGHT_ID),
+                        when(rule.pair_expr())
+                            .then(lit(rule.name.as_str()))
+                            .otherwise(lit(NULL).cast(DataType::String))
+                            .alias(RULE_MATCHED),
+                        lit(index as u32).alias(RULE_INDEX),
+                        rule.score_expr().alias(SCORE),
+                    ]
+                    .into_iter()
+                    .chain(self.field_score_exprs(rule, &scored_fields))
+                    .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let exprs_first = [RULE_MATCHED, SCORE]
+            .into_iter()
+            .map(String::from)
+            .chain(
+                scored_fields
+                    .iter()
+                    .map(|field| score_column(&field.column)),
+            )
+            // The first rule matched, or the first rule compared when none matched
+            .map(|column| {
+                col(column.as_str())
+                    .sort_by(
+                        [col(RULE_MATCHED).is_null(), col(RULE_INDEX)],
+                        SortMultipleOptions::default(),
+                    )
+                    .first()
+            })
+            .collect::<Vec<_>>();
+        Ok(concat(lfs_rule_pairs, UnionArgs::default())?
+            .group_by_stable([col(LEFT_ID), col(RIGHT_ID)])
+            .agg(exprs_first))
+    }
+
+    /// Pairs of the records meeting the conditions of `rule` and sharing its join keys.
+    fn rule_candidates(&self, lf: LazyFrame, rule: &MatchRule) -> LazyFrame {
+        let lf_rule = match rule.condition_expr() {
+            Some(condition) => lf.filter(condition),
+            None => lf,
+        };
+        let join_keys = rule.join_keys().into_iter().map(col).collect::<Vec<_>>();
+        if join_keys.is_empty() {
+            lf_rule
+                .clone()
+                .cross_join(lf_rule, Some(PlSmallStr::from(RIGHT_SUFFIX)))
+        } else {
+            // The right join keys are kept for the scores
+            lf_rule.clone().join(
+                lf_rule,
+                join_keys.clone(),
+                join_keys,
+                JoinArgs::new(JoinType::Inner).with_coalesce(JoinCoalesce::KeepColumns),
+            )
+        }
+    }
+
+    /// Scores of the scored fields for the pairs of `rule`, in the order of `scored_fields`.
+    fn field_score_exprs(&self, rule: &MatchRule, scored_fields: &[&FieldMatch]) -> Vec<Expr> {
+        let rule_scores = rule.field_score_exprs();
+        scored_fields
+            .iter()
+            .map(|scored| {
+                match rule
+                    .fields()
+                    .position(|field| field.column == scored.column)
+                {
+                    Some(position) => rule_scores[position].clone(),
+                    None => scored.score_expr().alias(score_column(&scored.column)),
+                }
+            })
+            .collect()
+    }
+
+    /// `(id_column, cluster_id)` of every record of `lf`, the records linked by matching pairs,
+    /// directly or not, sharing a cluster.
+    pub fn clusters(&self, lf: LazyFrame) -> PolarsResult<DataFrame> {
+        let df_pairs = self.pairs(lf.clone())?.collect()?;
+        self.clusters_of_pairs(lf, &df_pairs)
+    }
+
+    /// Clusters of the records of `lf` linked by `df_pairs`, e.g. the matching pairs
+    /// of an evidence already computed.
+    pub fn clusters_of_pairs(
+        &self,
+        lf: LazyFrame,
+        df_pairs: &DataFrame,
+    ) -> PolarsResult<DataFrame> {
+        let df_ids = lf.select([col(self.id_column.as_str())]).collect()?;
+        let df_clusters = clusters(
+            df_ids.column(&self.id_column)?,
+            df_pairs.column(LEFT_ID)?,
+            df_pairs.column(RIGHT_ID)?,
+            &self.id_column,
+        )?;
+        info!(
+            "{} duplicate pairs form {} clusters of {} records",
+            df_pairs.height(),
+            df_clusters.column(CLUSTER_ID)?.n_unique()?,
+            df_clusters.height()
+        );
+        Ok(df_clusters)
+    }
+
+    /// Adds the `cluster_id` column to the records of `lf`.
+    pub fn assign_clusters(&self, lf: LazyFrame) -> PolarsResult<LazyFrame> {
+        let df_clusters = self.clusters(lf.clone())?;
+        Ok(self.join_clusters(lf, df_clusters))
+    }
+
+    fn join_clusters(&self, lf: LazyFrame, df_clusters: DataFrame) -> LazyFrame {
+        lf.join(
+            df_clusters.lazy(),
+            [col(self.id_column.as_str())],
+            [col(self.id_column.as_str())],
+            JoinArgs::new(JoinType::Left),
+        )
+    }
+
+    /// Replaces the records of each cluster of duplicates with one merged record.
+    ///
+    /// The merged record keeps the cluster id as id and the value chosen by the survivorship
+    /// of each column, see [`Deduplicator::with_survivorship`]. Without survivorship, it keeps
+    /// the distinct text values joined by the separator, and the first value of the other columns.
+    ///
+    /// Its ids column lists the merged ids, and its provenance column gives the record each
+    /// survivorship column was taken from as a JSON object, e.g. `{"Email":"2"}`.
+    /// Both are null for the records without duplicates.
+    pub fn deduplicate(&self, lf: LazyFrame) -> PolarsResult<LazyFrame> {
+        let df_clusters = self.clusters(lf.clone())?;
+        self.merge_clusters(lf, df_clusters)
+    }
+
+    /// Merges the records of `lf` by the `(id_column, cluster_id)` of `df_clusters`,
+    /// as [`Deduplicator::deduplicate`] does.
+    pub fn merge_clusters(&self, lf: LazyFrame, df_clusters: DataFrame) -> PolarsResult<LazyFrame> {
+        let mut lf = lf;
+        let schema = lf.collect_schema()?;
+        let survivorship_columns = schema
+            .iter_names()
+            .filter(|name| name.as_str() != self.id_column && self.survivorship(name).is_some())
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        let lf_clustered = self
+            .join_clusters(lf, df_clusters)
+            .with_column(len().over([col(CLUSTER_ID)]).alias("_cluster_size"))
+            .with_columns(
+                self.survivorships
+                    .iter()
+                    .filter(|(column, _)| survivorship_columns.contains(column))
+                    .filter_map(|(column, survivorship)| {
+                        survivorship.prepare_expr(column, CLUSTER_ID)
+                    })
+                    .collect::<Vec<_>>(),
+            );
+
+        let lf_unique = lf_clustered
+            .clone()
+            .filter(col("_cluster_size").eq(lit(1)))
+            .with_columns(
+                [
+                    lit(NULL)
+                        .cast(DataType::String)
+                        .alias(self.ids_column.as_str()),
+                    lit(NULL)
+                        .cast(DataType::String)
+                        .alias(self.provenance_column.as_str()),
+                ]
+                .into_iter()
+                // A kept list holds the only value
+                .chain(schema.iter().filter_map(|(name, dtype)| {
+                    match self.survivorship(name) {
+                        Some(Survivorship::KeepAll) => Some(
+                            col(name.as_str())
+                                .cast(DataType::List(Box::new(dtype.clone())))
+                                .alias(name.as_str()),
+                        ),
+                        _ => None,
+                    }
+                }))
+                .collect::<Vec<_>>(),
+            );
+
+        let exprs_merge = schema
+            .iter()
+            .filter(|(name, _)| name.as_str() != self.id_column)
+            .map(|(name, dtype)| match (self.survivorship(name), dtype) {
+                (Some(survivorship), _) => survivorship.value_expr(name, &self.id_column),
+                // All null stays null instead of an empty text
+                (None, DataType::String) => when(col(name.as_str()).null_count().lt(len()))
+                    .then(
+                        col(name.as_str())
+                            .drop_nulls()
+                            .unique_stable()
+                            .str()
+                            .join(&self.separator, true),
+                    )
+                    .otherwise(lit(NULL).cast(DataType::String))
+                    .alias(name.as_str()),
+                (None, _) => col(name.as_str()).first(),
+            })
+            .chain(survivorship_columns.iter().filter_map(|column| {
+                self.survivorship(column).map(|survivorship| {
+                    survivorship.source_expr(column, &self.id_column, &self.separator)
+                })
+            }))
+            .chain([col(self.id_column.as_str())
+                .sort(SortOptions::default())
+                .cast(DataType::String)
+                .str()
+                .join(&self.separator, true)
+                .alias(self.ids_column.as_str())])
+            .collect::<Vec<_>>();
+        let lf_merged = lf_clustered
+            .filter(col("_cluster_size").gt(lit(1)))
+            .group_by_stable([col(CLUSTER_ID)])
+            .agg(exprs_merge)
+            .with_columns([
+                col(CLUSTER_ID).alias(self.id_column.as_str()),
+                if survivorship_columns.is_empty() {
+                    lit(NULL).cast(DataType::String)
+                } else {
+                    provenance_expr(&survivorship_columns)
+                }
+                .alias(self.provenance_column.as_str()),
+            ]);
+
+        let exprs_columns_to_select = schema
+            .iter_names()
+            .map(|name| col(name.as_str()))
+            .chain([
+                col(self.ids_column.as_str()),
+                col(self.provenance_column.as_str()),
+            ])
+            .collect::<Vec<_>>();
+        concat(
+            [
+                lf_unique.select(&exprs_columns_to_select),
+                lf_merged.select(&exprs_columns_to_select),
+            ],
+            UnionArgs::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::rules::{Comparator, Condition, NullPolicy};
+    use polars::df;
+
+    fn df_people() -> DataFrame {
+        df![
+            "ID" => &["1", "2", "3", "4", "5"],
+            "Nom" => &[Some("DUPONT"), Some("DUPONT"), Some("DUPONT"), Some("MARTIN"), Some("MARTIN")],
+            "Prenom" => &[Some("JEAN"), None, Some("PAUL"), Some("ANNE"), Some("ANNE")],
+            "Email" => &[Some("j@x.fr"), Some("j@x.fr"), Some("j@x.fr"), Some("a@x.fr"), Some("b@x.fr")],
+            "Telephone" => &[Some("0601"), Some("0602"), None, Some("0700"), Some("0700")],
+            "SIRET" => &[None, None, None, None, Some("44316952400120")],
+        ]
+        .unwrap()
+    }
+
+    fn same_person() -> MatchRule {
+        MatchRule::new("same_person")
+            .with_condition(Condition::IsNull("SIRET".to_string()))
+            .with_required(FieldMatch::exact("Nom"))
+            .with_required(FieldMatch::exact("Prenom").with_nulls(NullPolicy::Match))
+            .with_optional(FieldMatch::exact("Email"))
+            .with_optional(FieldMatch::exact("Telephone"))
+    }
+
+    fn str_values(df: &DataFrame, column: &str) -> Vec<Option<String>> {
+        df.column(column)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|value| value.map(|value| value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_pairs() {
+        let df_pairs = Deduplicator::new("ID")
+            .with_rule(same_person())
+            .pairs(df_people().lazy())
+            .unwrap()
+            .sort([LEFT_ID, RIGHT_ID], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        // 1-3 have different first names, 4-5 is excluded by the SIRET condition
+        assert_eq!(
+            str_values(&df_pairs, LEFT_ID),
+            vec![Some("1".to_string()), Some("2".to_string())]
+        );
+        assert_eq!(
+            str_values(&df_pairs, RIGHT_ID),
+            vec![Some("2".to_string()), Some("3".to_string())]
+        );
+        assert_eq!(
+            str_values(&df_pairs, RULE_MATCHED),
+            vec![Some("same_person".to_string()); 2]
+        );
+    }
+
+    #[test]
+    fn test_pairs_first_rule_matched() {
+        let df_pairs = Deduplicator::new("ID")
+            .with_rule(same_person())
+            .with_rule(MatchRule::new("same_email").with_required(FieldMatch::exact("Email")))
+            .pairs(df_people().lazy())
+            .unwrap()
+            .sort([LEFT_ID, RIGHT_ID], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        assert_eq!(
+            str_values(&df_pairs, RULE_MATCHED),
+            vec![
+                Some("same_person".to_string()),
+                Some("same_email".to_string()),
+                Some("same_person".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pairs_fuzzy_scores() {
+        let df = df![
+            "ID" => &[1i64, 2, 3],
+            "Nom" => &["SALLANDIER", "SALANDIER", "DURAND"],
+            "Prenom" => &["Benoît", "Benoit", "Benoît"],
+        ]
+        .unwrap();
+        let df_pairs = Deduplicator::new("ID")
+            .with_rule(
+                MatchRule::new("similar_person")
+                    .with_required(FieldMatch::new("Nom", Comparator::JaroWinkler))
+                    .with_required(
+                        FieldMatch::new("Prenom", Comparator::Levenshtein).with_threshold(0.8),
+                    ),
+            )
+            .pairs(df.lazy())
+            .unwrap()
+            .collect()
+            .unwrap();
+
+        assert_eq!(df_pairs.height(), 1);
+        let score = |column: &str| df_pairs.column(column).unwrap().f64().unwrap().get(0);
+        let nom_score = score("Nom_score").unwrap();
+        let prenom_score = score("Prenom_score").unwrap();
+        assert!(nom_score > 0.95);
+        assert!((prenom_score - 5.0 / 6.0).abs() < 1e-9);
+        assert_eq!(score(SCORE), Some((nom_score + prenom_score) / 2.0));
+    }
+
+    #[test]
+    fn test_evidence() {
+        let df_evidence = Deduplicator::new("ID")
+            .with_rule(same_person())
+            .evidence(df_people().lazy())
+            .unwrap()
+            .sort([LEFT_ID, RIGHT_ID], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        // The pairs of DUPONT without SIRET are compared, 1-3 is kept as no match
+        assert_eq!(
+            str_values(&df_evidence, LEFT_ID),
+            vec![
+                Some("1".to_string()),
+                Some("1".to_string()),
+                Some("2".to_string())
+            ]
+        );
+        assert_eq!(
+            str_values(&df_evidence, RIGHT_ID),
+            vec![
+                Some("2".to_string()),
+                Some("3".to_string()),
+                Some("3".to_string())
+            ]
+        );
+        assert_eq!(
+            str_values(&df_evidence, RULE_MATCHED),
+            vec![
+                Some("same_person".to_string()),
+                None,
+                Some("same_person".to_string())
+            ]
+        );
+        assert_eq!(
+            str_values(&df_evidence, DECISION),
+            vec![
+                Some(MATCH.to_string()),
+                Some(NO_MATCH.to_string()),
+                Some(MATCH.to_string())
+            ]
+        );
+        assert_eq!(
+            df_evidence
+                .column("Prenom_score")
+                .unwrap()
+                .f64()
+                .unwrap()
+                .get(1),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn test_pairs_without_rule() {
+        assert!(Deduplicator::new("ID").pairs(df_people().lazy()).is_err());
+    }
+
+    #[test]
+    fn test_pairs_with_blocking() {
+        let df = df![
+            "ID" => &[1i64, 2, 3, 4],
+            "Nom" => &["SALLANDIER", "SALANDIER", "DURAND", "SALLANDIER"],
+            "CP" => &["75011", "75011", "75011", "69001"],
+        ]
+        .unwrap();
+        let df_pairs = Deduplicator::new("ID")
+            .with_rule(
+                MatchRule::new("similar_name")
+                    .with_required(FieldMatch::new("Nom", Comparator::JaroWinkler)),
+            )
+            .with_blocking_key(BlockingKey::new("cp").with_column("CP"))
+            .pairs(df.lazy())
+            .unwrap()
+            .collect()
+            .unwrap();
+
+        // 1-4 are the same name but not in the same block
+        assert_eq!(df_pairs.height(), 1);
+        assert_eq!(
+            df_pairs.column(LEFT_ID).unwrap().i64().unwrap().get(0),
+            Some(1)
+        );
+        assert_eq!(
+            df_pairs.column(RIGHT_ID).unwrap().i64().unwrap().get(0),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_assign_clusters() {
+        let df = Deduplicator::new("ID")
+            .with_rule(same_person())
+            .assign_clusters(df_people().lazy())
+            .unwrap()
+            .sort(["ID"], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        // 1-2 and 2-3 put 1, 2 and 3 in the same cluster, even though 1 and 3 do not match
+        assert_eq!(
+            str_values(&df, CLUSTER_ID),
+            ["1", "1", "1", "4", "5"]
+                .iter()
+                .map(|id| Some(id.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let df = Deduplicator::new("ID")
+            .with_rule(same_person())
+            .deduplicate(df_people().lazy())
+            .unwrap()
+            .sort(["ID"], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        assert_eq!(
+            str_values(&df, "ID"),
+            ["1", "4", "5"]
+                .iter()
+                .map(|id| Some(id.to_string()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            str_values(&df, "IDS"),
+            vec![Some("1/2/3".to_string()), None, None]
+        );
+        assert_eq!(
+            str_values(&df, "SIRET"),
+            vec![None, None, Some("44316952400120".to_string())]
+        );
+        assert_eq!(
+            str_values(&df, "Prenom"),
+            vec![
+                Some("JEAN/PAUL".to_string()),
+                Some("ANNE".to_string()),
+                Some("ANNE".to_string())
+            ]
+        );
+        assert_eq!(
+            str_values(&df, "Telephone"),
+            vec![
+                Some("0601/0602".to_string()),
+                Some("0700".to_string()),
+                Some("0700".to_string())
+            ]
+        );
+        assert_eq!(str_values(&df, "Provenance"), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_deduplicate_survivorship() {
+        let df = Deduplicator::new("ID")
+            .with_rule(same_person())
+            .with_survivorship("Prenom", Survivorship::Longest)
+            .with_survivorship("Email", Survivorship::MostFrequent)
+            .with_survivorship("Telephone", Survivorship::KeepAll)
+            .deduplicate(df_people().lazy())
+            .unwrap()
+            .sort(["ID"], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        assert_eq!(
+            str_values(&df, "Prenom"),
+            vec![
+                Some("JEAN".to_string()),
+                Some("ANNE".to_string()),
+                Some("ANNE".to_string())
+            ]
+        );
+        assert_eq!(str_values(&df, "Email")[0], Some("j@x.fr".to_string()));
+        let telephones = df
+            .column("Telephone")
+            .unwrap()
+            .list()
+            .unwrap()
+            .get_as_series(0)
+            .unwrap();
+        assert_eq!(
+            telephones
+                .str()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<_>>(),
+            vec!["0601", "0602"]
+        );
+        // JEAN and PAUL have the same length, the smallest id wins
+        assert_eq!(
+            str_values(&df, "Provenance"),
+            vec![
+                Some(r#"{"Prenom":"1","Email":"1","Telephone":"1/2"}"#.to_string()),
+                None,
+                None
+            ]
+        );
+    }
+}
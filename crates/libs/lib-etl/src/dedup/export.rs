@@ -0,0 +1,224 @@
+use super::cluster::CLUSTER_ID;
+use super::deduplicator::{DECISION, LEFT_ID, MATCH, RIGHT_ID, RULE_MATCHED, SCORE};
+use core::error::Error;
+use polars::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Matching pair of records of a cluster.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClusterLink {
+    pub left_id: String,
+    pub right_id: String,
+    pub rule_matched: Option<String>,
+    pub score: Option<f64>,
+}
+
+/// Records merged together, with the pairs that linked them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClusterExport {
+    pub cluster_id: String,
+    pub records: Vec<String>,
+    pub links: Vec<ClusterLink>,
+}
+
+fn text_values(df: &DataFrame, column: &str) -> PolarsResult<Vec<Option<String>>> {
+    Ok(df
+        .column(column)?
+        .cast(&DataType::String)?
+        .str()?
+        .into_iter()
+        .map(|value| value.map(String::from))
+        .collect())
+}
+
+/// Clusters of more than one record of `df_clusters`, as returned by
+/// [`super::deduplicator::Deduplicator::clusters`], in the order of their first record,
+/// with the pairs of `df_pairs` linking their records.
+/// `df_pairs` holds the matching pairs, or an evidence whose pairs without match are left out.
+pub fn duplicate_clusters(
+    df_clusters: &DataFrame,
+    df_pairs: &DataFrame,
+    id_column: &str,
+) -> PolarsResult<Vec<ClusterExport>> {
+    let mut clusters: Vec<ClusterExport> = Vec::new();
+    let mut positions = HashMap::new();
+    let ids = text_values(df_clusters, id_column)?;
+    let cluster_ids = text_values(df_clusters, CLUSTER_ID)?;
+    for (id, cluster_id) in ids.into_iter().zip(cluster_ids) {
+        let (Some(id), Some(cluster_id)) = (id, cluster_id) else {
+            continue;
+        };
+        let position = *positions.entry(cluster_id.clone()).or_insert_with(|| {
+            clusters.push(ClusterExport {
+                cluster_id,
+                records: Vec::new(),
+                links: Vec::new(),
+            });
+            clusters.len() - 1
+        });
+        clusters[position].records.push(id);
+    }
+    let cluster_positions = clusters
+        .iter()
+        .enumerate()
+        .flat_map(|(position, cluster)| {
+            cluster.records.iter().map(move |id| (id.clone(), position))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let decisions = match df_pairs.column(DECISION) {
+        Ok(_) => text_values(df_pairs, DECISION)?,
+        Err(_) => vec![Some(MATCH.to_string()); df_pairs.height()],
+    };
+    let rules = text_values(df_pairs, RULE_MATCHED)?;
+    let scores = df_pairs.column(SCORE)?.cast(&DataType::Float64)?;
+    let pairs = text_values(df_pairs, LEFT_ID)?
+        .into_iter()
+        .zip(text_values(df_pairs, RIGHT_ID)?)
+        .zip(rules)
+        .zip(scores.f64()?)
+        .zip(decisions);
+    for ((((left_id, right_id), rule_matched), score), decision) in pairs {
+        if decision.as_deref() != Some(MATCH) {
+            continue;
+        }
+        let (Some(left_id), Some(right_id)) = (left_id, right_id) else {
+            continue;
+        };
+        if let Some(&position) = cluster_positions.get(&left_id) {
+            clusters[position].links.push(ClusterLink {
+                left_id,
+                right_id,
+                rule_matched,
+                score,
+            });
+        }
+    }
+
+    clusters.retain(|cluster| cluster.records.len() > 1);
+    Ok(clusters)
+}
+
+/// [`duplicate_clusters`] as a JSON array.
+pub fn clusters_to_json(
+    df_clusters: &DataFrame,
+    df_pairs: &DataFrame,
+    id_column: &str,
+) -> Result<String, Box<dyn Error>> {
+    let clusters = duplicate_clusters(df_clusters, df_pairs, id_column)?;
+    Ok(serde_json::to_string_pretty(&clusters)?)
+}
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// [`duplicate_clusters`] as a GraphViz graph, one boxed subgraph per cluster,
+/// the edges labelled with the rule matched and the score, e.g. `dot -Tsvg clusters.dot`.
+pub fn clusters_to_dot(
+    df_clusters: &DataFrame,
+    df_pairs: &DataFrame,
+    id_column: &str,
+) -> PolarsResult<String> {
+    let mut dot = String::from("graph clusters {\n");
+    for cluster in duplicate_clusters(df_clusters, df_pairs, id_column)? {
+        // GraphViz only boxes the subgraphs named cluster...
+        dot.push_str(&format!(
+            "  subgraph {} {{\n    label={};\n",
+            dot_quote(&format!("cluster_{}", cluster.cluster_id)),
+            dot_quote(&cluster.cluster_id)
+        ));
+        for id in &cluster.records {
+            dot.push_str(&format!("    {};\n", dot_quote(id)));
+        }
+        for link in &cluster.links {
+            let label = match (&link.rule_matched, link.score) {
+                (Some(rule), Some(score)) => format!("{} {:.2}", rule, score),
+                (Some(rule), None) => rule.clone(),
+                (None, Some(score)) => format!("{:.2}", score),
+                (None, None) => String::new(),
+            };
+            dot.push_str(&format!(
+                "    {} -- {} [label={}];\n",
+                dot_quote(&link.left_id),
+                dot_quote(&link.right_id),
+                dot_quote(&label)
+            ));
+        }
+        dot.push_str("  }\n");
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    fn df_clusters() -> DataFrame {
+        df![
+            "ID" => &[1i64, 2, 3, 4],
+            CLUSTER_ID => &[1i64, 1, 1, 4],
+        ]
+        .unwrap()
+    }
+
+    fn df_evidence() -> DataFrame {
+        df![
+            LEFT_ID => &[1i64, 2, 1],
+            RIGHT_ID => &[2i64, 3, 4],
+            RULE_MATCHED => &[Some("same_person"), Some("same_email"), None],
+            SCORE => &[0.98, 1.0, 0.4],
+            DECISION => &["match", "match", "no_match"],
+        ]
+        .unwrap()
+    }
+
+    #[test]
+    fn test_duplicate_clusters() {
+        let clusters = duplicate_clusters(&df_clusters(), &df_evidence(), "ID").unwrap();
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].cluster_id, "1");
+        assert_eq!(clusters[0].records, vec!["1", "2", "3"]);
+        assert_eq!(
+            clusters[0].links,
+            vec![
+                ClusterLink {
+                    left_id: "1".to_string(),
+                    right_id: "2".to_string(),
+                    rule_matched: Some("same_person".to_string()),
+                    score: Some(0.98),
+                },
+                ClusterLink {
+                    left_id: "2".to_string(),
+                    right_id: "3".to_string(),
+                    rule_matched: Some("same_email".to_string()),
+                    score: Some(1.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clusters_to_json() {
+        let json = clusters_to_json(&df_clusters(), &df_evidence(), "ID").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value[0]["cluster_id"], "1");
+        assert_eq!(value[0]["records"], serde_json::json!(["1", "2", "3"]));
+        assert_eq!(value[0]["links"][1]["rule_matched"], "same_email");
+    }
+
+    #[test]
+    fn test_clusters_to_dot() {
+        let dot = clusters_to_dot(&df_clusters(), &df_evidence(), "ID").unwrap();
+
+        assert_eq!(
+            dot,
+            "graph clusters {\n  subgraph \"cluster_1\" {\n    label=\"1\";\n    \"1\";\n    \"2\";\n    \"3\";\n    \"1\" -- \"2\" [label=\"same_person 0.98\"];\n    \"2\" -- \"3\" [label=\"same_email 1.00\"];\n  }\n}\n"
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use super::blocking::{blocking_index, BLOCK_KEY, BLOCK_VALUE};
+use super::cluster::CLUSTER_ID;
+use super::deduplicator::Deduplicator;
+use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Column of the cluster events holding the kind of event, see [`ClusterEvent`].
+pub const EVENT: &str = "event";
+/// Column of the cluster events holding the clusters before the update, joined by the separator.
+pub const CLUSTERS_BEFORE: &str = "clusters_before";
+/// Column of the cluster events holding the clusters after the update, joined by the separator.
+pub const CLUSTERS_AFTER: &str = "clusters_after";
+/// Column of the cluster events holding the changed records behind the event, joined by the separator.
+pub const CHANGED_IDS: &str = "changed_ids";
+
+/// Change of the clusters made by new or changed records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterEvent {
+    /// New records matching no existing record.
+    Created,
+    /// New records joining an existing cluster.
+    Attached,
+    /// Existing clusters linked into one by the changed records.
+    Merged,
+    /// Existing cluster whose records no longer all match, e.g. after a record changed.
+    Split,
+}
+
+impl ClusterEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClusterEvent::Created => "created",
+            ClusterEvent::Attached => "attached",
+            ClusterEvent::Merged => "merged",
+            ClusterEvent::Split => "split",
+        }
+    }
+}
+
+/// Result of [`Deduplicator::update`], to persist in place of the touched clusters.
+pub struct ClusterUpdate {
+    /// Records of the touched clusters with their `cluster_id`, to upsert into the members.
+    pub df_records: DataFrame,
+    /// Merged records of the touched clusters, to upsert into the golden records.
+    pub df_golden: DataFrame,
+    /// Blocking index of the changed records, to add to the persisted index.
+    pub df_index: DataFrame,
+    /// Clusters merged into another or split, whose golden records are to be deleted.
+    pub retired_cluster_ids: Vec<String>,
+    /// `(event, clusters_before, clusters_after, changed_ids)` of the cluster changes.
+    pub df_events: DataFrame,
+}
+
+fn text_values(df: &DataFrame, column: &str) -> PolarsResult<Vec<Option<String>>> {
+    Ok(df
+        .column(column)?
+        .cast(&DataType::String)?
+        .str()?
+        .into_iter()
+        .map(|value| value.map(String::from))
+        .collect())
+}
+
+/// Clusters by id, and ids by cluster in the order of `df_clusters`.
+fn cluster_maps(
+    df_clusters: &DataFrame,
+    id_column: &str,
+) -> PolarsResult<(HashMap<String, String>, Vec<(String, Vec<String>)>)> {
+    let mut clusters_by_id = HashMap::new();
+    let mut ids_by_cluster: HashMap<String, Vec<String>> = HashMap::new();
+    let mut cluster_order: Vec<String> = Vec::new();
+    let ids = text_values(df_clusters, id_column)?;
+    let cluster_ids = text_values(df_clusters, CLUSTER_ID)?;
+    for (id, cluster_id) in ids.into_iter().zip(cluster_ids) {
+        let (Some(id), Some(cluster_id)) = (id, cluster_id) else {
+            continue;
+        };
+        clusters_by_id.insert(id.clone(), cluster_id.clone());
+        ids_by_cluster
+            .entry(cluster_id.clone())
+            .or_insert_with(|| {
+                cluster_order.push(cluster_id);
+                Vec::new()
+            })
+            .push(id);
+    }
+    let ids_by_cluster = cluster_order
+        .into_iter()
+        .map(|cluster_id| {
+            let ids = ids_by_cluster.remove(&cluster_id).unwrap_or_default();
+            (cluster_id, ids)
+        })
+        .collect();
+    Ok((clusters_by_id, ids_by_cluster))
+}
+
+/// Distinct values in order of appearance.
+fn distinct<'a>(values: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut distinct: Vec<String> = Vec::new();
+    for value in values {
+        if !distinct.contains(value) {
+            distinct.push(value.clone());
+        }
+    }
+    distinct
+}
+
+/// Events turning the `(id_column, cluster_id)` of `df_before` into those of `df_after`.
+pub fn cluster_events(
+    df_before: &DataFrame,
+    df_after: &DataFrame,
+    changed_ids: &HashSet<String>,
+    id_column: &str,
+    separator: &str,
+) -> PolarsResult<DataFrame> {
+    let (before_by_id, ids_before) = cluster_maps(df_before, id_column)?;
+    let (after_by_id, ids_after) = cluster_maps(df_after, id_column)?;
+    let mut events: Vec<(ClusterEvent, Vec<String>, Vec<String>, Vec<String>)> = Vec::new();
+
+    for (cluster_id, ids) in &ids_after {
+        let before = distinct(ids.iter().filter_map(|id| before_by_id.get(id)));
+        let changed = ids
+            .iter()
+            .filter(|id| changed_ids.contains(*id))
+            .cloned()
+            .collect::<Vec<_>>();
+        let event = match before.len() {
+            0 => Some(ClusterEvent::Created),
+            1 if ids.iter().any(|id| !before_by_id.contains_key(id)) => {
+                Some(ClusterEvent::Attached)
+            }
+            1 => None,
+            _ => Some(ClusterEvent::Merged),
+        };
+        if let Some(event) = event {
+            events.push((event, before, vec![cluster_id.clone()], changed));
+        }
+    }
+    for (cluster_id, ids) in &ids_before {
+        let after = distinct(ids.iter().filter_map(|id| after_by_id.get(id)));
+        if after.len() > 1 {
+            let changed = ids
+                .iter()
+                .filter(|id| changed_ids.contains(*id))
+                .cloned()
+                .collect::<Vec<_>>();
+            events.push((
+                ClusterEvent::Split,
+                vec![cluster_id.clone()],
+                after,
+                changed,
+            ));
+        }
+    }
+
+    let joined = |values: &Vec<String>| (!values.is_empty()).then(|| values.join(separator));
+    DataFrame::new(vec![
+        Column::new(
+            EVENT.into(),
+            events
+                .iter()
+                .map(|(event, ..)| event.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            CLUSTERS_BEFORE.into(),
+            events
+                .iter()
+                .map(|(_, before, ..)| joined(before))
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            CLUSTERS_AFTER.into(),
+            events
+                .iter()
+                .map(|(_, _, after, _)| joined(after))
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            CHANGED_IDS.into(),
+            events
+                .iter()
+                .map(|(.., changed)| joined(changed))
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+impl Deduplicator {
+    /// Matches new or changed records against the existing clusters, instead of deduplicating
+    /// the whole dataset again.
+    ///
+    /// `lf_members` holds the records already deduplicated with their `cluster_id`,
+    /// `lf_index` their blocking index, see [`blocking_index`], and `lf_changed` the new or
+    /// changed records, with the columns of the members but the `cluster_id`.
+    /// Only the clusters of the changed records, or sharing a block with them, are clustered again,
+    /// so the records can attach to a cluster, start one, merge clusters or split their former one.
+    /// Without blocking keys, every cluster is touched.
+    pub fn update(
+        &self,
+        lf_members: LazyFrame,
+        lf_index: LazyFrame,
+        lf_changed: LazyFrame,
+    ) -> PolarsResult<ClusterUpdate> {
+        let id_column = self.id_column();
+        let mut lf_changed = lf_changed;
+        let exprs_record_columns = lf_changed
+            .collect_schema()?
+            .iter_names()
+            .map(|name| col(name.as_str()))
+            .collect::<Vec<_>>();
+        let df_changed = lf_changed.collect()?;
+        let lf_changed_ids = df_changed.clone().lazy().select([col(id_column)]);
+        let df_index = blocking_index(df_changed.clone().lazy(), id_column, self.blocking_keys())?
+            .collect()?;
+
+        // The records sharing a block with a changed record, and the changed records themselves
+        let lf_touched_ids = if self.blocking_keys().is_empty() {
+            lf_members.clone().select([col(id_column)])
+        } else {
+            let lf_blocks = df_index
+                .clone()
+                .lazy()
+                .select([col(BLOCK_KEY), col(BLOCK_VALUE)])
+                .unique(None, UniqueKeepStrategy::First);
+            concat(
+                [
+                    lf_index
+                        .join(
+                            lf_blocks,
+                            [col(BLOCK_KEY), col(BLOCK_VALUE)],
+                            [col(BLOCK_KEY), col(BLOCK_VALUE)],
+                            JoinArgs::new(JoinType::Inner),
+                        )
+                        .select([col(id_column)]),
+                    lf_changed_ids.clone(),
+                ],
+                UnionArgs::default(),
+            )?
+        };
+        let lf_touched_clusters = lf_members
+            .clone()
+            .join(
+                lf_touched_ids,
+                [col(id_column)],
+                [col(id_column)],
+                JoinArgs::new(JoinType::Semi),
+            )
+            .select([col(CLUSTER_ID)])
+            .unique(None, UniqueKeepStrategy::First);
+        let df_before = lf_members
+            .join(
+                lf_touched_clusters,
+                [col(CLUSTER_ID)],
+                [col(CLUSTER_ID)],
+                JoinArgs::new(JoinType::Semi),
+            )
+            .collect()?;
+
+        // The changed records replace their former version
+        let lf_records = concat(
+            [
+                df_before
+                    .clone()
+                    .lazy()
+                    .join(
+                        lf_changed_ids,
+                        [col(id_column)],
+                        [col(id_column)],
+                        JoinArgs::new(JoinType::Anti),
+                    )
+                    .select(&exprs_record_columns),
+                df_changed.clone().lazy().select(&exprs_record_columns),
+            ],
+            UnionArgs::default(),
+        )?;
+        let df_after = self.clusters(lf_records.clone())?;
+
+        let changed_ids = text_values(&df_changed, id_column)?
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<_>>();
+        let df_events = cluster_events(
+            &df_before,
+            &df_after,
+            &changed_ids,
+            id_column,
+            self.separator(),
+        )?;
+        let clusters_after = text_values(&df_after, CLUSTER_ID)?
+            .into_iter()
+            .flatten()
+            .collect::<HashSet<_>>();
+        let mut retired_cluster_ids = Vec::new();
+        for cluster_id in text_values(&df_before, CLUSTER_ID)?.into_iter().flatten() {
+            if !clusters_after.contains(&cluster_id) && !retired_cluster_ids.contains(&cluster_id) {
+                retired_cluster_ids.push(cluster_id);
+            }
+        }
+
+        let df_golden = self
+            .merge_clusters(lf_records.clone(), df_after.clone())?
+            .collect()?;
+        let df_records = lf_records
+            .join(
+                df_after.lazy(),
+                [col(id_column)],
+                [col(id_column)],
+                JoinArgs::new(JoinType::Left),
+            )
+            .collect()?;
+        Ok(ClusterUpdate {
+            df_records,
+            df_golden,
+            df_index,
+            retired_cluster_ids,
+            df_events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::blocking::BlockingKey;
+    use crate::dedup::rules::{FieldMatch, MatchRule, NullPolicy};
+    use polars::df;
+
+    fn deduplicator() -> Deduplicator {
+        Deduplicator::new("ID")
+            .with_rule(
+                MatchRule::new("same_person")
+                    .with_required(FieldMatch::exact("Nom"))
+                    .with_required(FieldMatch::exact("Prenom").with_nulls(NullPolicy::Match)),
+            )
+            .with_blocking_key(BlockingKey::new("nom").with_column("Nom"))
+    }
+
+    fn df_people() -> DataFrame {
+        df![
+            "ID" => &["1", "2", "3", "4"],
+            "Nom" => &["DUPONT", "DUPONT", "DUPONT", "MARTIN"],
+            "Prenom" => &[Some("JEAN"), None, Some("PAUL"), Some("ANNE")],
+        ]
+        .unwrap()
+    }
+
+    /// Clusters {1, 2, 3}, through 2 without Prenom, and {4}.
+    fn update(df_changed: DataFrame) -> ClusterUpdate {
+        let deduplicator = deduplicator();
+        let lf_members = deduplicator.assign_clusters(df_people().lazy()).unwrap();
+        let lf_index =
+            blocking_index(df_people().lazy(), "ID", deduplicator.blocking_keys()).unwrap();
+        deduplicator
+            .update(lf_members, lf_index, df_changed.lazy())
+            .unwrap()
+    }
+
+    fn str_values(df: &DataFrame, column: &str) -> Vec<Option<String>> {
+        text_values(df, column).unwrap()
+    }
+
+    fn some(values: &[&str]) -> Vec<Option<String>> {
+        values.iter().map(|value| Some(value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_update_attach_and_create() {
+        let cluster_update = update(
+            df![
+                "ID" => &["5", "6"],
+                "Nom" => &["DUPONT", "BERNARD"],
+                "Prenom" => &["JEAN", "LUC"],
+            ]
+            .unwrap(),
+        );
+
+        assert_eq!(
+            str_values(&cluster_update.df_events, EVENT),
+            some(&["attached", "created"])
+        );
+        assert_eq!(
+            str_values(&cluster_update.df_events, CLUSTERS_BEFORE),
+            vec![Some("1".to_string()), None]
+        );
+        assert_eq!(
+            str_values(&cluster_update.df_events, CLUSTERS_AFTER),
+            some(&["1", "6"])
+        );
+        assert_eq!(
+            str_values(&cluster_update.df_events, CHANGED_IDS),
+            some(&["5", "6"])
+        );
+        // MARTIN shares no block with the changed records
+        let df_records = cluster_update
+            .df_records
+            .sort(["ID"], SortMultipleOptions::default())
+            .unwrap();
+        assert_eq!(
+            str_values(&df_records, "ID"),
+            some(&["1", "2", "3", "5", "6"])
+        );
+        assert_eq!(
+            str_values(&df_records, CLUSTER_ID),
+            some(&["1", "1", "1", "1", "6"])
+        );
+        assert_eq!(cluster_update.df_golden.height(), 2);
+        assert_eq!(cluster_update.df_index.height(), 2);
+        assert!(cluster_update.retired_cluster_ids.is_empty());
+    }
+
+    #[test]
+    fn test_update_split() {
+        let cluster_update = update(
+            df![
+                "ID" => &["2"],
+                "Nom" => &["DUPONT"],
+                "Prenom" => &["LUC"],
+            ]
+            .unwrap(),
+        );
+
+        // 2 linked 1 and 3, which no longer match
+        assert_eq!(
+            str_values(&cluster_update.df_events, EVENT),
+            some(&["split"])
+        );
+        assert_eq!(
+            str_values(&cluster_update.df_events, CLUSTERS_AFTER),
+            some(&["1/2/3"])
+        );
+        assert_eq!(
+            str_values(&cluster_update.df_events, CHANGED_IDS),
+            some(&["2"])
+        );
+        assert_eq!(cluster_update.df_golden.height(), 3);
+    }
+
+    #[test]
+    fn test_cluster_events_merge() {
+        let df_before = df![
+            "ID" => &["1", "2"],
+            CLUSTER_ID => &["1", "2"],
+        ]
+        .unwrap();
+        let df_after = df![
+            "ID" => &["1", "2", "3"],
+            CLUSTER_ID => &["1", "1", "1"],
+        ]
+        .unwrap();
+        let changed_ids = HashSet::from(["3".to_string()]);
+        let df_events = cluster_events(&df_before, &df_after, &changed_ids, "ID", "/").unwrap();
+
+        assert_eq!(str_values(&df_events, EVENT), some(&["merged"]));
+        assert_eq!(str_values(&df_events, CLUSTERS_BEFORE), some(&["1/2"]));
+        assert_eq!(str_values(&df_events, CLUSTERS_AFTER), some(&["1"]));
+    }
+}
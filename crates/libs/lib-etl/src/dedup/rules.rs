@@ -0,0 +1,474 @@
+use super::similarity::{self, similarity_expr};
+use polars::prelude::*;
+use serde::Deserialize;
+
+/// Suffix of the columns of the right record of a candidate pair.
+pub const RIGHT_SUFFIX: &str = "_right";
+
+/// Name of the column of the right record of a candidate pair, e.g. `Nom_right`.
+pub fn right_column(column: &str) -> String {
+    format!("{}{}", column, RIGHT_SUFFIX)
+}
+
+/// Name of the column holding the similarity score of a column for a pair, e.g. `Nom_score`.
+pub fn score_column(column: &str) -> String {
+    format!("{}_score", column)
+}
+
+/// How two values of a column are compared. Every comparator gives a score between 0 and 1,
+/// exact comparators only 0 or 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparator {
+    #[default]
+    Exact,
+    /// Equal once lowercased, for text columns.
+    IgnoreCase,
+    /// See [`similarity::jaro_winkler`], for names.
+    JaroWinkler,
+    /// See [`similarity::normalised_levenshtein`].
+    Levenshtein,
+    /// See [`similarity::token_set_ratio`], for company names.
+    TokenSet,
+}
+
+impl Comparator {
+    /// Score a pair needs to match when the field sets no threshold.
+    pub fn default_threshold(&self) -> f64 {
+        match self {
+            Comparator::Exact | Comparator::IgnoreCase => 1.0,
+            Comparator::JaroWinkler => 0.9,
+            Comparator::Levenshtein => 0.8,
+            Comparator::TokenSet => 0.85,
+        }
+    }
+
+    /// Float64 expression scoring `left` against `right`, null when either value is null.
+    pub fn score_expr(&self, left: Expr, right: Expr) -> Expr {
+        let exact_score = |eq: Expr| {
+            when(eq)
+                .then(lit(1.0))
+                .otherwise(lit(0.0))
+                .cast(DataType::Float64)
+        };
+        match self {
+            Comparator::Exact => {
+                let either_null = left.clone().is_null().or(right.clone().is_null());
+                when(either_null)
+                    .then(lit(NULL).cast(DataType::Float64))
+                    .otherwise(exact_score(left.eq(right)))
+            }
+            Comparator::IgnoreCase => {
+                let either_null = left.clone().is_null().or(right.clone().is_null());
+                when(either_null)
+                    .then(lit(NULL).cast(DataType::Float64))
+                    .otherwise(exact_score(
+                        left.str().to_lowercase().eq(right.str().to_lowercase()),
+                    ))
+            }
+            Comparator::JaroWinkler => similarity_expr(left, right, similarity::jaro_winkler),
+            Comparator::Levenshtein => {
+                similarity_expr(left, right, similarity::normalised_levenshtein)
+            }
+            Comparator::TokenSet => similarity_expr(left, right, similarity::token_set_ratio),
+        }
+    }
+}
+
+/// What a comparison with a null value gives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NullPolicy {
+    /// A null never matches, not even another null.
+    #[default]
+    NoMatch,
+    /// A null on either side matches anything, e.g. a missing first name.
+    Match,
+    /// Two nulls match each other, a null never matches a value.
+    BothNull,
+}
+
+/// Comparison of one column between the two records of a pair.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FieldMatch {
+    pub column: String,
+    #[serde(default)]
+    pub comparator: Comparator,
+    #[serde(default)]
+    pub nulls: NullPolicy,
+    /// Score the pair needs to match, defaults to [`Comparator::default_threshold`].
+    #[serde(default)]
+    pub threshold: Option<f64>,
+}
+
+impl FieldMatch {
+    pub fn new(column: &str, comparator: Comparator) -> Self {
+        FieldMatch {
+            column: column.to_string(),
+            comparator,
+            nulls: NullPolicy::default(),
+            threshold: None,
+        }
+    }
+
+    pub fn exact(column: &str) -> Self {
+        FieldMatch::new(column, Comparator::Exact)
+    }
+
+    pub fn with_nulls(mut self, nulls: NullPolicy) -> Self {
+        self.nulls = nulls;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+            .unwrap_or_else(|| self.comparator.default_threshold())
+    }
+
+    /// Exact comparisons where nulls never match can be used as join keys.
+    pub(crate) fn is_join_key(&self) -> bool {
+        self.comparator == Comparator::Exact && self.nulls == NullPolicy::NoMatch
+    }
+
+    /// Score of the field for a pair, null when either value is null.
+    pub fn score_expr(&self) -> Expr {
+        self.comparator.score_expr(
+            col(self.column.as_str()),
+            col(right_column(&self.column).as_str()),
+        )
+    }
+
+    /// Boolean expression over a pair, never null.
+    pub fn match_expr(&self) -> Expr {
+        let left = col(self.column.as_str());
+        let right = col(right_column(&self.column).as_str());
+        let above = self.score_expr().gt_eq(lit(self.threshold()));
+        let matched = match self.nulls {
+            NullPolicy::NoMatch => above,
+            NullPolicy::Match => above.or(left.is_null()).or(right.is_null()),
+            NullPolicy::BothNull => above.or(left.is_null().and(right.is_null())),
+        };
+        matched.fill_null(lit(false))
+    }
+}
+
+/// Condition a record must meet to be compared by a rule.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    IsNull(String),
+    IsNotNull(String),
+}
+
+impl Condition {
+    pub fn expr(&self) -> Expr {
+        self.expr_on(|column| column.to_string())
+    }
+
+    /// Condition on the right record of a pair.
+    pub fn right_expr(&self) -> Expr {
+        self.expr_on(right_column)
+    }
+
+    fn expr_on(&self, column_name: impl Fn(&str) -> String) -> Expr {
+        match self {
+            Condition::IsNull(column) => col(column_name(column).as_str()).is_null(),
+            Condition::IsNotNull(column) => col(column_name(column).as_str()).is_not_null(),
+        }
+    }
+}
+
+/// A pair of records matches the rule when both records meet the conditions,
+/// all the required fields match, and at least `min_optional` of the optional fields match.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MatchRule {
+    pub name: String,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub required: Vec<FieldMatch>,
+    #[serde(default)]
+    pub optional: Vec<FieldMatch>,
+    /// Defaults to 1 when there are optional fields.
+    #[serde(default)]
+    pub min_optional: Option<usize>,
+}
+
+impl MatchRule {
+    pub fn new(name: &str) -> Self {
+        MatchRule {
+            name: name.to_string(),
+            conditions: Vec::new(),
+            required: Vec::new(),
+            optional: Vec::new(),
+            min_optional: None,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn with_required(mut self, field: FieldMatch) -> Self {
+        self.required.push(field);
+        self
+    }
+
+    pub fn with_optional(mut self, field: FieldMatch) -> Self {
+        self.optional.push(field);
+        self
+    }
+
+    pub fn with_min_optional(mut self, min_optional: usize) -> Self {
+        self.min_optional = Some(min_optional);
+        self
+    }
+
+    fn min_optional(&self) -> usize {
+        self.min_optional
+            .unwrap_or(if self.optional.is_empty() { 0 } else { 1 })
+    }
+
+    /// Columns the records are joined on when there are no blocking keys,
+    /// so only the pairs sharing them are compared.
+    pub(crate) fn join_keys(&self) -> Vec<&str> {
+        self.required
+            .iter()
+            .filter(|field| field.is_join_key())
+            .map(|field| field.column.as_str())
+            .collect()
+    }
+
+    /// Filter of the records the rule compares.
+    pub(crate) fn condition_expr(&self) -> Option<Expr> {
+        self.conditions
+            .iter()
+            .map(Condition::expr)
+            .reduce(|acc, expr| acc.and(expr))
+    }
+
+    /// Required then optional fields.
+    pub fn fields(&self) -> impl Iterator<Item = &FieldMatch> {
+        self.required.iter().chain(self.optional.iter())
+    }
+
+    /// Score of each field for a pair, named by [`score_column`].
+    pub(crate) fn field_score_exprs(&self) -> Vec<Expr> {
+        self.fields()
+            .map(|field| field.score_expr().alias(score_column(&field.column)))
+            .collect()
+    }
+
+    /// Mean of the non-null field scores of a pair.
+    pub(crate) fn score_expr(&self) -> Expr {
+        let scores = self.field_score_exprs();
+        let total = scores
+            .iter()
+            .cloned()
+            .map(|score| score.fill_null(lit(0.0)))
+            .fold(lit(0.0), |acc, score| acc + score);
+        let count = scores
+            .into_iter()
+            .map(|score| score.is_not_null().cast(DataType::Float64))
+            .fold(lit(0.0), |acc, count| acc + count);
+        when(count.clone().gt(lit(0.0)))
+            .then(total / count)
+            .otherwise(lit(NULL).cast(DataType::Float64))
+    }
+
+    /// Boolean expression over a pair: both records meet the conditions and the fields match.
+    pub(crate) fn pair_expr(&self) -> Expr {
+        let conditions = self
+            .conditions
+            .iter()
+            .flat_map(|condition| [condition.expr(), condition.right_expr()]);
+        let required = self
+            .required
+            .iter()
+            .map(FieldMatch::match_expr)
+            .chain(conditions)
+            .fold(lit(true), |acc, expr| acc.and(expr));
+        if self.min_optional() == 0 {
+            return required;
+        }
+        let optional_count = self
+            .optional
+            .iter()
+            .map(|field| field.match_expr().cast(DataType::UInt32))
+            .fold(lit(0u32), |acc, expr| acc + expr);
+        required.and(optional_count.gt_eq(lit(self.min_optional() as u32)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    fn pair_df() -> DataFrame {
+        df![
+            "Prenom" => &[Some("Jean"), Some("Jean"), None, None, Some("jean")],
+            "Prenom_right" => &[Some("Jean"), Some("Paul"), Some("Paul"), None, Some("JEAN")],
+        ]
+        .unwrap()
+    }
+
+    fn matches(field: FieldMatch) -> Vec<bool> {
+        pair_df()
+            .lazy()
+            .select([field.match_expr().alias("matched")])
+            .collect()
+            .unwrap()
+            .column("matched")
+            .unwrap()
+            .bool()
+            .unwrap()
+            .into_iter()
+            .map(|matched| matched.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_field_match_nulls() {
+        let test_cases = vec![
+            (NullPolicy::NoMatch, vec![true, false, false, false, false]),
+            (NullPolicy::Match, vec![true, false, true, true, false]),
+            (NullPolicy::BothNull, vec![true, false, false, true, false]),
+        ];
+        for (nulls, expected) in test_cases {
+            assert_eq!(
+                matches(FieldMatch::exact("Prenom").with_nulls(nulls)),
+                expected,
+                "{:?}",
+                nulls
+            );
+        }
+    }
+
+    #[test]
+    fn test_field_match_ignore_case() {
+        assert_eq!(
+            matches(FieldMatch::new("Prenom", Comparator::IgnoreCase)),
+            vec![true, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_field_match_fuzzy_threshold() {
+        let df = df![
+            "Nom" => &[Some("SALLANDIER"), Some("DUPONT"), None],
+            "Nom_right" => &[Some("SALANDIER"), Some("DURAND"), Some("DUPONT")],
+        ]
+        .unwrap();
+        let test_cases = vec![
+            (
+                FieldMatch::new("Nom", Comparator::JaroWinkler),
+                vec![true, false, false],
+            ),
+            (
+                FieldMatch::new("Nom", Comparator::Levenshtein),
+                vec![true, false, false],
+            ),
+            (
+                FieldMatch::new("Nom", Comparator::Levenshtein).with_threshold(0.95),
+                vec![false, false, false],
+            ),
+            (
+                FieldMatch::new("Nom", Comparator::JaroWinkler).with_nulls(NullPolicy::Match),
+                vec![true, false, true],
+            ),
+        ];
+        for (field, expected) in test_cases {
+            let matched = df
+                .clone()
+                .lazy()
+                .select([field.match_expr().alias("matched")])
+                .collect()
+                .unwrap()
+                .column("matched")
+                .unwrap()
+                .bool()
+                .unwrap()
+                .into_iter()
+                .map(|matched| matched.unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(matched, expected, "{:?}", field);
+        }
+    }
+
+    #[test]
+    fn test_rule_score() {
+        let df = df![
+            "Nom" => &[Some("SALLANDIER")],
+            "Nom_right" => &[Some("SALANDIER")],
+            "Prenom" => &[None::<&str>],
+            "Prenom_right" => &[Some("BENOIT")],
+        ]
+        .unwrap();
+        let rule = MatchRule::new("similar_person")
+            .with_required(FieldMatch::new("Nom", Comparator::Levenshtein))
+            .with_required(FieldMatch::exact("Prenom").with_nulls(NullPolicy::Match));
+        let df_scores = df
+            .lazy()
+            .select(
+                rule.field_score_exprs()
+                    .into_iter()
+                    .chain([rule.score_expr().alias("score")])
+                    .collect::<Vec<_>>(),
+            )
+            .collect()
+            .unwrap();
+
+        let score = |column: &str| df_scores.column(column).unwrap().f64().unwrap().get(0);
+        assert_eq!(score("Nom_score"), Some(0.9));
+        assert_eq!(score("Prenom_score"), None);
+        // The null Prenom score is left out of the mean
+        assert_eq!(score("score"), Some(0.9));
+    }
+
+    #[test]
+    fn test_rule_join_keys_and_min_optional() {
+        let rule = MatchRule::new("same_person")
+            .with_required(FieldMatch::exact("Nom"))
+            .with_required(FieldMatch::exact("Prenom").with_nulls(NullPolicy::Match))
+            .with_optional(FieldMatch::exact("Email"))
+            .with_optional(FieldMatch::exact("Telephone"));
+        assert_eq!(rule.join_keys(), vec!["Nom"]);
+        assert_eq!(rule.min_optional(), 1);
+        assert_eq!(rule.clone().with_min_optional(2).min_optional(), 2);
+        assert_eq!(MatchRule::new("empty").min_optional(), 0);
+    }
+
+    #[test]
+    fn test_rule_deserialize() {
+        let rule: MatchRule = serde_json::from_str(
+            r#"{
+                "name": "same_person",
+                "conditions": [{"is_null": "SIRET"}],
+                "required": [{"column": "Nom"}, {"column": "Prenom", "nulls": "match"}],
+                "optional": [
+                    {"column": "Email", "comparator": "ignore_case"},
+                    {"column": "Raison_sociale", "comparator": "token_set", "threshold": 0.9}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule,
+            MatchRule::new("same_person")
+                .with_condition(Condition::IsNull("SIRET".to_string()))
+                .with_required(FieldMatch::exact("Nom"))
+                .with_required(FieldMatch::exact("Prenom").with_nulls(NullPolicy::Match))
+                .with_optional(FieldMatch::new("Email", Comparator::IgnoreCase))
+                .with_optional(
+                    FieldMatch::new("Raison_sociale", Comparator::TokenSet).with_threshold(0.9)
+                )
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use polars::prelude::*;
+use std::collections::BTreeSet;
+
+/// Jaro-Winkler similarity, favouring strings sharing a prefix, e.g. names with a typo at the end.
+pub fn jaro_winkler(left: &str, right: &str) -> f64 {
+    strsim::jaro_winkler(left, right)
+}
+
+/// Levenshtein distance divided by the length of the longest string, subtracted from 1.
+pub fn normalised_levenshtein(left: &str, right: &str) -> f64 {
+    strsim::normalized_levenshtein(left, right)
+}
+
+/// Similarity of the words of two texts regardless of their order and repetitions,
+/// e.g. "BOULANGERIE DUPONT ET FILS" and "DUPONT BOULANGERIE".
+///
+/// The common words are compared with each text, sorted, by normalised Levenshtein,
+/// and the best ratio is kept, so a text whose words are all in the other one scores 1.
+pub fn token_set_ratio(left: &str, right: &str) -> f64 {
+    let left_tokens = left.split_whitespace().collect::<BTreeSet<_>>();
+    let right_tokens = right.split_whitespace().collect::<BTreeSet<_>>();
+    if left_tokens.is_empty() && right_tokens.is_empty() {
+        return 1.0;
+    }
+    let join = |tokens: Vec<&str>| tokens.join(" ");
+    let common = join(left_tokens.intersection(&right_tokens).copied().collect());
+    let left_only = join(left_tokens.difference(&right_tokens).copied().collect());
+    let right_only = join(right_tokens.difference(&left_tokens).copied().collect());
+    let left_sorted = format!("{} {}", common, left_only).trim().to_string();
+    let right_sorted = format!("{} {}", common, right_only).trim().to_string();
+
+    let mut ratio = normalised_levenshtein(&left_sorted, &right_sorted);
+    if !common.is_empty() {
+        ratio = ratio
+            .max(normalised_levenshtein(&common, &left_sorted))
+            .max(normalised_levenshtein(&common, &right_sorted));
+    }
+    ratio
+}
+
+/// Float64 expression applying `similarity` to the values of `left` and `right`,
+/// null when either value is null.
+pub fn similarity_expr(left: Expr, right: Expr, similarity: fn(&str, &str) -> f64) -> Expr {
+    map_multiple(
+        move |columns: &mut [Column]| {
+            let left_values = columns[0].str()?;
+            let right_values = columns[1].str()?;
+            let scores = left_values
+                .into_iter()
+                .zip(right_values)
+                .map(
+                    |(left_value, right_value)| match (left_value, right_value) {
+                        (Some(left_value), Some(right_value)) => {
+                            Some(similarity(left_value, right_value))
+                        }
+                        _ => None,
+                    },
+                )
+                .collect::<Float64Chunked>()
+                .with_name(columns[0].name().clone());
+            Ok(Some(scores.into_column()))
+        },
+        [left, right],
+        GetOutput::from_type(DataType::Float64),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    #[test]
+    fn test_similarities() {
+        let test_cases: Vec<(fn(&str, &str) -> f64, &str, &str, f64)> = vec![
+            (jaro_winkler, "SALLANDIER", "SALANDIER", 0.98),
+            (jaro_winkler, "BENOIT", "BENOIT", 1.0),
+            (normalised_levenshtein, "SALLANDIER", "SALANDIER", 0.9),
+            (normalised_levenshtein, "BENOIT", "PAUL", 0.0),
+            (
+                token_set_ratio,
+                "BOULANGERIE DUPONT ET FILS",
+                "DUPONT BOULANGERIE",
+                1.0,
+            ),
+            (token_set_ratio, "DUPONT", "DUPONT", 1.0),
+            (token_set_ratio, "", "", 1.0),
+        ];
+        for (similarity, left, right, expected) in test_cases {
+            let score = similarity(left, right);
+            assert!(
+                (score - expected).abs() < 0.01,
+                "{} / {}: {} instead of {}",
+                left,
+                right,
+                score,
+                expected
+            );
+        }
+        assert!(token_set_ratio("GARAGE MARTIN", "BOULANGERIE DUPONT") < 0.5);
+    }
+
+    #[test]
+    fn test_similarity_expr() {
+        let df = df![
+            "Nom" => &[Some("SALLANDIER"), Some("DUPONT"), None],
+            "Nom_right" => &[Some("SALANDIER"), Some("DUPONT"), Some("DUPONT")],
+        ]
+        .unwrap();
+        let scores = df
+            .lazy()
+            .select([similarity_expr(
+                col("Nom"),
+                col("Nom_right"),
+                normalised_levenshtein,
+            )])
+            .collect()
+            .unwrap();
+        let scores = scores
+            .column("Nom")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(scores, vec![Some(0.9), Some(1.0), None]);
+    }
+}
@@ -0,0 +1,297 @@
+use polars::prelude::*;
+use serde::Deserialize;
+
+/// How the value of a column of a merged record is chosen among the values of its cluster.
+///
+/// A null value never survives when the cluster has another value,
+/// and ties go to the record with the smallest id.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Survivorship {
+    /// Value shared by the most records.
+    MostFrequent,
+    /// Value of the record with the latest `order_by`, e.g. an update date.
+    MostRecent { order_by: String },
+    /// Longest value as text.
+    Longest,
+    /// Value of the record whose `source_column` comes first in `priorities`,
+    /// the records of the unlisted sources coming last, e.g. `Id_source`.
+    SourcePriority {
+        source_column: String,
+        priorities: Vec<String>,
+    },
+    /// Distinct values of the cluster as a list.
+    KeepAll,
+}
+
+/// Helper column counting the records sharing the value of `column` in their cluster.
+fn frequency_column(column: &str) -> String {
+    format!("_{}_frequency", column)
+}
+
+/// Helper column of the merged records holding the id of the record `column` was taken from.
+pub fn source_column(column: &str) -> String {
+    format!("_{}_source", column)
+}
+
+impl Survivorship {
+    /// Helper column to add to the records before they are grouped by `cluster`, if any.
+    pub(crate) fn prepare_expr(&self, column: &str, cluster: &str) -> Option<Expr> {
+        match self {
+            Survivorship::MostFrequent => Some(
+                len()
+                    .over([col(cluster), col(column)])
+                    .alias(frequency_column(column)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Records of a cluster from the best to the worst value of `column`.
+    fn sort_keys(&self, column: &str, id_column: &str) -> (Vec<Expr>, Vec<bool>) {
+        let (key, descending) = match self {
+            Survivorship::MostFrequent => (col(frequency_column(column).as_str()), true),
+            Survivorship::MostRecent { order_by } => (col(order_by.as_str()), true),
+            Survivorship::Longest => (col(column).cast(DataType::String).str().len_chars(), true),
+            Survivorship::SourcePriority {
+                source_column,
+                priorities,
+            } => {
+                let rank = priorities.iter().enumerate().rev().fold(
+                    lit(priorities.len() as u32),
+                    |rank, (position, source)| {
+                        when(
+                            col(source_column.as_str())
+                                .cast(DataType::String)
+                                .eq(lit(source.as_str())),
+                        )
+                        .then(lit(position as u32))
+                        .otherwise(rank)
+                    },
+                );
+                (rank, false)
+            }
+            Survivorship::KeepAll => (col(id_column), false),
+        };
+        (
+            vec![col(column).is_null(), key, col(id_column)],
+            vec![false, descending, false],
+        )
+    }
+
+    fn sort_by(&self, expr: Expr, column: &str, id_column: &str) -> Expr {
+        let (keys, descending) = self.sort_keys(column, id_column);
+        expr.sort_by(
+            keys,
+            SortMultipleOptions::default()
+                .with_order_descending_multi(descending)
+                .with_nulls_last(true),
+        )
+    }
+
+    /// Aggregation of the surviving value of `column` in a cluster.
+    pub(crate) fn value_expr(&self, column: &str, id_column: &str) -> Expr {
+        match self {
+            Survivorship::KeepAll => col(column).drop_nulls().unique_stable(),
+            _ => self.sort_by(col(column), column, id_column).first(),
+        }
+        .alias(column)
+    }
+
+    /// Aggregation of the id of the record the value of `column` was taken from, as text,
+    /// the ids of all the records with a value joined by `separator` for [`Survivorship::KeepAll`].
+    /// Null when no record of the cluster has a value.
+    pub(crate) fn source_expr(&self, column: &str, id_column: &str, separator: &str) -> Expr {
+        let source = match self {
+            Survivorship::KeepAll => col(id_column)
+                .filter(col(column).is_not_null())
+                .sort(SortOptions::default())
+                .cast(DataType::String)
+                .str()
+                .join(separator, true),
+            _ => self
+                .sort_by(col(id_column), column, id_column)
+                .first()
+                .cast(DataType::String),
+        };
+        when(col(column).is_not_null().any(true))
+            .then(source)
+            .otherwise(lit(NULL).cast(DataType::String))
+            .alias(source_column(column))
+    }
+}
+
+/// JSON object mapping each of `columns` to the id of the record its value was taken from,
+/// read from the [`source_column`]s, e.g. `{"Email":"2","Telephone":"1"}`.
+/// Columns without value are left out.
+pub fn provenance_expr(columns: &[String]) -> Expr {
+    let names = columns.to_vec();
+    map_multiple(
+        move |sources: &mut [Column]| {
+            let sources = sources
+                .iter()
+                .map(|source| source.str())
+                .collect::<PolarsResult<Vec<_>>>()?;
+            let height = sources.first().map_or(0, |source| source.len());
+            let provenance = (0..height)
+                .map(|row| {
+                    let map = names
+                        .iter()
+                        .zip(&sources)
+                        .filter_map(|(name, source)| {
+                            source
+                                .get(row)
+                                .map(|id| (name.clone(), serde_json::Value::from(id)))
+                        })
+                        .collect::<serde_json::Map<_, _>>();
+                    serde_json::Value::Object(map).to_string()
+                })
+                .collect::<StringChunked>();
+            Ok(Some(provenance.into_column()))
+        },
+        columns
+            .iter()
+            .map(|column| col(source_column(column).as_str()))
+            .collect::<Vec<_>>(),
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    fn df_cluster() -> DataFrame {
+        df![
+            "cluster_id" => &[1i64, 1, 1, 1],
+            "ID" => &[1i64, 2, 3, 4],
+            "Id_source" => &["3", "1", "2", "2"],
+            "Updated" => &[Some(20240101i64), Some(20250101), None, Some(20230101)],
+            "Email" => &[Some("a@x.fr"), Some("b@x.fr"), Some("b@x.fr"), None],
+        ]
+        .unwrap()
+    }
+
+    fn survive(survivorship: Survivorship) -> (Option<String>, Option<String>) {
+        let mut lf = df_cluster().lazy();
+        if let Some(expr) = survivorship.prepare_expr("Email", "cluster_id") {
+            lf = lf.with_column(expr);
+        }
+        let df = lf
+            .group_by([col("cluster_id")])
+            .agg([
+                survivorship.value_expr("Email", "ID"),
+                survivorship.source_expr("Email", "ID", "/"),
+            ])
+            .collect()
+            .unwrap();
+        let value = |column: &str| {
+            df.column(column)
+                .unwrap()
+                .str()
+                .unwrap()
+                .get(0)
+                .map(String::from)
+        };
+        (value("Email"), value(&source_column("Email")))
+    }
+
+    #[test]
+    fn test_survivorship() {
+        let test_cases = vec![
+            (Survivorship::MostFrequent, ("b@x.fr", "2")),
+            (
+                Survivorship::MostRecent {
+                    order_by: "Updated".to_string(),
+                },
+                ("b@x.fr", "2"),
+            ),
+            // Same length, the smallest id wins
+            (Survivorship::Longest, ("a@x.fr", "1")),
+            (
+                Survivorship::SourcePriority {
+                    source_column: "Id_source".to_string(),
+                    priorities: vec!["2".to_string(), "3".to_string()],
+                },
+                ("b@x.fr", "3"),
+            ),
+            // The null value of the top source does not survive
+            (
+                Survivorship::SourcePriority {
+                    source_column: "Id_source".to_string(),
+                    priorities: vec!["3".to_string()],
+                },
+                ("a@x.fr", "1"),
+            ),
+        ];
+
+        for (survivorship, (value, source)) in test_cases {
+            assert_eq!(
+                survive(survivorship.clone()),
+                (Some(value.to_string()), Some(source.to_string())),
+                "Failed on survivorship: {:?}",
+                survivorship
+            );
+        }
+    }
+
+    #[test]
+    fn test_survivorship_keep_all() {
+        let df = df_cluster()
+            .lazy()
+            .group_by([col("cluster_id")])
+            .agg([
+                Survivorship::KeepAll.value_expr("Email", "ID"),
+                Survivorship::KeepAll.source_expr("Email", "ID", "/"),
+            ])
+            .collect()
+            .unwrap();
+
+        let emails = df.column("Email").unwrap().list().unwrap().get_as_series(0);
+        let emails = emails
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(emails, vec!["a@x.fr", "b@x.fr"]);
+        assert_eq!(
+            df.column(&source_column("Email"))
+                .unwrap()
+                .str()
+                .unwrap()
+                .get(0),
+            Some("1/2/3")
+        );
+    }
+
+    #[test]
+    fn test_provenance_expr() {
+        let df = df![
+            "_Email_source" => &[Some("2"), None],
+            "_Nom_source" => &[Some("1"), Some("7")],
+        ]
+        .unwrap();
+        let df = df
+            .lazy()
+            .select(
+                [provenance_expr(&["Email".to_string(), "Nom".to_string()]).alias("Provenance")],
+            )
+            .collect()
+            .unwrap();
+
+        let provenance = df
+            .column("Provenance")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            provenance,
+            vec![r#"{"Email":"2","Nom":"1"}"#, r#"{"Nom":"7"}"#]
+        );
+    }
+}
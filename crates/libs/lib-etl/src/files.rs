@@ -0,0 +1,22 @@
+//! File sources and sinks other than CSV.
+//!
+//! Excel workbooks are read only, see [`excel::ExcelSource`].
+//!
+//! Sinks write a single file, or a Hive-style partitioned directory
+//! (`<dir>/REGION=Bretagne/part-00000.parquet`) when partition columns are set.
+//! Sources read either layout back, restoring the partition columns from the directory names.
+//!
+//! Text sources (CSV, JSON) decompress `.gz`, `.zst` and `.zip` inputs on the fly,
+//! see [`compression::read_members`].
+//!
+//! [`uri::read_dataframe`] and [`uri::write_dataframe`] take either a local path or an
+//! `s3://bucket/prefix` URI, the latter going through [`s3::S3Source`] and [`s3::S3Sink`].
+
+pub mod compression;
+pub mod excel;
+pub mod ipc;
+pub mod json;
+pub mod parquet;
+pub mod partition;
+pub mod s3;
+pub mod uri;
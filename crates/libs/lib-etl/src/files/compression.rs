@@ -0,0 +1,233 @@
+use flate2::read::MultiGzDecoder;
+use glob::{MatchOptions, Pattern};
+use log::debug;
+use polars::prelude::{polars_bail, polars_err, PolarsResult};
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+};
+use zip::ZipArchive;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// `.gz`, a single file compressed with gzip.
+    Gzip,
+    /// `.zst`, a single file compressed with Zstandard.
+    Zstd,
+    /// `.zip`, an archive of one or more files.
+    Zip,
+}
+
+impl Compression {
+    /// Compression of the file from its extension, `None` for plain files.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "zip" => Some(Compression::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// Path of the decompressed file, `jdd.csv.gz` gives `jdd.csv`. Plain paths are returned as is.
+pub fn strip_compression_extension(path: &Path) -> PathBuf {
+    match Compression::from_path(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    }
+}
+
+/// A decompressed file, loaded in memory.
+pub struct Member {
+    /// Path of the file inside a zip archive, or the file name without the compression extension.
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Decompresses `path` into its member files.
+///
+/// Gzip and Zstandard files hold a single member. For zip archives, only the files matching the
+/// `members` glob (e.g. `*.csv`, `exports/jdd_*.csv`, case insensitive) are read, in archive order.
+pub fn read_members(path: &Path, members: &str) -> PolarsResult<Vec<Member>> {
+    if Compression::from_path(path).is_none() {
+        polars_bail!(ComputeError: "{} is not a compressed file", path.display());
+    }
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let decompressed = decompress(&name, data, members)?;
+    debug!(
+        "{} decompressed into {} member(s)",
+        path.display(),
+        decompressed.len()
+    );
+    Ok(decompressed)
+}
+
+/// Decompresses the content of the file `name`, e.g. an object downloaded from a bucket,
+/// like [`read_members`]. Plain files are returned as their single member.
+pub fn decompress(name: &str, data: Vec<u8>, members: &str) -> PolarsResult<Vec<Member>> {
+    let Some(compression) = Compression::from_path(Path::new(name)) else {
+        return Ok(vec![Member {
+            name: name.to_string(),
+            data,
+        }]);
+    };
+    let stripped_name = || {
+        strip_compression_extension(Path::new(name))
+            .to_string_lossy()
+            .to_string()
+    };
+
+    match compression {
+        Compression::Gzip => {
+            let mut decompressed = Vec::new();
+            MultiGzDecoder::new(data.as_slice()).read_to_end(&mut decompressed)?;
+            Ok(vec![Member {
+                name: stripped_name(),
+                data: decompressed,
+            }])
+        }
+        Compression::Zstd => Ok(vec![Member {
+            name: stripped_name(),
+            data: zstd::decode_all(data.as_slice())?,
+        }]),
+        Compression::Zip => read_zip_members(Cursor::new(data), members),
+    }
+}
+
+fn read_zip_members<R: Read + Seek>(reader: R, members: &str) -> PolarsResult<Vec<Member>> {
+    let pattern = Pattern::new(members)
+        .map_err(|e| polars_err!(ComputeError: "invalid member glob {}: {}", members, e))?;
+    let mut archive = ZipArchive::new(reader)
+        .map_err(|e| polars_err!(ComputeError: "invalid zip archive: {}", e))?;
+
+    let mut matched = Vec::new();
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index(index)
+            .map_err(|e| polars_err!(ComputeError: "invalid zip archive: {}", e))?;
+        if file.is_dir() || !pattern.matches_with(file.name(), MATCH_OPTIONS) {
+            continue;
+        }
+        // The declared size is not trusted to allocate, only to stop the read
+        let name = file.name().to_string();
+        let size = file.size();
+        let mut data = Vec::new();
+        file.take(size).read_to_end(&mut data)?;
+        matched.push(Member { name, data });
+    }
+    if matched.is_empty() {
+        return Err(polars_err!(NoData: "no member of the archive matches {}", members));
+    }
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression as GzLevel};
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    const CSV: &str = "Id_source;Nom\n1;SALLANDIER\n";
+
+    fn zip_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .expect("Zip entry creation failed");
+            writer
+                .write_all(content.as_bytes())
+                .expect("Zip write failed");
+        }
+        writer.finish().expect("Zip finish failed").into_inner()
+    }
+
+    #[test]
+    fn test_compression_from_path() {
+        let test_cases = vec![
+            ("jdd.csv.gz", Some(Compression::Gzip)),
+            ("jdd.csv.ZST", Some(Compression::Zstd)),
+            ("livraison.zip", Some(Compression::Zip)),
+            ("jdd.csv", None),
+            ("jdd", None),
+        ];
+        for (path, expected) in test_cases {
+            assert_eq!(
+                Compression::from_path(Path::new(path)),
+                expected,
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            strip_compression_extension(Path::new("files/jdd.csv.gz")),
+            Path::new("files/jdd.csv")
+        );
+    }
+
+    #[test]
+    fn test_read_members_gzip_and_zstd() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let gz_path = dir.path().join("hdd.csv.gz");
+        let mut encoder = GzEncoder::new(File::create(&gz_path).unwrap(), GzLevel::default());
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+        let zst_path = dir.path().join("hdd.csv.zst");
+        std::fs::write(&zst_path, zstd::encode_all(CSV.as_bytes(), 0).unwrap()).unwrap();
+
+        for path in [gz_path, zst_path] {
+            let members = read_members(&path, "*").expect("Decompression failed");
+
+            assert_eq!(members.len(), 1);
+            assert_eq!(members[0].name, "hdd.csv");
+            assert_eq!(members[0].data, CSV.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_read_zip_members_with_glob() {
+        let archive = zip_archive(&[
+            ("LISEZMOI.txt", "Livraison de mars"),
+            ("exports/jdd_01.csv", CSV),
+            ("exports/JDD_02.CSV", CSV),
+            ("exports/hdd_01.csv", CSV),
+        ]);
+
+        let all_csv = read_zip_members(Cursor::new(&archive), "*.csv").expect("Read failed");
+        let jdd = read_zip_members(Cursor::new(&archive), "exports/jdd_*").expect("Read failed");
+        let none = read_zip_members(Cursor::new(&archive), "*.parquet");
+
+        assert_eq!(all_csv.len(), 3);
+        assert_eq!(
+            jdd.iter()
+                .map(|member| member.name.as_str())
+                .collect::<Vec<_>>(),
+            ["exports/jdd_01.csv", "exports/JDD_02.CSV"]
+        );
+        assert!(none.is_err());
+    }
+
+    #[test]
+    fn test_decompress_plain_file() {
+        let members = decompress("exports/jdd.csv", CSV.as_bytes().to_vec(), "*")
+            .expect("Decompression failed");
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].name, "exports/jdd.csv");
+    }
+}
@@ -0,0 +1,428 @@
+use crate::schemas::SchemasEnum;
+use calamine::{open_workbook_auto, Data, DataType as _, Dimensions, Range, Reader, Sheets};
+use chrono::NaiveDateTime;
+use core::error::Error;
+use log::debug;
+use polars::prelude::{
+    polars_bail, polars_ensure, polars_err, DataFrame, DataType, DatetimeChunked, IntoColumn,
+    IntoSeries, NamedFrom, PolarsResult, Series, TimeUnit,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SheetSelector {
+    Name(String),
+    /// Position of the sheet in the workbook, starting at 0.
+    Index(usize),
+}
+
+/// Reads a sheet of an Excel workbook (`.xlsx`, `.xlsm`, `.xlsb`, `.xls`) into a DataFrame.
+///
+/// Cells are read the way a `;`-CSV export of the sheet would be parsed by [`crate::csv::csv_to_dataframe`]:
+/// - text and numbers become strings, integral numbers without decimals (`606060606`, not `606060606.0`),
+/// - the dataset schema, when set, casts its non-text columns,
+/// - columns whose cells are all dates are read as `Date`, or `Datetime` when a time is set.
+///
+/// Merged cells repeat their value over the whole merged area, rows without any value are skipped.
+pub struct ExcelSource {
+    path: PathBuf,
+    sheet: SheetSelector,
+    header_row: Option<u32>,
+    cell_range: Option<Dimensions>,
+    dataset: Option<SchemasEnum>,
+}
+
+impl ExcelSource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        ExcelSource {
+            path: path.as_ref().to_path_buf(),
+            sheet: SheetSelector::Index(0),
+            header_row: None,
+            cell_range: None,
+            dataset: None,
+        }
+    }
+
+    pub fn with_sheet(mut self, sheet: SheetSelector) -> Self {
+        self.sheet = sheet;
+        self
+    }
+
+    /// Row holding the column names, starting at 0 from the top of the sheet.
+    /// Defaults to the first non-empty row of the cell range, and must be inside it.
+    pub fn with_header_row(mut self, header_row: u32) -> Self {
+        self.header_row = Some(header_row);
+        self
+    }
+
+    /// Restricts the read to a cell range in A1 notation, e.g. `B3:H200`.
+    pub fn with_cell_range(mut self, cell_range: &str) -> Result<Self, Box<dyn Error>> {
+        self.cell_range = Some(parse_cell_range(cell_range)?);
+        Ok(self)
+    }
+
+    pub fn with_dataset_schema(mut self, se: SchemasEnum) -> Self {
+        self.dataset = Some(se);
+        self
+    }
+
+    pub fn read(&self) -> Result<DataFrame, Box<dyn Error>> {
+        let mut workbook = open_workbook_auto(&self.path)?;
+        let sheet_name = match &self.sheet {
+            SheetSelector::Name(name) => name.clone(),
+            SheetSelector::Index(index) => workbook
+                .sheet_names()
+                .get(*index)
+                .cloned()
+                .ok_or_else(|| format!("Workbook has no sheet at index {}", index))?,
+        };
+        let range = workbook.worksheet_range(&sheet_name)?;
+        let merged_cells = merged_cells(&mut workbook, &sheet_name)?;
+        debug!(
+            "Sheet {} of {}: {:?} cells, {} merged areas",
+            sheet_name,
+            self.path.display(),
+            range.get_size(),
+            merged_cells.len()
+        );
+
+        let mut df = range_to_dataframe(&range, &merged_cells, self.cell_range, self.header_row)?;
+        if let Some(se) = self.dataset {
+            for (name, dtype) in se.polars_schema().iter() {
+                let column = df.column(name)?;
+                if column.dtype() == &DataType::String && dtype != &DataType::String {
+                    let column = column.strict_cast(dtype)?;
+                    df.with_column(column)?;
+                }
+            }
+        }
+        Ok(df)
+    }
+}
+
+fn merged_cells<RS>(
+    workbook: &mut Sheets<RS>,
+    sheet_name: &str,
+) -> Result<Vec<Dimensions>, Box<dyn Error>>
+where
+    RS: std::io::Read + std::io::Seek,
+{
+    let merged_cells = match workbook {
+        Sheets::Xlsx(xlsx) => {
+            xlsx.load_merged_regions()?;
+            xlsx.merged_regions_by_sheet(sheet_name)
+                .into_iter()
+                .map(|(_, _, dimensions)| *dimensions)
+                .collect()
+        }
+        Sheets::Xls(xls) => xls.worksheet_merge_cells(sheet_name).unwrap_or_default(),
+        // Merged areas are not exposed for the other formats, only their first cell has a value
+        _ => Vec::new(),
+    };
+    Ok(merged_cells)
+}
+
+/// Builds the DataFrame from the cells of `range` (absolute positions) inside `bounds`.
+fn range_to_dataframe(
+    range: &Range<Data>,
+    merged_cells: &[Dimensions],
+    bounds: Option<Dimensions>,
+    header_row: Option<u32>,
+) -> PolarsResult<DataFrame> {
+    let (Some(start), Some(end)) = (range.start(), range.end()) else {
+        return Ok(DataFrame::empty());
+    };
+    let bounds = bounds.unwrap_or(Dimensions { start, end });
+
+    let cell = |row: u32, col: u32| -> &Data {
+        let position = merged_cells
+            .iter()
+            .find(|area| area.contains(row, col))
+            .map(|area| area.start)
+            .unwrap_or((row, col));
+        range.get_value(position).unwrap_or(&Data::Empty)
+    };
+    let is_empty_row =
+        |row: u32| (bounds.start.1..=bounds.end.1).all(|col| cell(row, col).is_empty());
+
+    let header_row = match header_row {
+        Some(row) => {
+            polars_ensure!(
+                (bounds.start.0..=bounds.end.0).contains(&row),
+                ComputeError: "header row {} is outside the rows {} to {} of the cell range",
+                row, bounds.start.0, bounds.end.0
+            );
+            row
+        }
+        None => (bounds.start.0..=bounds.end.0)
+            .find(|row| !is_empty_row(*row))
+            .ok_or_else(|| polars_err!(NoData: "no header row in the cell range"))?,
+    };
+    let rows = (header_row..=bounds.end.0)
+        .skip(1)
+        .filter(|row| !is_empty_row(*row))
+        .collect::<Vec<_>>();
+
+    let mut names: Vec<String> = Vec::new();
+    let mut columns = Vec::new();
+    for col in bounds.start.1..=bounds.end.1 {
+        let cells = rows.iter().map(|row| cell(*row, col)).collect::<Vec<_>>();
+        let name = cell_to_string(cell(header_row, col)).unwrap_or_default();
+        if name.is_empty() && cells.iter().all(|cell| cell.is_empty()) {
+            continue;
+        }
+        let name = unique_column_name(&names, name, col);
+        columns.push(cells_to_series(&name, &cells)?.into_column());
+        names.push(name);
+    }
+    DataFrame::new(columns)
+}
+
+/// Names empty headers after their column letter and suffixes duplicates, as polars needs unique names.
+fn unique_column_name(names: &[String], name: String, col: u32) -> String {
+    let name = if name.is_empty() {
+        column_letters(col)
+    } else {
+        name
+    };
+    if !names.contains(&name) {
+        return name;
+    }
+    (1..)
+        .map(|n| format!("{}_{}", name, n))
+        .find(|candidate| !names.contains(candidate))
+        .expect("unbounded suffixes")
+}
+
+fn cells_to_series(name: &str, cells: &[&Data]) -> PolarsResult<Series> {
+    let is_date_column = cells.iter().any(|cell| !cell.is_empty())
+        && cells
+            .iter()
+            .all(|cell| cell.is_empty() || cell_to_datetime(cell).is_some());
+    if !is_date_column {
+        let values = cells
+            .iter()
+            .map(|cell| cell_to_string(cell))
+            .collect::<Vec<_>>();
+        return Ok(Series::new(name.into(), values));
+    }
+
+    let values = cells
+        .iter()
+        .map(|cell| cell_to_datetime(cell))
+        .collect::<Vec<_>>();
+    let has_time = values
+        .iter()
+        .flatten()
+        .any(|datetime| datetime.time() != chrono::NaiveTime::MIN);
+    if has_time {
+        Ok(DatetimeChunked::from_naive_datetime_options(
+            name.into(),
+            values,
+            TimeUnit::Microseconds,
+        )
+        .into_series())
+    } else {
+        let dates = values
+            .iter()
+            .map(|datetime| datetime.map(|datetime| datetime.date()))
+            .collect::<Vec<_>>();
+        Ok(Series::new(name.into(), dates))
+    }
+}
+
+/// Text of the cell as Excel writes it in a CSV export, `None` for empty and error cells.
+fn cell_to_string(cell: &Data) -> Option<String> {
+    match cell {
+        Data::Empty | Data::Error(_) => None,
+        Data::String(s) if s.trim().is_empty() => None,
+        Data::String(s) => Some(s.clone()),
+        Data::Int(i) => Some(i.to_string()),
+        // f64 Display never adds a trailing `.0` nor an exponent
+        Data::Float(f) => Some(f.to_string()),
+        Data::Bool(b) => Some(if *b { "TRUE" } else { "FALSE" }.to_string()),
+        Data::DateTime(datetime) => datetime
+            .as_datetime()
+            .map(|datetime| datetime.to_string())
+            .or_else(|| Some(datetime.to_string())),
+        Data::DateTimeIso(s) | Data::DurationIso(s) => Some(s.clone()),
+    }
+}
+
+fn cell_to_datetime(cell: &Data) -> Option<NaiveDateTime> {
+    match cell {
+        Data::DateTime(datetime) if datetime.is_datetime() => datetime.as_datetime(),
+        Data::DateTimeIso(_) => cell.as_datetime(),
+        _ => None,
+    }
+}
+
+/// Parses an A1 notation range (`B3:H200`) into 0-based `(row, column)` positions.
+fn parse_cell_range(cell_range: &str) -> Result<Dimensions, Box<dyn Error>> {
+    let (start, end) = cell_range
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid cell range {}, expected e.g. A1:F200", cell_range))?;
+    let (start, end) = (parse_cell_ref(start)?, parse_cell_ref(end)?);
+    if start.0 > end.0 || start.1 > end.1 {
+        return Err(format!("Invalid cell range {}, start after end", cell_range).into());
+    }
+    Ok(Dimensions { start, end })
+}
+
+fn parse_cell_ref(cell_ref: &str) -> PolarsResult<(u32, u32)> {
+    let cell_ref = cell_ref.trim().replace('$', "").to_uppercase();
+    let digits_at = cell_ref
+        .find(|c: char| c.is_ascii_digit())
+        .filter(|index| *index > 0)
+        .ok_or_else(|| polars_err!(ComputeError: "invalid cell reference {}", cell_ref))?;
+    let (letters, digits) = cell_ref.split_at(digits_at);
+    if !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        polars_bail!(ComputeError: "invalid cell reference {}", cell_ref);
+    }
+    let col = letters
+        .chars()
+        .try_fold(0u32, |acc, c| {
+            acc.checked_mul(26)?.checked_add(c as u32 - 'A' as u32 + 1)
+        })
+        .and_then(|col| col.checked_sub(1))
+        .ok_or_else(
+            || polars_err!(ComputeError: "column of cell reference {} out of range", cell_ref),
+        )?;
+    let row = digits
+        .parse::<u32>()
+        .ok()
+        .and_then(|row| row.checked_sub(1))
+        .ok_or_else(|| polars_err!(ComputeError: "invalid cell reference {}", cell_ref))?;
+    Ok((row, col))
+}
+
+fn column_letters(col: u32) -> String {
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.push((b'A' + rem as u8) as char);
+        n = (n - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+    use chrono::NaiveDate;
+    use polars::prelude::AnyValue;
+
+    fn excel_date(value: f64) -> Data {
+        Data::DateTime(ExcelDateTime::new(
+            value,
+            ExcelDateTimeType::DateTime,
+            false,
+        ))
+    }
+
+    /// Title row, blank row, header, a merged `RAISON_SOCIALE` over two rows, a blank row.
+    fn sheet() -> (Range<Data>, Vec<Dimensions>) {
+        let mut range = Range::new((0, 0), (6, 3));
+        range.set_value((0, 0), Data::String("Extraction JDD".to_string()));
+        range.set_value((2, 0), Data::String("RAISON_SOCIALE".to_string()));
+        range.set_value((2, 1), Data::String("TELEPHONE".to_string()));
+        range.set_value((2, 2), Data::String("DATE_CREATION".to_string()));
+        range.set_value((3, 0), Data::String("Société dupont".to_string()));
+        range.set_value((3, 1), Data::Float(146773218.0));
+        range.set_value((3, 2), excel_date(44256.0));
+        range.set_value((4, 1), Data::Int(606060606));
+        range.set_value((6, 0), Data::String("CARRE SAS".to_string()));
+        range.set_value((6, 1), Data::Float(1.5));
+        let merged = vec![Dimensions {
+            start: (3, 0),
+            end: (4, 0),
+        }];
+        (range, merged)
+    }
+
+    #[test]
+    fn test_range_to_dataframe() {
+        let (range, merged) = sheet();
+
+        let df = range_to_dataframe(&range, &merged, None, Some(2)).expect("Conversion failed");
+
+        assert_eq!(
+            df.get_column_names_str(),
+            ["RAISON_SOCIALE", "TELEPHONE", "DATE_CREATION"]
+        );
+        assert_eq!(df.height(), 3);
+        let test_cases = vec![
+            ("RAISON_SOCIALE", 1, AnyValue::String("Société dupont")),
+            ("TELEPHONE", 0, AnyValue::String("146773218")),
+            ("TELEPHONE", 1, AnyValue::String("606060606")),
+            ("TELEPHONE", 2, AnyValue::String("1.5")),
+            ("DATE_CREATION", 1, AnyValue::Null),
+        ];
+        for (column, row, expected) in test_cases {
+            assert_eq!(
+                df.column(column).unwrap().get(row).unwrap(),
+                expected,
+                "{} at row {}",
+                column,
+                row
+            );
+        }
+        let dates = df.column("DATE_CREATION").unwrap();
+        assert_eq!(dates.dtype(), &DataType::Date);
+        assert_eq!(
+            dates.date().unwrap().as_date_iter().next().flatten(),
+            NaiveDate::from_ymd_opt(2021, 3, 1)
+        );
+    }
+
+    #[test]
+    fn test_range_to_dataframe_with_bounds() {
+        let (range, merged) = sheet();
+        let bounds = parse_cell_range("B3:B7").expect("Invalid range");
+
+        let df =
+            range_to_dataframe(&range, &merged, Some(bounds), None).expect("Conversion failed");
+
+        assert_eq!(df.get_column_names_str(), ["TELEPHONE"]);
+        assert_eq!(df.height(), 3);
+    }
+
+    #[test]
+    fn test_range_to_dataframe_header_outside_bounds() {
+        let (range, merged) = sheet();
+        let bounds = parse_cell_range("B3:B7").expect("Invalid range");
+
+        assert!(range_to_dataframe(&range, &merged, Some(bounds), Some(0)).is_err());
+        assert!(range_to_dataframe(&range, &merged, Some(bounds), Some(u32::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_parse_cell_ref() {
+        let test_cases = vec![
+            ("A1", Some((0, 0))),
+            ("$B$3", Some((2, 1))),
+            ("aa10", Some((9, 26))),
+            ("A0", None),
+            ("ZZZZZZZ1", None),
+            ("12", None),
+            ("B", None),
+        ];
+        for (cell_ref, expected) in test_cases {
+            assert_eq!(parse_cell_ref(cell_ref).ok(), expected, "{}", cell_ref);
+        }
+    }
+
+    #[test]
+    fn test_unique_column_name() {
+        let names = vec!["NOM".to_string(), "NOM_1".to_string()];
+
+        assert_eq!(unique_column_name(&names, "NOM".to_string(), 0), "NOM_2");
+        assert_eq!(unique_column_name(&names, String::new(), 27), "AB");
+        assert_eq!(
+            unique_column_name(&names, "PRENOM".to_string(), 1),
+            "PRENOM"
+        );
+    }
+}
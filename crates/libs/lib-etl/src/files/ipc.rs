@@ -0,0 +1,118 @@
+use super::partition::write_partitioned;
+use polars::{
+    io::HiveOptions,
+    prelude::{
+        DataFrame, IpcCompression, IpcWriter, LazyFrame, PolarsResult, ScanArgsIpc, SerWriter,
+    },
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// Writes DataFrames as Arrow IPC (Feather v2) files, the fastest format to read back into Polars.
+pub struct IpcSink {
+    path: PathBuf,
+    compression: Option<IpcCompression>,
+    partition_by: Vec<String>,
+}
+
+impl IpcSink {
+    /// `path` is the file to write, or the root directory when partitioned.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        IpcSink {
+            path: path.as_ref().to_path_buf(),
+            compression: Some(IpcCompression::default()),
+            partition_by: Vec::new(),
+        }
+    }
+
+    /// Compression of the record batches, `None` to write them uncompressed.
+    pub fn with_compression(mut self, compression: Option<IpcCompression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Columns to partition the output by, e.g. `REGION` or `Id_source`.
+    pub fn with_partition_by<S: AsRef<str>>(mut self, columns: &[S]) -> Self {
+        self.partition_by = columns.iter().map(|c| c.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Writes `df` and returns the written files.
+    pub fn write(&self, df: &mut DataFrame) -> PolarsResult<Vec<PathBuf>> {
+        write_partitioned(df, &self.path, &self.partition_by, "arrow", |df, path| {
+            IpcWriter::new(File::create(path)?)
+                .with_compression(self.compression)
+                .finish(df)
+        })
+    }
+}
+
+/// Reads an Arrow IPC file, a glob, or a Hive partitioned directory written by [`IpcSink`].
+pub struct IpcSource {
+    path: PathBuf,
+}
+
+impl IpcSource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        IpcSource {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn scan(&self) -> PolarsResult<LazyFrame> {
+        let args = ScanArgsIpc {
+            hive_options: HiveOptions {
+                enabled: Some(self.path.is_dir()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        LazyFrame::scan_ipc(&self.path, args)
+    }
+
+    pub fn read(&self) -> PolarsResult<DataFrame> {
+        self.scan()?.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    #[test]
+    fn test_ipc_partitioned_by_id_source() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let mut df = df![
+            "Id_source" => &[1, 2, 1],
+            "Nom" => &["SALLANDIER", "CARRE", "SALLANDIER"],
+            "Telephone" => &[Some("0606060606"), None, Some("0607070707")],
+        ]
+        .expect("DataFrame creation failed");
+
+        let written = IpcSink::new(dir.path())
+            .with_partition_by(&["Id_source"])
+            .write(&mut df)
+            .expect("Write failed");
+        let read = IpcSource::new(dir.path())
+            .scan()
+            .expect("Scan failed")
+            .sort(["Id_source"], Default::default())
+            .collect()
+            .expect("Read failed");
+
+        assert_eq!(written.len(), 2);
+        assert!(dir
+            .path()
+            .join("Id_source=1")
+            .join("part-00000.arrow")
+            .exists());
+        assert_eq!(read.height(), 3);
+        assert_eq!(
+            read.column("Nom").unwrap().str().unwrap().get(2),
+            Some("CARRE")
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use super::compression::{read_members, strip_compression_extension, Compression};
+use crate::schemas::SchemasEnum;
+use polars::{
+    io::mmap::MmapBytesReader,
+    prelude::{
+        DataFrame, DataType, JsonFormat, JsonReader, JsonWriter, PolarsResult, Schema, SerReader,
+        SerWriter,
+    },
+};
+use serde::Deserialize;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Cursor},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const DEFAULT_INFER_SCHEMA_LENGTH: usize = 100;
+/// Members of a zip archive read by default, `.json`, `.jsonl` and `.ndjson` files at any depth.
+const DEFAULT_JSON_MEMBERS: &str = "*json*";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonLayout {
+    /// One JSON object per line (NDJSON / JSON Lines).
+    #[default]
+    Lines,
+    /// A single JSON array of objects.
+    Array,
+}
+
+impl JsonLayout {
+    /// Layout matching the file extension: `.json` is an array, anything else is read as lines.
+    /// The compression extension is ignored, `.json.gz` is an array.
+    pub fn from_path(path: &Path) -> Self {
+        match strip_compression_extension(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("json") => JsonLayout::Array,
+            _ => JsonLayout::Lines,
+        }
+    }
+
+    pub(crate) fn polars_format(&self) -> JsonFormat {
+        match self {
+            JsonLayout::Lines => JsonFormat::JsonLines,
+            JsonLayout::Array => JsonFormat::Json,
+        }
+    }
+}
+
+/// Writes DataFrames as JSON Lines or as a JSON array.
+pub struct JsonSink {
+    path: PathBuf,
+    layout: JsonLayout,
+}
+
+impl JsonSink {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        JsonSink {
+            layout: JsonLayout::from_path(&path),
+            path,
+        }
+    }
+
+    pub fn with_layout(mut self, layout: JsonLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn write(&self, df: &mut DataFrame) -> PolarsResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        JsonWriter::new(BufWriter::new(File::create(&self.path)?))
+            .with_json_format(self.layout.polars_format())
+            .finish(df)
+    }
+}
+
+/// Reads JSON Lines or JSON array files.
+///
+/// Without dataset schema, the column dtypes are inferred from the first records.
+/// With a dataset schema, the dataset columns are read whatever their JSON type (e.g. `"1"` or `1`)
+/// and cast to the dataset dtypes, so files produced from CSV records load like the CSV itself.
+///
+/// `.gz`, `.zst` and `.zip` files are decompressed, the members of an archive are stacked.
+/// Unless set with [`JsonSource::with_layout`], the layout of each file or member follows its
+/// extension, see [`JsonLayout::from_path`].
+pub struct JsonSource {
+    path: PathBuf,
+    layout: Option<JsonLayout>,
+    dataset: Option<SchemasEnum>,
+    infer_schema_length: Option<usize>,
+    members: String,
+}
+
+impl JsonSource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        JsonSource {
+            path: path.as_ref().to_path_buf(),
+            layout: None,
+            dataset: None,
+            infer_schema_length: Some(DEFAULT_INFER_SCHEMA_LENGTH),
+            members: DEFAULT_JSON_MEMBERS.to_string(),
+        }
+    }
+
+    pub fn with_layout(mut self, layout: JsonLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    pub fn with_dataset_schema(mut self, se: SchemasEnum) -> Self {
+        self.dataset = Some(se);
+        self
+    }
+
+    /// Number of records used to infer the dtypes, `None` to scan the whole file.
+    pub fn with_infer_schema_length(mut self, infer_schema_length: Option<usize>) -> Self {
+        self.infer_schema_length = infer_schema_length;
+        self
+    }
+
+    /// Glob of the zip archive members to read, e.g. `exports/jdd_*.jsonl`.
+    pub fn with_members(mut self, members: &str) -> Self {
+        self.members = members.to_string();
+        self
+    }
+
+    pub fn read(&self) -> PolarsResult<DataFrame> {
+        if Compression::from_path(&self.path).is_none() {
+            return self.read_from(File::open(&self.path)?);
+        }
+
+        let mut df: Option<DataFrame> = None;
+        for member in read_members(&self.path, &self.members)? {
+            let layout = self
+                .layout
+                .unwrap_or_else(|| JsonLayout::from_path(Path::new(&member.name)));
+            let member_df = self.read_with_layout(Cursor::new(member.data), layout)?;
+            match df.as_mut() {
+                Some(df) => {
+                    df.vstack_mut(&member_df)?;
+                }
+                None => df = Some(member_df),
+            }
+        }
+        let mut df = df.unwrap_or_default();
+        df.as_single_chunk_par();
+        Ok(df)
+    }
+
+    /// Reads the records from `reader` instead of the source path, e.g. an in-memory buffer.
+    pub fn read_from<R: MmapBytesReader>(&self, reader: R) -> PolarsResult<DataFrame> {
+        let layout = self
+            .layout
+            .unwrap_or_else(|| JsonLayout::from_path(&self.path));
+        self.read_with_layout(reader, layout)
+    }
+
+    fn read_with_layout<R: MmapBytesReader>(
+        &self,
+        reader: R,
+        layout: JsonLayout,
+    ) -> PolarsResult<DataFrame> {
+        let reader = JsonReader::new(reader).with_json_format(layout.polars_format());
+
+        let Some(se) = self.dataset else {
+            return reader
+                .infer_schema_len(self.infer_schema_length.and_then(NonZeroUsize::new))
+                .finish();
+        };
+
+        let dataset_schema = se.polars_schema();
+        let text_schema = dataset_schema
+            .iter_names()
+            .map(|name| (name.clone(), DataType::String))
+            .collect::<Schema>();
+        let mut df = reader.with_schema(Arc::new(text_schema)).finish()?;
+        for (name, dtype) in dataset_schema.iter() {
+            if dtype != &DataType::String {
+                let column = df.column(name)?.strict_cast(dtype)?;
+                df.with_column(column)?;
+            }
+        }
+        Ok(df)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::{hdd::Hdd, AsString};
+    use polars::prelude::AnyValue;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_json_layout_from_path() {
+        let test_cases = vec![
+            ("jdd.json", JsonLayout::Array),
+            ("jdd.jsonl", JsonLayout::Lines),
+            ("jdd.ndjson", JsonLayout::Lines),
+            ("jdd.json.gz", JsonLayout::Array),
+            ("jdd.jsonl.zst", JsonLayout::Lines),
+        ];
+        for (path, expected) in test_cases {
+            assert_eq!(JsonLayout::from_path(Path::new(path)), expected);
+        }
+    }
+
+    #[test]
+    fn test_read_with_dataset_schema() {
+        let lines = concat!(
+            r#"{"Id_source": "1", "Telephone": "0606060606", "Nom": "SALLANDIER"}"#,
+            "\n",
+            r#"{"Id_source": 2, "Telephone": 607070707, "Nom": "CARRE", "Email": null}"#,
+            "\n",
+        );
+
+        let df = JsonSource::new("hdd.jsonl")
+            .with_dataset_schema(SchemasEnum::Hdd)
+            .read_from(Cursor::new(lines))
+            .expect("Read failed");
+
+        assert_eq!(df.width(), SchemasEnum::Hdd.fields().len());
+        let test_cases = vec![
+            (Hdd::IdSource, 0, AnyValue::Int32(1)),
+            (Hdd::IdSource, 1, AnyValue::Int32(2)),
+            (Hdd::Telephone, 0, AnyValue::String("0606060606")),
+            (Hdd::Telephone, 1, AnyValue::String("607070707")),
+            (Hdd::Siret, 0, AnyValue::Null),
+        ];
+        for (column, row, expected) in test_cases {
+            assert_eq!(
+                df.column(column.as_str()).unwrap().get(row).unwrap(),
+                expected,
+                "{} at row {}",
+                column.as_str(),
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_zip_of_json_arrays() {
+        use std::io::Write;
+        use zip::write::{SimpleFileOptions, ZipWriter};
+
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let path = dir.path().join("livraison.zip");
+        let mut writer = ZipWriter::new(File::create(&path).expect("Zip creation failed"));
+        let files = [
+            ("jdd_1.json", r#"[{"Nom": "SALLANDIER"}, {"Nom": "CARRE"}]"#),
+            ("jdd_2.jsonl", "{\"Nom\": \"MARTIN\"}\n"),
+        ];
+        for (name, content) in files {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .expect("Zip entry creation failed");
+            writer
+                .write_all(content.as_bytes())
+                .expect("Zip write failed");
+        }
+        writer.finish().expect("Zip finish failed");
+
+        let df = JsonSource::new(&path).read().expect("Read failed");
+
+        let noms = df
+            .column("Nom")
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_no_null_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(noms, vec!["SALLANDIER", "CARRE", "MARTIN"]);
+    }
+
+    #[test]
+    fn test_json_array_round_trip() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let path = dir.path().join("scores.json");
+        let mut df = polars::df![
+            "Nom" => &["SALLANDIER", "CARRE"],
+            "score" => &[0.5, 1.0],
+        ]
+        .expect("DataFrame creation failed");
+
+        JsonSink::new(&path).write(&mut df).expect("Write failed");
+        let content = fs::read_to_string(&path).expect("Missing file");
+        let read = JsonSource::new(&path).read().expect("Read failed");
+
+        assert!(content.starts_with('['));
+        assert!(read.equals_missing(&df));
+    }
+}
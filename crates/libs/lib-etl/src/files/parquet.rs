@@ -0,0 +1,152 @@
+use super::partition::write_partitioned;
+use polars::{
+    io::HiveOptions,
+    prelude::{
+        DataFrame, LazyFrame, ParquetCompression, ParquetWriter, PolarsResult, ScanArgsParquet,
+        StatisticsOptions,
+    },
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// Writes DataFrames as Parquet, compressed with zstd by default.
+pub struct ParquetSink {
+    path: PathBuf,
+    compression: ParquetCompression,
+    row_group_size: Option<usize>,
+    partition_by: Vec<String>,
+}
+
+impl ParquetSink {
+    /// `path` is the file to write, or the root directory when partitioned.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        ParquetSink {
+            path: path.as_ref().to_path_buf(),
+            compression: ParquetCompression::default(),
+            row_group_size: None,
+            partition_by: Vec::new(),
+        }
+    }
+
+    pub fn with_compression(mut self, compression: ParquetCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Maximum number of rows per row group, polars picks one when unset.
+    pub fn with_row_group_size(mut self, row_group_size: Option<usize>) -> Self {
+        self.row_group_size = row_group_size;
+        self
+    }
+
+    /// Columns to partition the output by, e.g. `REGION` or `Id_source`.
+    pub fn with_partition_by<S: AsRef<str>>(mut self, columns: &[S]) -> Self {
+        self.partition_by = columns.iter().map(|c| c.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Writes `df` and returns the written files.
+    pub fn write(&self, df: &mut DataFrame) -> PolarsResult<Vec<PathBuf>> {
+        write_partitioned(df, &self.path, &self.partition_by, "parquet", |df, path| {
+            ParquetWriter::new(File::create(path)?)
+                .with_compression(self.compression)
+                .with_row_group_size(self.row_group_size)
+                .with_statistics(StatisticsOptions::default())
+                .finish(df)?;
+            Ok(())
+        })
+    }
+}
+
+/// Reads a Parquet file, a glob, or a Hive partitioned directory written by [`ParquetSink`].
+pub struct ParquetSource {
+    path: PathBuf,
+}
+
+impl ParquetSource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        ParquetSource {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Lazily scans the files, filters on partition columns skip the partitions that do not match.
+    pub fn scan(&self) -> PolarsResult<LazyFrame> {
+        let args = ScanArgsParquet {
+            hive_options: HiveOptions {
+                enabled: Some(self.path.is_dir()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        LazyFrame::scan_parquet(&self.path, args)
+    }
+
+    pub fn read(&self) -> PolarsResult<DataFrame> {
+        self.scan()?.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::{df, prelude::*};
+
+    fn jdd() -> DataFrame {
+        df![
+            "RAISON_SOCIALE" => &["Société dupont", "CARRE SAS", "GRDF"],
+            "SIRET" => &[Some("44316952400120"), None, Some("44478637100014")],
+            "REGION" => &[Some("Bretagne"), Some("Île-de-France"), Some("Bretagne")],
+        ]
+        .expect("DataFrame creation failed")
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let path = dir.path().join("jdd.parquet");
+        let mut df = jdd();
+
+        let written = ParquetSink::new(&path)
+            .with_compression(ParquetCompression::Snappy)
+            .with_row_group_size(Some(2))
+            .write(&mut df)
+            .expect("Write failed");
+        let read = ParquetSource::new(&path).read().expect("Read failed");
+
+        assert_eq!(written, vec![path]);
+        assert!(read.equals_missing(&df));
+    }
+
+    #[test]
+    fn test_parquet_partitioned_by_region() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let mut df = jdd();
+
+        let written = ParquetSink::new(dir.path())
+            .with_partition_by(&["REGION"])
+            .write(&mut df)
+            .expect("Write failed");
+
+        assert_eq!(written.len(), 2);
+        assert!(dir
+            .path()
+            .join("REGION=Bretagne")
+            .join("part-00000.parquet")
+            .exists());
+
+        let bretagne = ParquetSource::new(dir.path())
+            .scan()
+            .expect("Scan failed")
+            .filter(col("REGION").eq(lit("Bretagne")))
+            .collect()
+            .expect("Read failed");
+        assert_eq!(bretagne.height(), 2);
+        assert_eq!(
+            bretagne.get_column_names_str(),
+            ["RAISON_SOCIALE", "SIRET", "REGION"]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use log::debug;
+use polars::prelude::{polars_bail, polars_ensure, DataFrame, DataType, PolarsResult};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directory name used by Hive for NULL partition values.
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Writes `df` with `write_file`, into `path` itself without partition columns, otherwise into one
+/// `<path>/<column>=<value>/.../part-00000.<extension>` file per distinct value of the columns.
+/// Partition columns are not written in the files, readers get them from the directory names.
+/// The partitions of a previous write are replaced as a whole: they are written into a staging
+/// directory next to `path`, which takes the place of the previous directory once complete,
+/// so a row whose partition value changed does not stay in its former partition. A `path`
+/// holding anything but the partitions of the first column is refused.
+/// Returns the written files.
+pub fn write_partitioned<F, S>(
+    df: &mut DataFrame,
+    path: &Path,
+    partition_by: &[S],
+    extension: &str,
+    write_file: F,
+) -> PolarsResult<Vec<PathBuf>>
+where
+    F: Fn(&mut DataFrame, &Path) -> PolarsResult<()>,
+    S: AsRef<str>,
+{
+    if partition_by.is_empty() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_file(df, path)?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let partition_by = partition_by
+        .iter()
+        .map(|column| column.as_ref())
+        .collect::<Vec<_>>();
+    ensure_replaceable(path, partition_by[0])?;
+    let staging = sibling_dir(path, "staging");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    let mut written = Vec::new();
+    for partition in df.partition_by_stable(partition_by.clone(), true)? {
+        let file_path =
+            hive_path(&partition, &partition_by)?.join(format!("part-{:05}.{}", 0, extension));
+        let staging_path = staging.join(&file_path);
+        fs::create_dir_all(staging_path.parent().unwrap_or(&staging))?;
+
+        let mut partition = partition.drop_many(partition_by.iter().copied());
+        debug!(
+            "Writing {} rows into {}",
+            partition.height(),
+            staging_path.display()
+        );
+        write_file(&mut partition, &staging_path)?;
+        written.push(path.join(file_path));
+    }
+
+    // Moved aside rather than deleted, the dataset is never missing and the previous one is
+    // still there if the swap is interrupted
+    if path.exists() {
+        let previous = sibling_dir(path, "previous");
+        if previous.exists() {
+            fs::remove_dir_all(&previous)?;
+        }
+        fs::rename(path, &previous)?;
+        fs::rename(&staging, path)?;
+        fs::remove_dir_all(&previous)?;
+    } else {
+        fs::rename(&staging, path)?;
+    }
+    Ok(written)
+}
+
+/// Fails unless `path` is missing, or a directory of `<column>=<value>` partitions only.
+fn ensure_replaceable(path: &Path, column: &str) -> PolarsResult<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if !path.is_dir() {
+        polars_bail!(ComputeError: "{} is not a partitioned dataset directory", path.display());
+    }
+    let prefix = format!("{}=", encode_hive_value(column));
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        polars_ensure!(
+            entry.file_type()?.is_dir() && name.starts_with(&prefix),
+            ComputeError: "{} holds {}, which is not a partition of {}, refusing to replace it",
+            path.display(), name, column
+        );
+    }
+    Ok(())
+}
+
+/// Hidden sibling of `path`, so a scan of its parent directory does not read it.
+fn sibling_dir(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// `<column>=<value>` path of the partition, from the first row of `partition`.
+fn hive_path(partition: &DataFrame, partition_by: &[&str]) -> PolarsResult<PathBuf> {
+    polars_ensure!(partition.height() > 0, NoData: "empty partition");
+    let mut path = PathBuf::new();
+    for column in partition_by {
+        let value = partition
+            .column(column)?
+            .slice(0, 1)
+            .cast(&DataType::String)?;
+        let value = match value.str()?.get(0) {
+            Some(value) => encode_hive_value(value),
+            None => HIVE_DEFAULT_PARTITION.to_string(),
+        };
+        path.push(format!("{}={}", encode_hive_value(column), value));
+    }
+    Ok(path)
+}
+
+/// Percent-encodes the characters that cannot appear in a partition directory name.
+pub fn encode_hive_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '/' | '\\' | '=' | '%' | ':' | '?' | '*' | '"' | '<' | '>' | '|' | '#' | '\n'
+            | '\r' | '\t' => {
+                let mut buffer = [0; 4];
+                for byte in c.encode_utf8(&mut buffer).bytes() {
+                    let _ = write!(encoded, "%{:02X}", byte);
+                }
+            }
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    #[test]
+    fn test_encode_hive_value() {
+        let test_cases = vec![
+            ("Bretagne", "Bretagne"),
+            ("Île-de-France", "Île-de-France"),
+            ("Provence/Alpes", "Provence%2FAlpes"),
+            ("a=b%", "a%3Db%25"),
+        ];
+        for (value, expected) in test_cases {
+            assert_eq!(encode_hive_value(value), expected);
+        }
+    }
+
+    #[test]
+    fn test_write_partitioned_replaces_previous_partitions() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let path = dir.path().join("jdd");
+        let write_rows = |df: &mut DataFrame, file_path: &Path| -> PolarsResult<()> {
+            fs::write(file_path, df.height().to_string())?;
+            Ok(())
+        };
+        let mut bretagne = df![
+            "SIRET" => &["44316952400120", "44478637100014"],
+            "REGION" => &["Bretagne", "Bretagne"],
+        ]
+        .unwrap();
+        // The company moved to Normandie
+        let mut normandie = df![
+            "SIRET" => &["44316952400120"],
+            "REGION" => &["Normandie"],
+        ]
+        .unwrap();
+
+        write_partitioned(&mut bretagne, &path, &["REGION"], "txt", write_rows)
+            .expect("First write failed");
+        let written = write_partitioned(&mut normandie, &path, &["REGION"], "txt", write_rows)
+            .expect("Second write failed");
+
+        let normandie_path = path.join("REGION=Normandie").join("part-00000.txt");
+        assert_eq!(written, vec![normandie_path.clone()]);
+        assert_eq!(fs::read_to_string(normandie_path).unwrap(), "1");
+        assert!(!path.join("REGION=Bretagne").exists());
+        assert!(!sibling_dir(&path, "staging").exists());
+        assert!(!sibling_dir(&path, "previous").exists());
+    }
+
+    #[test]
+    fn test_write_partitioned_refuses_other_files() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        fs::write(dir.path().join("notes.txt"), "Livraison de mars").unwrap();
+        let mut df = df![
+            "SIRET" => &["44316952400120"],
+            "REGION" => &["Bretagne"],
+        ]
+        .unwrap();
+
+        let result = write_partitioned(&mut df, dir.path(), &["REGION"], "txt", |_, _| Ok(()));
+
+        assert!(result.is_err());
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_hive_path() {
+        let df = df![
+            "REGION" => &[None::<&str>],
+            "Id_source" => &[Some(3)],
+        ]
+        .expect("DataFrame creation failed");
+
+        let path = hive_path(&df, &["REGION", "Id_source"]).expect("Hive path failed");
+
+        assert_eq!(
+            path,
+            PathBuf::from(format!("REGION={}/Id_source=3", HIVE_DEFAULT_PARTITION))
+        );
+    }
+}
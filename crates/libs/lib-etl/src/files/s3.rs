@@ -0,0 +1,628 @@
+use super::{
+    compression::{decompress, strip_compression_extension, Compression},
+    json::{JsonLayout, JsonSource},
+};
+use crate::{csv::csv_read_options, schemas::SchemasEnum};
+use core::error::Error;
+use futures::TryStreamExt;
+use log::{debug, info, warn};
+use object_store::{
+    aws::AmazonS3Builder, path::Path as ObjectPath, MultipartUpload, ObjectStore, PutPayload,
+};
+use polars::{
+    io::parquet::write::BatchedWriter as BatchedParquetWriter,
+    prelude::{
+        polars_bail, CsvWriter, DataFrame, JsonWriter, ParquetReader, ParquetWriter, PolarsResult,
+        Schema, SerReader, SerWriter, StatisticsOptions,
+    },
+};
+use std::{
+    io::{self, Cursor, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::task::JoinSet;
+
+const S3_SCHEME: &str = "s3://";
+/// S3 refuses parts under 5 MiB, except for the last one.
+const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+/// Rows encoded at once before their bytes are uploaded.
+const DEFAULT_BATCH_SIZE: usize = 50_000;
+/// Parts uploaded at the same time, encoding waits beyond.
+const MAX_CONCURRENT_PARTS: usize = 4;
+/// Members of the zip objects read by default.
+const DEFAULT_MEMBERS: &str = "*";
+
+pub fn is_s3_uri(uri: &str) -> bool {
+    uri.starts_with(S3_SCHEME)
+}
+
+/// `s3://bucket/prefix` split into its bucket and key prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Uri {
+    pub bucket: String,
+    pub prefix: String,
+}
+
+impl S3Uri {
+    pub fn parse(uri: &str) -> Result<Self, Box<dyn Error>> {
+        let rest = uri
+            .strip_prefix(S3_SCHEME)
+            .ok_or_else(|| format!("{} is not an s3:// URI", uri))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(format!("{} has no bucket", uri).into());
+        }
+        Ok(S3Uri {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+/// Client for `bucket`, configured from the `AWS_*` environment variables:
+/// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`, and for MinIO
+/// `AWS_ENDPOINT=http://localhost:9000` with `AWS_ALLOW_HTTP=true`.
+pub fn s3_store_from_env(bucket: &str) -> Result<Arc<dyn ObjectStore>, Box<dyn Error>> {
+    let store = AmazonS3Builder::from_env()
+        .with_bucket_name(bucket)
+        .build()?;
+    Ok(Arc::new(store))
+}
+
+/// File format of an object, from its key once the compression extension is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectFormat {
+    /// `;` separated dataset file, as read by [`crate::csv::csv_to_dataframe`].
+    Csv,
+    Parquet,
+    Json(JsonLayout),
+}
+
+impl ObjectFormat {
+    pub fn from_key(key: &str) -> Option<Self> {
+        let path = strip_compression_extension(Path::new(key));
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(ObjectFormat::Csv),
+            "parquet" => Some(ObjectFormat::Parquet),
+            "json" | "jsonl" | "ndjson" => Some(ObjectFormat::Json(JsonLayout::from_path(&path))),
+            _ => None,
+        }
+    }
+}
+
+/// Reads CSV, Parquet and JSON objects from a bucket.
+///
+/// The prefix is either a single object key, or a "directory" whose objects are all read and
+/// stacked, in key order. Objects of unknown format (`_SUCCESS` markers, ...) are skipped,
+/// compressed objects are decompressed like local files. Hive partition columns are not restored.
+pub struct S3Source {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    dataset: Option<SchemasEnum>,
+    members: String,
+}
+
+impl S3Source {
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Self {
+        S3Source {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+            dataset: None,
+            members: DEFAULT_MEMBERS.to_string(),
+        }
+    }
+
+    /// Source for an `s3://bucket/prefix` URI, with the credentials from the environment.
+    pub fn from_uri(uri: &str) -> Result<Self, Box<dyn Error>> {
+        let uri = S3Uri::parse(uri)?;
+        Ok(S3Source::new(s3_store_from_env(&uri.bucket)?, &uri.prefix))
+    }
+
+    /// Dataset schema of the CSV and JSON objects, required for CSV.
+    pub fn with_dataset_schema(mut self, se: SchemasEnum) -> Self {
+        self.dataset = Some(se);
+        self
+    }
+
+    /// Glob of the members to read in zip objects, e.g. `*.csv`.
+    pub fn with_members(mut self, members: &str) -> Self {
+        self.members = members.to_string();
+        self
+    }
+
+    /// Keys of the objects to read, sorted.
+    pub async fn list(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        if is_readable_key(&self.prefix) {
+            return Ok(vec![self.prefix.clone()]);
+        }
+        let prefix = ObjectPath::from(self.prefix.as_str());
+        let prefix = (!self.prefix.is_empty()).then_some(&prefix);
+        let mut keys = self
+            .store
+            .list(prefix)
+            .map_ok(|meta| meta.location.to_string())
+            .try_filter(|key| futures::future::ready(is_readable_key(key)))
+            .try_collect::<Vec<_>>()
+            .await?;
+        keys.sort();
+        debug!("{} objects to read under {}", keys.len(), self.prefix);
+        Ok(keys)
+    }
+
+    pub async fn read(&self) -> Result<DataFrame, Box<dyn Error>> {
+        let keys = self.list().await?;
+        if keys.is_empty() {
+            return Err(format!("No object to read under {}", self.prefix).into());
+        }
+
+        let mut df: Option<DataFrame> = None;
+        for key in &keys {
+            let data = self
+                .store
+                .get(&ObjectPath::from(key.as_str()))
+                .await?
+                .bytes()
+                .await?;
+            for object_df in self.read_object(key, data.to_vec())? {
+                match df.as_mut() {
+                    Some(df) => {
+                        df.vstack_mut(&object_df)
+                            .map_err(|e| format!("Failed to stack {}: {}", key, e))?;
+                    }
+                    None => df = Some(object_df),
+                }
+            }
+        }
+        let mut df = df.unwrap_or_default();
+        df.as_single_chunk_par();
+        info!(
+            "Read {} rows from {} objects under {}",
+            df.height(),
+            keys.len(),
+            self.prefix
+        );
+        Ok(df)
+    }
+
+    fn read_object(&self, key: &str, data: Vec<u8>) -> PolarsResult<Vec<DataFrame>> {
+        decompress(key, data, &self.members)?
+            .into_iter()
+            .filter_map(|member| {
+                ObjectFormat::from_key(&member.name).map(|format| (member, format))
+            })
+            .map(|(member, format)| {
+                let reader = Cursor::new(member.data);
+                match (format, self.dataset) {
+                    (ObjectFormat::Csv, Some(se)) => csv_read_options(se)
+                        .into_reader_with_file_handle(reader)
+                        .finish(),
+                    (ObjectFormat::Csv, None) => {
+                        polars_bail!(ComputeError: "{} is a CSV, set the dataset schema to read it", member.name)
+                    }
+                    (ObjectFormat::Parquet, _) => ParquetReader::new(reader).finish(),
+                    (ObjectFormat::Json(layout), dataset) => {
+                        let mut source = JsonSource::new(&member.name).with_layout(layout);
+                        if let Some(se) = dataset {
+                            source = source.with_dataset_schema(se);
+                        }
+                        source.read_from(reader)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+fn is_readable_key(key: &str) -> bool {
+    ObjectFormat::from_key(key).is_some()
+        || Compression::from_path(Path::new(key)) == Some(Compression::Zip)
+}
+
+/// Writes a DataFrame as a single CSV, Parquet or JSON object, with a multipart upload.
+///
+/// The rows are encoded `batch_size` at a time and each full part is uploaded right away, so at
+/// most a batch and [`MAX_CONCURRENT_PARTS`] parts are held in memory. A failed upload is aborted.
+pub struct S3Sink {
+    store: Arc<dyn ObjectStore>,
+    key: String,
+    format: Option<ObjectFormat>,
+    part_size: usize,
+    batch_size: usize,
+}
+
+impl S3Sink {
+    pub fn new(store: Arc<dyn ObjectStore>, key: &str) -> Self {
+        S3Sink {
+            store,
+            key: key.trim_matches('/').to_string(),
+            format: ObjectFormat::from_key(key),
+            part_size: DEFAULT_PART_SIZE,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Sink for an `s3://bucket/key` URI, with the credentials from the environment.
+    pub fn from_uri(uri: &str) -> Result<Self, Box<dyn Error>> {
+        let uri = S3Uri::parse(uri)?;
+        Ok(S3Sink::new(s3_store_from_env(&uri.bucket)?, &uri.prefix))
+    }
+
+    /// Format of the object, when the key extension does not tell it.
+    pub fn with_format(mut self, format: ObjectFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Size of the uploaded parts, at least 5 MiB for S3.
+    pub fn with_part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size.max(1);
+        self
+    }
+
+    /// Number of rows encoded at once, a row group for Parquet.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Uploads `df` and returns the size of the object.
+    pub async fn write(&self, df: &mut DataFrame) -> Result<usize, Box<dyn Error>> {
+        let format = self
+            .format
+            .ok_or_else(|| format!("Unknown format for {}, set it explicitly", self.key))?;
+        let mut upload = self
+            .store
+            .put_multipart(&ObjectPath::from(self.key.as_str()))
+            .await?;
+
+        let size = match self.upload_parts(df, format, upload.as_mut()).await {
+            Ok(size) => size,
+            Err(e) => {
+                self.abort(upload.as_mut()).await;
+                return Err(e);
+            }
+        };
+        if let Err(e) = upload.complete().await {
+            self.abort(upload.as_mut()).await;
+            return Err(e.into());
+        }
+        info!(
+            "Uploaded {} rows to {} ({} bytes)",
+            df.height(),
+            self.key,
+            size
+        );
+        Ok(size)
+    }
+
+    /// Encodes `df` batch after batch into parts of `part_size` bytes and waits for their upload.
+    async fn upload_parts(
+        &self,
+        df: &DataFrame,
+        format: ObjectFormat,
+        upload: &mut dyn MultipartUpload,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut encoder = BatchEncoder::new(format, df.schema())?;
+        let mut parts = JoinSet::new();
+        let mut part_count = 0;
+        let mut part = Vec::new();
+        let mut size = 0;
+
+        let batches = df.height().div_ceil(self.batch_size).max(1);
+        for batch in 0..batches {
+            let mut batch_df = df.slice((batch * self.batch_size) as i64, self.batch_size);
+            let bytes = encoder.encode(&mut batch_df)?;
+            size += bytes.len();
+            part.extend_from_slice(&bytes);
+            while part.len() >= self.part_size {
+                let rest = part.split_off(self.part_size);
+                put_part(&mut parts, upload, std::mem::replace(&mut part, rest)).await?;
+                part_count += 1;
+            }
+        }
+        let bytes = encoder.finish()?;
+        size += bytes.len();
+        part.extend_from_slice(&bytes);
+        // The last part may be smaller, and an object needs at least one
+        if !part.is_empty() || part_count == 0 {
+            put_part(&mut parts, upload, part).await?;
+        }
+
+        while let Some(result) = parts.join_next().await {
+            result??;
+        }
+        Ok(size)
+    }
+
+    async fn abort(&self, upload: &mut dyn MultipartUpload) {
+        match upload.abort().await {
+            Ok(()) => warn!("Upload of {} aborted", self.key),
+            Err(e) => warn!("Failed to abort the upload of {}: {}", self.key, e),
+        }
+    }
+}
+
+/// Starts the upload of `part`, once fewer than [`MAX_CONCURRENT_PARTS`] are running.
+async fn put_part(
+    parts: &mut JoinSet<object_store::Result<()>>,
+    upload: &mut dyn MultipartUpload,
+    part: Vec<u8>,
+) -> Result<(), Box<dyn Error>> {
+    while parts.len() >= MAX_CONCURRENT_PARTS {
+        if let Some(result) = parts.join_next().await {
+            result??;
+        }
+    }
+    parts.spawn(upload.put_part(PutPayload::from(part)));
+    Ok(())
+}
+
+/// Writer whose bytes are taken after each batch, for the encoders that own their writer.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().expect("Poisoned buffer"))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .expect("Poisoned buffer")
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes a DataFrame one batch of rows at a time, the bytes of the batches make up the object.
+enum BatchEncoder {
+    Csv {
+        header: bool,
+    },
+    Json {
+        layout: JsonLayout,
+        objects: usize,
+    },
+    Parquet {
+        writer: BatchedParquetWriter<SharedBuffer>,
+        buffer: SharedBuffer,
+    },
+}
+
+impl BatchEncoder {
+    fn new(format: ObjectFormat, schema: &Schema) -> PolarsResult<Self> {
+        Ok(match format {
+            ObjectFormat::Csv => BatchEncoder::Csv { header: true },
+            ObjectFormat::Json(layout) => BatchEncoder::Json { layout, objects: 0 },
+            ObjectFormat::Parquet => {
+                let buffer = SharedBuffer::default();
+                let writer = ParquetWriter::new(buffer.clone())
+                    .with_statistics(StatisticsOptions::default())
+                    .batched(schema)?;
+                BatchEncoder::Parquet { writer, buffer }
+            }
+        })
+    }
+
+    fn encode(&mut self, df: &mut DataFrame) -> PolarsResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self {
+            BatchEncoder::Csv { header } => {
+                CsvWriter::new(&mut bytes)
+                    .include_header(*header)
+                    .with_separator(b';')
+                    .finish(df)?;
+                *header = false;
+            }
+            BatchEncoder::Json {
+                layout: JsonLayout::Lines,
+                ..
+            } => JsonWriter::new(&mut bytes)
+                .with_json_format(JsonLayout::Lines.polars_format())
+                .finish(df)?,
+            BatchEncoder::Json {
+                layout: JsonLayout::Array,
+                objects,
+            } => {
+                // Objects never span lines, the batches are joined into a single array
+                let mut lines = Vec::new();
+                JsonWriter::new(&mut lines)
+                    .with_json_format(JsonLayout::Lines.polars_format())
+                    .finish(df)?;
+                for line in lines
+                    .split(|byte| *byte == b'\n')
+                    .filter(|line| !line.is_empty())
+                {
+                    bytes.push(if *objects == 0 { b'[' } else { b',' });
+                    bytes.extend_from_slice(line);
+                    *objects += 1;
+                }
+            }
+            BatchEncoder::Parquet { writer, buffer } => {
+                writer.write_batch(df)?;
+                bytes = buffer.take();
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Bytes closing the object.
+    fn finish(self) -> PolarsResult<Vec<u8>> {
+        Ok(match self {
+            BatchEncoder::Csv { .. }
+            | BatchEncoder::Json {
+                layout: JsonLayout::Lines,
+                ..
+            } => Vec::new(),
+            BatchEncoder::Json { objects: 0, .. } => b"[]".to_vec(),
+            BatchEncoder::Json { .. } => b"]".to_vec(),
+            BatchEncoder::Parquet { mut writer, buffer } => {
+                writer.finish()?;
+                buffer.take()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::{hdd::Hdd, AsString};
+    use object_store::memory::InMemory;
+    use polars::prelude::{df, AnyValue, NamedFrom};
+
+    fn hdd_df() -> DataFrame {
+        df![
+            Hdd::IdSource.as_str() => [1, 2],
+            Hdd::Nom.as_str() => ["SALLANDIER", "CARRE"],
+            Hdd::Telephone.as_str() => ["0606060606", "0607070707"],
+        ]
+        .expect("DataFrame creation failed")
+    }
+
+    #[test]
+    fn test_parse_s3_uri() {
+        let test_cases = vec![
+            ("s3://etl/exports/jdd/", Some(("etl", "exports/jdd"))),
+            ("s3://etl", Some(("etl", ""))),
+            ("s3:///exports", None),
+            ("/files/jdd.csv", None),
+        ];
+        for (uri, expected) in test_cases {
+            let parsed = S3Uri::parse(uri).ok();
+            assert_eq!(
+                parsed
+                    .as_ref()
+                    .map(|uri| (uri.bucket.as_str(), uri.prefix.as_str())),
+                expected,
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn test_object_format_from_key() {
+        let test_cases = vec![
+            ("exports/jdd.csv", Some(ObjectFormat::Csv)),
+            ("exports/jdd.csv.gz", Some(ObjectFormat::Csv)),
+            ("exports/jdd.parquet", Some(ObjectFormat::Parquet)),
+            (
+                "exports/jdd.json",
+                Some(ObjectFormat::Json(JsonLayout::Array)),
+            ),
+            (
+                "exports/jdd.jsonl.zst",
+                Some(ObjectFormat::Json(JsonLayout::Lines)),
+            ),
+            ("exports/_SUCCESS", None),
+        ];
+        for (key, expected) in test_cases {
+            assert_eq!(ObjectFormat::from_key(key), expected, "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read_prefix() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        S3Sink::new(store.clone(), "exports/hdd/part-1.csv")
+            .write(&mut hdd_df())
+            .await
+            .expect("CSV upload failed");
+        S3Sink::new(store.clone(), "exports/hdd/part-2.parquet")
+            .write(&mut hdd_df())
+            .await
+            .expect("Parquet upload failed");
+        store
+            .put(&ObjectPath::from("exports/hdd/_SUCCESS"), PutPayload::new())
+            .await
+            .expect("Marker upload failed");
+
+        let source = S3Source::new(store, "exports/hdd/").with_dataset_schema(SchemasEnum::Hdd);
+        let keys = source.list().await.expect("List failed");
+        let df = source.read().await.expect("Read failed");
+
+        assert_eq!(
+            keys,
+            ["exports/hdd/part-1.csv", "exports/hdd/part-2.parquet"]
+        );
+        assert_eq!(df.height(), 4);
+        assert_eq!(
+            df.column(Hdd::IdSource.as_str()).unwrap().get(3).unwrap(),
+            AnyValue::Int32(2)
+        );
+        assert_eq!(
+            df.column(Hdd::Telephone.as_str()).unwrap().get(0).unwrap(),
+            AnyValue::String("0606060606")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_in_batches_and_small_parts() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let keys = [
+            "exports/hdd.csv",
+            "exports/hdd.parquet",
+            "exports/hdd.json",
+            "exports/hdd.jsonl",
+        ];
+        for key in keys {
+            let size = S3Sink::new(store.clone(), key)
+                .with_batch_size(1)
+                .with_part_size(16)
+                .write(&mut hdd_df())
+                .await
+                .expect("Upload failed");
+            let object = store
+                .get(&ObjectPath::from(key))
+                .await
+                .expect("Get failed")
+                .bytes()
+                .await
+                .expect("Download failed");
+            let df = S3Source::new(store.clone(), key)
+                .with_dataset_schema(SchemasEnum::Hdd)
+                .read()
+                .await
+                .expect("Read failed");
+
+            assert_eq!(object.len(), size, "{}", key);
+            assert_eq!(df.height(), 2, "{}", key);
+            assert_eq!(
+                df.column(Hdd::Nom.as_str()).unwrap().get(1).unwrap(),
+                AnyValue::String("CARRE"),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the MinIO service of docker-compose.yml and the AWS_* variables of .env"]
+    async fn test_minio_round_trip() {
+        dotenv::dotenv().ok();
+        let uri = "s3://etl/tests/hdd.parquet";
+
+        S3Sink::from_uri(uri)
+            .expect("Sink creation failed")
+            .write(&mut hdd_df())
+            .await
+            .expect("Upload failed");
+        let df = S3Source::from_uri(uri)
+            .expect("Source creation failed")
+            .read()
+            .await
+            .expect("Read failed");
+
+        assert!(df.equals(&hdd_df()));
+    }
+}
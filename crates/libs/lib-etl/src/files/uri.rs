@@ -0,0 +1,142 @@
+use super::{
+    compression::Compression,
+    json::{JsonSink, JsonSource},
+    parquet::{ParquetSink, ParquetSource},
+    s3::{is_s3_uri, ObjectFormat, S3Sink, S3Source},
+};
+use crate::{csv::csv_to_dataframe, schemas::SchemasEnum};
+use core::error::Error;
+use log::info;
+use polars::prelude::{CsvWriter, DataFrame, SerWriter};
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+/// Reads a CSV, Parquet or JSON dataset from a local path or an `s3://bucket/prefix` URI.
+///
+/// The format follows the extension, compressed files included. A local directory is read as
+/// a partitioned Parquet dataset, an S3 prefix as all the objects under it, see [`S3Source`].
+/// CSV needs the dataset schema.
+pub async fn read_dataframe(
+    location: &str,
+    dataset: Option<SchemasEnum>,
+) -> Result<DataFrame, Box<dyn Error>> {
+    if is_s3_uri(location) {
+        let mut source = S3Source::from_uri(location)?;
+        if let Some(se) = dataset {
+            source = source.with_dataset_schema(se);
+        }
+        return source.read().await;
+    }
+
+    let path = Path::new(location);
+    if path.is_dir() {
+        return Ok(ParquetSource::new(path).read()?);
+    }
+    let df = match (ObjectFormat::from_key(location), dataset) {
+        (Some(ObjectFormat::Csv), Some(se)) => csv_to_dataframe(location, se)?,
+        (Some(ObjectFormat::Csv), None) => {
+            return Err(format!("{} is a CSV, set the dataset schema to read it", location).into())
+        }
+        (Some(ObjectFormat::Parquet), _) => ParquetSource::new(path).read()?,
+        (Some(ObjectFormat::Json(_)), dataset) => {
+            let mut source = JsonSource::new(path);
+            if let Some(se) = dataset {
+                source = source.with_dataset_schema(se);
+            }
+            source.read()?
+        }
+        (None, _) => return Err(format!("Unknown format for {}", location).into()),
+    };
+    info!("Read {} rows from {}", df.height(), location);
+    Ok(df)
+}
+
+/// Writes `df` as a single CSV, Parquet or JSON file, at a local path or an `s3://bucket/key` URI
+/// with a multipart upload. The format follows the extension, CSV is `;` separated.
+pub async fn write_dataframe(location: &str, df: &mut DataFrame) -> Result<(), Box<dyn Error>> {
+    if Compression::from_path(Path::new(location)).is_some() {
+        return Err(format!(
+            "{} is compressed, only uncompressed files are written",
+            location
+        )
+        .into());
+    }
+    if is_s3_uri(location) {
+        S3Sink::from_uri(location)?.write(df).await?;
+        return Ok(());
+    }
+
+    let path = Path::new(location);
+    match ObjectFormat::from_key(location) {
+        Some(ObjectFormat::Csv) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            CsvWriter::new(File::create(path)?)
+                .include_header(true)
+                .with_separator(b';')
+                .finish(df)?;
+        }
+        Some(ObjectFormat::Parquet) => {
+            ParquetSink::new(path).write(df)?;
+        }
+        Some(ObjectFormat::Json(layout)) => JsonSink::new(path).with_layout(layout).write(df)?,
+        None => return Err(format!("Unknown format for {}", location).into()),
+    }
+    info!("Wrote {} rows to {}", df.height(), location);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::{hdd::Hdd, AsString};
+    use polars::prelude::{df, NamedFrom};
+
+    fn hdd_df() -> DataFrame {
+        df![
+            Hdd::IdSource.as_str() => [1, 2],
+            Hdd::Nom.as_str() => ["SALLANDIER", "CARRE"],
+            Hdd::Telephone.as_str() => ["0606060606", "0607070707"],
+        ]
+        .expect("DataFrame creation failed")
+    }
+
+    #[tokio::test]
+    async fn test_local_round_trip() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        for file_name in ["hdd.csv", "hdd.parquet", "hdd.json", "hdd.jsonl"] {
+            let location = dir.path().join(file_name).to_string_lossy().to_string();
+
+            write_dataframe(&location, &mut hdd_df())
+                .await
+                .expect("Write failed");
+            let df = read_dataframe(&location, Some(SchemasEnum::Hdd))
+                .await
+                .expect("Read failed");
+
+            assert_eq!(df.height(), 2, "{}", file_name);
+            assert_eq!(
+                df.column(Hdd::Nom.as_str()).unwrap().str().unwrap().get(1),
+                Some("CARRE"),
+                "{}",
+                file_name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_locations() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let location = dir.path().join("hdd.csv").to_string_lossy().to_string();
+        write_dataframe(&location, &mut hdd_df())
+            .await
+            .expect("Write failed");
+
+        assert!(read_dataframe(&location, None).await.is_err());
+        assert!(write_dataframe("hdd.txt", &mut hdd_df()).await.is_err());
+        assert!(write_dataframe("hdd.csv.gz", &mut hdd_df()).await.is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use self::serializer::SchemaSerializer;
 use crate::config::{CsvConfig, KafkaConfig};
 use chrono::Utc;
 use core::error::Error;
@@ -13,6 +14,9 @@ use rdkafka::{
 };
 use serde_json::Value;
 
+pub mod registry;
+pub mod serializer;
+
 // --Start-Producer--
 fn create_kafka_producer(kafka_config: &KafkaConfig) -> FutureProducer {
     ClientConfig::new()
@@ -34,7 +38,34 @@ pub async fn push_json_to_kafka(
 
         let kafka_headers = create_kafka_headers(csv_config, &index.to_string());
 
-        produce_to_kafka(&producer, kafka_config, &key, &json_string, kafka_headers).await?;
+        produce_to_kafka(
+            &producer,
+            kafka_config,
+            &key,
+            json_string.as_bytes(),
+            kafka_headers,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn push_serialized_json_to_kafka(
+    json_objects: &[Value],
+    kafka_config: &KafkaConfig,
+    csv_config: &CsvConfig,
+    serializer: &SchemaSerializer,
+) -> Result<(), Box<dyn Error>> {
+    let producer = create_kafka_producer(kafka_config);
+
+    for (index, json_obj) in json_objects.iter().enumerate() {
+        let payload = serializer.serialize(json_obj)?;
+        let key = index.to_string();
+
+        let kafka_headers = create_kafka_headers(csv_config, &index.to_string());
+
+        produce_to_kafka(&producer, kafka_config, &key, &payload, kafka_headers).await?;
     }
 
     Ok(())
@@ -44,7 +75,7 @@ async fn produce_to_kafka(
     producer: &FutureProducer,
     kafka_config: &KafkaConfig,
     key: &str,
-    message: &str,
+    message: &[u8],
     headers: OwnedHeaders,
 ) -> Result<(), Box<dyn Error>> {
     let record = FutureRecord::to(&kafka_config.topic)
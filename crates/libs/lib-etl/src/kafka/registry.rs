@@ -0,0 +1,247 @@
+use core::error::Error;
+use reqwest::{header::CONTENT_TYPE, Client, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+const SCHEMA_REGISTRY_CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    #[default]
+    Avro,
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredSchema {
+    pub id: u32,
+    pub schema: String,
+    #[serde(default)]
+    pub schema_type: SchemaType,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaRequest<'a> {
+    schema: &'a str,
+    schema_type: SchemaType,
+}
+
+#[derive(Deserialize)]
+struct RegisterResponse {
+    id: u32,
+}
+
+#[derive(Deserialize)]
+struct CompatibilityResponse {
+    is_compatible: bool,
+}
+
+/// Minimal client for the Confluent Schema Registry REST API.
+#[derive(Debug, Clone)]
+pub struct SchemaRegistryClient {
+    base_url: String,
+    http: Client,
+}
+
+impl SchemaRegistryClient {
+    pub fn new(base_url: &str) -> Self {
+        SchemaRegistryClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: Client::new(),
+        }
+    }
+
+    /// Registers `schema` under `subject` and returns its global id.
+    /// The schema is first checked against the latest version of the subject,
+    /// and registration is refused if the registry reports it as incompatible.
+    pub async fn register(
+        &self,
+        subject: &str,
+        schema: &str,
+        schema_type: SchemaType,
+    ) -> Result<u32, Box<dyn Error>> {
+        if !self.is_compatible(subject, schema, schema_type).await? {
+            return Err(format!(
+                "Schema is not compatible with the latest version of subject {}",
+                subject
+            )
+            .into());
+        }
+
+        let response = self
+            .http
+            .post(format!("{}/subjects/{}/versions", self.base_url, subject))
+            .header(CONTENT_TYPE, SCHEMA_REGISTRY_CONTENT_TYPE)
+            .json(&SchemaRequest {
+                schema,
+                schema_type,
+            })
+            .send()
+            .await?;
+        let registered: RegisterResponse = parse_response(response).await?;
+
+        Ok(registered.id)
+    }
+
+    /// Checks `schema` against the latest version of `subject`.
+    /// A subject that does not exist yet accepts any schema.
+    pub async fn is_compatible(
+        &self,
+        subject: &str,
+        schema: &str,
+        schema_type: SchemaType,
+    ) -> Result<bool, Box<dyn Error>> {
+        let response = self
+            .http
+            .post(format!(
+                "{}/compatibility/subjects/{}/versions/latest",
+                self.base_url, subject
+            ))
+            .header(CONTENT_TYPE, SCHEMA_REGISTRY_CONTENT_TYPE)
+            .json(&SchemaRequest {
+                schema,
+                schema_type,
+            })
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(true);
+        }
+        let compatibility: CompatibilityResponse = parse_response(response).await?;
+
+        Ok(compatibility.is_compatible)
+    }
+
+    pub async fn get_schema_by_id(&self, id: u32) -> Result<RegisteredSchema, Box<dyn Error>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SchemaByIdResponse {
+            schema: String,
+            #[serde(default)]
+            schema_type: SchemaType,
+        }
+
+        let response = self
+            .http
+            .get(format!("{}/schemas/ids/{}", self.base_url, id))
+            .send()
+            .await?;
+        let schema: SchemaByIdResponse = parse_response(response).await?;
+
+        Ok(RegisteredSchema {
+            id,
+            schema: schema.schema,
+            schema_type: schema.schema_type,
+        })
+    }
+
+    pub async fn get_latest_schema(
+        &self,
+        subject: &str,
+    ) -> Result<RegisteredSchema, Box<dyn Error>> {
+        let response = self
+            .http
+            .get(format!(
+                "{}/subjects/{}/versions/latest",
+                self.base_url, subject
+            ))
+            .send()
+            .await?;
+
+        parse_response(response).await
+    }
+}
+
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, Box<dyn Error>> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Schema registry returned {}: {}", status, body).into());
+    }
+    Ok(response.json::<T>().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    #[tokio::test]
+    async fn test_register_checks_compatibility_first() {
+        let mut server = Server::new_async().await;
+        let compatibility_mock = server
+            .mock("POST", "/compatibility/subjects/jdd-value/versions/latest")
+            .with_status(404)
+            .with_body(r#"{"error_code":40401,"message":"Subject not found"}"#)
+            .create_async()
+            .await;
+        let register_mock = server
+            .mock("POST", "/subjects/jdd-value/versions")
+            .match_header("content-type", SCHEMA_REGISTRY_CONTENT_TYPE)
+            .match_body(Matcher::PartialJsonString(
+                r#"{"schemaType":"AVRO"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"id":7}"#)
+            .create_async()
+            .await;
+
+        let client = SchemaRegistryClient::new(&server.url());
+        let id = client
+            .register("jdd-value", r#"{"type":"string"}"#, SchemaType::Avro)
+            .await
+            .expect("Registration failed");
+
+        assert_eq!(id, 7);
+        compatibility_mock.assert_async().await;
+        register_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_register_rejects_incompatible_schema() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/compatibility/subjects/jdd-value/versions/latest")
+            .with_status(200)
+            .with_body(r#"{"is_compatible":false}"#)
+            .create_async()
+            .await;
+        let register_mock = server
+            .mock("POST", "/subjects/jdd-value/versions")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = SchemaRegistryClient::new(&server.url());
+        let result = client
+            .register("jdd-value", r#"{"type":"object"}"#, SchemaType::Json)
+            .await;
+
+        assert!(result.is_err(), "Incompatible schema should be refused");
+        register_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_schema_by_id() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/schemas/ids/3")
+            .with_status(200)
+            .with_body(r#"{"schema":"{\"type\":\"object\"}","schemaType":"JSON"}"#)
+            .create_async()
+            .await;
+
+        let client = SchemaRegistryClient::new(&server.url());
+        let schema = client
+            .get_schema_by_id(3)
+            .await
+            .expect("Schema lookup failed");
+
+        assert_eq!(schema.id, 3);
+        assert_eq!(schema.schema_type, SchemaType::Json);
+        assert_eq!(schema.schema, r#"{"type":"object"}"#);
+    }
+}
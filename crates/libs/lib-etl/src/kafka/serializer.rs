@@ -0,0 +1,384 @@
+use super::registry::{SchemaRegistryClient, SchemaType};
+use crate::{
+    config::PayloadFormat,
+    schemas::{FieldDef, FieldType, SchemasEnum},
+};
+use apache_avro::{from_avro_datum, to_avro_datum, types::Value as AvroValue, Schema};
+use core::error::Error;
+use serde_json::{json, Map, Number, Value};
+use std::collections::HashMap;
+
+/// First byte of every message framed with the Confluent wire format,
+/// followed by the 4 bytes big-endian schema id and the encoded payload.
+pub const MAGIC_BYTE: u8 = 0;
+const HEADER_LEN: usize = 5;
+
+/// Avro names only allow `[A-Za-z0-9_]`, so columns such as `CODE POSTALE` are renamed.
+pub fn avro_field_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub fn avro_schema_for(se: SchemasEnum) -> Value {
+    let fields = se
+        .fields()
+        .iter()
+        .map(|field| {
+            let avro_type = match field.field_type {
+                FieldType::String => "string",
+                FieldType::Int32 => "int",
+                FieldType::Float64 => "double",
+            };
+            if field.nullable {
+                json!({
+                    "name": avro_field_name(field.name),
+                    "type": ["null", avro_type],
+                    "default": null,
+                })
+            } else {
+                json!({ "name": avro_field_name(field.name), "type": avro_type })
+            }
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "type": "record",
+        "name": se.name(),
+        "namespace": "lib_etl",
+        "fields": fields,
+    })
+}
+
+pub fn json_schema_for(se: SchemasEnum) -> Value {
+    let fields = se.fields();
+    let properties = fields
+        .iter()
+        .map(|field| {
+            let json_type = match field.field_type {
+                FieldType::String => "string",
+                FieldType::Int32 => "integer",
+                FieldType::Float64 => "number",
+            };
+            let property = if field.nullable {
+                json!({ "type": [json_type, "null"] })
+            } else {
+                json!({ "type": json_type })
+            };
+            (field.name.to_string(), property)
+        })
+        .collect::<Map<String, Value>>();
+    let required = fields
+        .iter()
+        .filter(|field| !field.nullable)
+        .map(|field| field.name)
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": se.name(),
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// Converts a JSON record (e.g. a CSV row from `csv_to_json`, where every value is a string)
+/// into the types declared by the dataset fields.
+pub fn coerce_record(
+    record: &Value,
+    fields: &[FieldDef],
+) -> Result<Map<String, Value>, Box<dyn Error>> {
+    let object = record
+        .as_object()
+        .ok_or("Kafka record must be a JSON object")?;
+    let mut coerced = Map::new();
+
+    for field in fields {
+        let value = match object.get(field.name) {
+            None | Some(Value::Null) => Value::Null,
+            Some(Value::String(s)) if s.trim().is_empty() => Value::Null,
+            Some(value) => coerce_value(value, field)?,
+        };
+        if value.is_null() && !field.nullable {
+            return Err(format!("Field {} is not nullable", field.name).into());
+        }
+        coerced.insert(field.name.to_string(), value);
+    }
+
+    Ok(coerced)
+}
+
+fn coerce_value(value: &Value, field: &FieldDef) -> Result<Value, Box<dyn Error>> {
+    let invalid = || format!("Invalid value {} for field {}", value, field.name);
+    let coerced = match (field.field_type, value) {
+        (FieldType::String, Value::String(_)) => value.clone(),
+        (FieldType::String, other) => Value::String(other.to_string()),
+        (FieldType::Int32, Value::Number(n)) => {
+            let n = n.as_i64().ok_or_else(invalid)?;
+            json!(i32::try_from(n).map_err(|_| invalid())?)
+        }
+        (FieldType::Int32, Value::String(s)) => {
+            json!(s.trim().parse::<i32>().map_err(|_| invalid())?)
+        }
+        (FieldType::Float64, Value::Number(_)) => value.clone(),
+        (FieldType::Float64, Value::String(s)) => {
+            let n = s.trim().parse::<f64>().map_err(|_| invalid())?;
+            Value::Number(Number::from_f64(n).ok_or_else(invalid)?)
+        }
+        _ => return Err(invalid().into()),
+    };
+    Ok(coerced)
+}
+
+fn to_avro_value(record: &Map<String, Value>, fields: &[FieldDef]) -> AvroValue {
+    let avro_fields = fields
+        .iter()
+        .map(|field| {
+            let value = match record.get(field.name).unwrap_or(&Value::Null) {
+                Value::Null => AvroValue::Null,
+                Value::String(s) => AvroValue::String(s.clone()),
+                Value::Number(n) => match field.field_type {
+                    FieldType::Int32 => AvroValue::Int(n.as_i64().unwrap_or_default() as i32),
+                    _ => AvroValue::Double(n.as_f64().unwrap_or_default()),
+                },
+                other => AvroValue::String(other.to_string()),
+            };
+            let value = match (field.nullable, value) {
+                (true, AvroValue::Null) => AvroValue::Union(0, Box::new(AvroValue::Null)),
+                (true, value) => AvroValue::Union(1, Box::new(value)),
+                (false, value) => value,
+            };
+            (avro_field_name(field.name), value)
+        })
+        .collect();
+    AvroValue::Record(avro_fields)
+}
+
+fn from_avro_value(value: AvroValue, names: &HashMap<String, &'static str>) -> Value {
+    match value {
+        AvroValue::Record(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let name = names.get(&name).map(|n| n.to_string()).unwrap_or(name);
+                    (name, from_avro_value(value, names))
+                })
+                .collect(),
+        ),
+        AvroValue::Union(_, value) => from_avro_value(*value, names),
+        AvroValue::Null => Value::Null,
+        AvroValue::Boolean(b) => Value::Bool(b),
+        AvroValue::Int(n) => json!(n),
+        AvroValue::Long(n) => json!(n),
+        AvroValue::Float(n) => json!(n),
+        AvroValue::Double(n) => json!(n),
+        AvroValue::String(s) | AvroValue::Enum(_, s) => Value::String(s),
+        other => Value::String(format!("{:?}", other)),
+    }
+}
+
+fn frame(schema_id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.push(MAGIC_BYTE);
+    bytes.extend_from_slice(&schema_id.to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+fn unframe(bytes: &[u8]) -> Result<(u32, &[u8]), Box<dyn Error>> {
+    if bytes.len() < HEADER_LEN || bytes[0] != MAGIC_BYTE {
+        return Err("Payload is not framed with the schema registry wire format".into());
+    }
+    let schema_id = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+    Ok((schema_id, &bytes[HEADER_LEN..]))
+}
+
+enum Encoder {
+    Json,
+    Avro(Box<Schema>),
+    JsonSchema,
+}
+
+/// Serializes dataset records into Kafka payloads.
+///
+/// `PayloadFormat::Json` keeps the historical untyped JSON string, the two other formats
+/// register the dataset schema in the registry and prefix the payload with its id.
+pub struct SchemaSerializer {
+    schema_id: Option<u32>,
+    fields: Vec<FieldDef>,
+    encoder: Encoder,
+}
+
+impl SchemaSerializer {
+    pub fn json(se: SchemasEnum) -> Self {
+        SchemaSerializer {
+            schema_id: None,
+            fields: se.fields(),
+            encoder: Encoder::Json,
+        }
+    }
+
+    pub async fn register(
+        client: &SchemaRegistryClient,
+        subject: &str,
+        se: SchemasEnum,
+        format: PayloadFormat,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (schema, schema_type, encoder) = match format {
+            PayloadFormat::Json => return Ok(Self::json(se)),
+            PayloadFormat::Avro => {
+                let schema = avro_schema_for(se).to_string();
+                let parsed = Schema::parse_str(&schema)?;
+                (schema, SchemaType::Avro, Encoder::Avro(Box::new(parsed)))
+            }
+            PayloadFormat::JsonSchema => (
+                json_schema_for(se).to_string(),
+                SchemaType::Json,
+                Encoder::JsonSchema,
+            ),
+        };
+        let schema_id = client.register(subject, &schema, schema_type).await?;
+
+        Ok(SchemaSerializer {
+            schema_id: Some(schema_id),
+            fields: se.fields(),
+            encoder,
+        })
+    }
+
+    pub fn schema_id(&self) -> Option<u32> {
+        self.schema_id
+    }
+
+    pub fn serialize(&self, record: &Value) -> Result<Vec<u8>, Box<dyn Error>> {
+        match (&self.encoder, self.schema_id) {
+            (Encoder::Json, _) => Ok(serde_json::to_vec(record)?),
+            (Encoder::Avro(schema), Some(schema_id)) => {
+                let coerced = coerce_record(record, &self.fields)?;
+                let datum = to_avro_datum(schema, to_avro_value(&coerced, &self.fields))?;
+                Ok(frame(schema_id, &datum))
+            }
+            (Encoder::JsonSchema, Some(schema_id)) => {
+                let coerced = coerce_record(record, &self.fields)?;
+                Ok(frame(schema_id, &serde_json::to_vec(&coerced)?))
+            }
+            _ => Err("Schema serializer has no registered schema id".into()),
+        }
+    }
+}
+
+/// Decodes Kafka payloads produced by `SchemaSerializer` back into JSON records.
+/// Writer schemas are fetched from the registry by id and cached.
+pub struct SchemaDeserializer {
+    client: SchemaRegistryClient,
+    avro_names: HashMap<String, &'static str>,
+    schemas: HashMap<u32, Option<Schema>>,
+}
+
+impl SchemaDeserializer {
+    pub fn new(client: SchemaRegistryClient, se: SchemasEnum) -> Self {
+        let avro_names = se
+            .fields()
+            .into_iter()
+            .map(|field| (avro_field_name(field.name), field.name))
+            .collect();
+        SchemaDeserializer {
+            client,
+            avro_names,
+            schemas: HashMap::new(),
+        }
+    }
+
+    pub async fn deserialize(&mut self, bytes: &[u8]) -> Result<Value, Box<dyn Error>> {
+        let (schema_id, payload) = unframe(bytes)?;
+        if !self.schemas.contains_key(&schema_id) {
+            let registered = self.client.get_schema_by_id(schema_id).await?;
+            let schema = match registered.schema_type {
+                SchemaType::Avro => Some(Schema::parse_str(&registered.schema)?),
+                SchemaType::Json => None,
+            };
+            self.schemas.insert(schema_id, schema);
+        }
+
+        match &self.schemas[&schema_id] {
+            Some(schema) => {
+                let value = from_avro_datum(schema, &mut &payload[..], None)?;
+                Ok(from_avro_value(value, &self.avro_names))
+            }
+            None => Ok(serde_json::from_slice(payload)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::{hdd::Hdd, jdd::Jdd, AsString};
+
+    #[test]
+    fn test_avro_schema_for_jdd_is_valid() {
+        let schema = avro_schema_for(SchemasEnum::Jdd);
+        Schema::parse_str(&schema.to_string()).expect("Generated Avro schema is invalid");
+
+        let field_names = schema["fields"]
+            .as_array()
+            .expect("Fields should be an array")
+            .iter()
+            .map(|field| field["name"].as_str().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        assert!(field_names.contains(&"CODE_POSTALE".to_string()));
+    }
+
+    #[test]
+    fn test_coerce_record() {
+        let fields = SchemasEnum::Hdd.fields();
+        let record = json!({
+            Hdd::IdSource.as_str(): "2",
+            Hdd::Pce.as_str(): "01234567891012",
+            Hdd::Nom.as_str(): "SALLANDIER",
+            Hdd::Email.as_str(): "",
+        });
+
+        let coerced = coerce_record(&record, &fields).expect("Coercion failed");
+
+        assert_eq!(coerced[Hdd::IdSource.as_str()], json!(2));
+        assert_eq!(coerced[Hdd::Pce.as_str()], json!("01234567891012"));
+        assert_eq!(coerced[Hdd::Nom.as_str()], json!("SALLANDIER"));
+        assert_eq!(coerced[Hdd::Email.as_str()], Value::Null);
+        assert_eq!(coerced[Hdd::Siret.as_str()], Value::Null);
+
+        let invalid = json!({ Hdd::IdSource.as_str(): "two" });
+        assert!(coerce_record(&invalid, &fields).is_err());
+    }
+
+    #[test]
+    fn test_avro_round_trip() {
+        let se = SchemasEnum::Jdd;
+        let schema = Schema::parse_str(&avro_schema_for(se).to_string()).expect("Invalid schema");
+        let serializer = SchemaSerializer {
+            schema_id: Some(42),
+            fields: se.fields(),
+            encoder: Encoder::Avro(Box::new(schema.clone())),
+        };
+        let record = json!({
+            Jdd::RaisonSociale.as_str(): "Société dupont",
+            Jdd::CodePostale.as_str(): "75001",
+        });
+
+        let bytes = serializer.serialize(&record).expect("Serialization failed");
+        let (schema_id, payload) = unframe(&bytes).expect("Invalid framing");
+        assert_eq!(schema_id, 42);
+
+        let deserializer = SchemaDeserializer::new(SchemaRegistryClient::new("http://unused"), se);
+        let value = from_avro_datum(&schema, &mut &payload[..], None).expect("Decoding failed");
+        let decoded = from_avro_value(value, &deserializer.avro_names);
+
+        assert_eq!(
+            decoded[Jdd::RaisonSociale.as_str()],
+            json!("Société dupont")
+        );
+        assert_eq!(decoded[Jdd::CodePostale.as_str()], json!("75001"));
+        assert_eq!(decoded[Jdd::Siret.as_str()], Value::Null);
+    }
+}
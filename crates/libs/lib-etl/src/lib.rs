@@ -1,5 +1,13 @@
+pub mod bus;
 pub mod config;
 pub mod csv;
+pub mod dedup;
+pub mod files;
 pub mod kafka;
+pub mod pipelines;
+pub mod postgres;
+pub mod resolution;
+pub mod run;
 pub mod schemas;
 pub mod transforms;
+pub mod watch;
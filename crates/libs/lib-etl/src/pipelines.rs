@@ -0,0 +1,61 @@
+//! Pipelines runnable from the examples and from the web-server.
+pub mod hdd_deduplication;
+pub mod hdd_incremental_deduplication;
+pub mod jdd_hdd_resolution;
+pub mod jdd_normalisation;
+
+use crate::run::{RunRecorder, RunStats};
+use core::error::Error;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pipeline {
+    JddNormalisation,
+    HddDeduplication,
+    HddIncrementalDeduplication,
+    JddHddResolution,
+}
+
+impl Pipeline {
+    /// Name recorded as `pipeline_name` of the run.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pipeline::JddNormalisation => "jdd_normalisation",
+            Pipeline::HddDeduplication => "hdd_deduplication",
+            Pipeline::HddIncrementalDeduplication => "hdd_incremental_deduplication",
+            Pipeline::JddHddResolution => "jdd_hdd_resolution",
+        }
+    }
+
+    pub async fn run(
+        &self,
+        pool: &PgPool,
+        recorder: &RunRecorder,
+    ) -> Result<RunStats, Box<dyn Error>> {
+        match self {
+            Pipeline::JddNormalisation => jdd_normalisation::run(pool, recorder).await,
+            Pipeline::HddDeduplication => hdd_deduplication::run(pool, recorder).await,
+            Pipeline::HddIncrementalDeduplication => {
+                hdd_incremental_deduplication::run(pool, recorder).await
+            }
+            Pipeline::JddHddResolution => jdd_hdd_resolution::run(pool, recorder).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_serde() {
+        let pipeline: Pipeline = serde_json::from_str("\"hdd_deduplication\"").unwrap();
+        assert_eq!(pipeline, Pipeline::HddDeduplication);
+        assert_eq!(
+            serde_json::to_string(&Pipeline::JddNormalisation).unwrap(),
+            format!("\"{}\"", Pipeline::JddNormalisation.name())
+        );
+    }
+}
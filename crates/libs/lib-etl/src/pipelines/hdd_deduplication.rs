@@ -0,0 +1,288 @@
+use crate::config::{Config, Transform, WriteMode, FILES_PATH, IO_CONFIG_PATH};
+use crate::dedup::blocking::{blocking_index, BlockingKey};
+use crate::dedup::deduplicator::{Deduplicator, DECISION, MATCH};
+use crate::dedup::export::{clusters_to_dot, clusters_to_json};
+use crate::dedup::rules::{Comparator, Condition, FieldMatch, MatchRule, NullPolicy};
+use crate::dedup::survivorship::Survivorship;
+use crate::files::parquet::ParquetSink;
+use crate::postgres::{
+    rename_to_dataset_columns, rename_to_sql_columns, sink::PgSink, source::PgSource,
+};
+use crate::run::{RunRecorder, RunStats};
+use crate::schemas::hdd::Hdd;
+use crate::schemas::{AsString, SchemasEnum};
+use crate::transforms::col_with_udf_expr;
+use crate::transforms::email::col_email_with_polars_expr;
+use crate::transforms::nom::col_nom_with_polars_expr;
+use crate::transforms::pce::col_pce_with_polars_expr;
+use crate::transforms::phonetic::{col_nom_phonetic_with_polars_expr, phonetic_column};
+use crate::transforms::prenom::col_prenom_with_polars_expr;
+use crate::transforms::raison_sociale::{
+    col_forme_juridique_with_polars_expr, col_raison_sociale_key_with_polars_expr,
+    col_raison_sociale_with_polars_expr,
+};
+use crate::transforms::siret::col_siret_with_polars_expr;
+use crate::transforms::siret_successeur::{
+    col_siret_actif_with_polars_expr, col_siret_ss_with_polars_expr, SuccessorChains,
+};
+use core::error::Error;
+use log::info;
+use polars::prelude::*;
+use sea_query::Iden;
+use sqlx::PgPool;
+use std::path::Path;
+
+/// Records sharing the first letters of their Nom, so the names with a typo are still compared.
+const NOM_PREFIX_LENGTH: usize = 3;
+/// Larger blocks are too common a Nom prefix to be compared pair by pair.
+const MAX_BLOCK_SIZE: usize = 5000;
+/// Reference file of establishment transfers, used with the `SIRET successeur` of the records.
+const SUCCESSIONS_FILE: &str = "liens_succession.csv";
+
+/// The normalised records and their cluster, kept by dataset column names for the
+/// incremental runs.
+pub(crate) fn members_table(golden_table: &str) -> String {
+    format!("{}_members", golden_table)
+}
+
+/// The blocking index of the members, for the incremental runs.
+pub(crate) fn blocking_index_table(golden_table: &str) -> String {
+    format!("{}_blocking_index", golden_table)
+}
+
+/// Same person when no SIRET, similar Nom, similar or missing Prenom, and same PCE, Email,
+/// Telephone or company name.
+fn hdd_match_rule() -> MatchRule {
+    MatchRule::new("same_person")
+        .with_condition(Condition::IsNull(Hdd::Siret.as_str().to_string()))
+        .with_required(FieldMatch::new(Hdd::Nom.as_str(), Comparator::JaroWinkler))
+        .with_required(
+            FieldMatch::new(Hdd::Prenom.as_str(), Comparator::JaroWinkler)
+                .with_nulls(NullPolicy::Match),
+        )
+        .with_optional(FieldMatch::exact(Hdd::Pce.as_str()))
+        .with_optional(FieldMatch::exact(Hdd::Email.as_str()))
+        .with_optional(FieldMatch::exact(Hdd::Telephone.as_str()))
+        .with_optional(FieldMatch::exact(Hdd::RaisonSocialeCle.as_str()))
+}
+
+/// Same person in the same establishment, once transferred: same active SIRET, similar Nom,
+/// similar or missing Prenom.
+fn hdd_establishment_match_rule() -> MatchRule {
+    MatchRule::new("same_establishment_contact")
+        .with_required(FieldMatch::exact(Hdd::SiretActif.as_str()))
+        .with_required(FieldMatch::new(Hdd::Nom.as_str(), Comparator::JaroWinkler))
+        .with_required(
+            FieldMatch::new(Hdd::Prenom.as_str(), Comparator::JaroWinkler)
+                .with_nulls(NullPolicy::Match),
+        )
+}
+
+/// Deduplicator of the HDD contacts: the rule, the blocking keys and the survivorship.
+pub(crate) fn hdd_deduplicator() -> Deduplicator {
+    // The phonetic key brings the spelling variants of a Nom into the same block
+    let nom_phonetic = phonetic_column(Hdd::Nom.as_str());
+    Deduplicator::new(Hdd::Id.as_str())
+        .with_rule(hdd_match_rule())
+        .with_rule(hdd_establishment_match_rule())
+        .with_blocking_key(
+            BlockingKey::new("nom_prefix").with_prefix(Hdd::Nom.as_str(), NOM_PREFIX_LENGTH),
+        )
+        .with_blocking_key(BlockingKey::new("nom_phonetic").with_column(&nom_phonetic))
+        .with_max_block_size(MAX_BLOCK_SIZE)
+        .with_ids_column(Hdd::Ids.as_str())
+        .with_provenance_column(Hdd::Provenance.as_str())
+        // The usual spelling and contact details of the person, the fullest company name.
+        // The PCE stay joined, a person may have several delivery points
+        .with_survivorship(Hdd::Nom.as_str(), Survivorship::MostFrequent)
+        .with_survivorship(Hdd::Prenom.as_str(), Survivorship::MostFrequent)
+        .with_survivorship(Hdd::Email.as_str(), Survivorship::MostFrequent)
+        .with_survivorship(Hdd::Telephone.as_str(), Survivorship::MostFrequent)
+        .with_survivorship(Hdd::RaisonSociale.as_str(), Survivorship::Longest)
+}
+
+fn exprs_record_columns() -> Vec<Expr> {
+    vec![
+        col(Hdd::Id.as_str()),
+        col(Hdd::Nom.as_str()),
+        col(Hdd::Prenom.as_str()),
+        col(Hdd::Pce.as_str()),
+        col(Hdd::Email.as_str()),
+        col(Hdd::Telephone.as_str()),
+        col(Hdd::Siret.as_str()),
+        col(Hdd::SiretSuccesseur.as_str()),
+        col(Hdd::SiretActif.as_str()),
+        col(Hdd::RaisonSociale.as_str()),
+        col(Hdd::IdSource.as_str()),
+    ]
+}
+
+/// Columns of the golden records: the record columns, the legal form and the key of the
+/// surviving raison sociale, the merged ids and the provenance.
+pub(crate) fn exprs_golden_columns() -> Vec<Expr> {
+    exprs_record_columns()
+        .into_iter()
+        .chain([
+            col_forme_juridique_with_polars_expr(SchemasEnum::Hdd),
+            col_raison_sociale_key_with_polars_expr(SchemasEnum::Hdd),
+            col(Hdd::Ids.as_str()),
+            col(Hdd::Provenance.as_str()),
+        ])
+        .collect()
+}
+
+/// Chains of the establishment transfers of the reference file, when there is one.
+fn reference_successor_chains() -> PolarsResult<SuccessorChains> {
+    let file_path = String::from(FILES_PATH) + SUCCESSIONS_FILE;
+    if !Path::new(&file_path).exists() {
+        return Ok(SuccessorChains::new());
+    }
+    info!("Establishment transfers read from {}", file_path);
+    SuccessorChains::new().with_reference_file(&file_path)
+}
+
+/// Normalised records of the raw `hdd` table, named as in the dataset, with their active SIRET,
+/// the key of their raison sociale for the matching, and the phonetic key of their Nom
+/// for the blocking.
+pub(crate) fn hdd_records(df_original: DataFrame) -> PolarsResult<LazyFrame> {
+    // Merged records join the ids and sources as text
+    let lf_original = df_original.lazy().with_columns(vec![
+        col(Hdd::IdSource.as_str()).cast(DataType::String),
+        col(Hdd::Id.as_str()).cast(DataType::String),
+    ]);
+
+    let lf_normalised = lf_original.with_columns(vec![
+        col_pce_with_polars_expr(SchemasEnum::Hdd),
+        col_nom_with_polars_expr(SchemasEnum::Hdd),
+        col_prenom_with_polars_expr(SchemasEnum::Hdd),
+        col_email_with_polars_expr(SchemasEnum::Hdd),
+        col_with_udf_expr(Hdd::Telephone, Transform::Telephone),
+        col_raison_sociale_with_polars_expr(SchemasEnum::Hdd),
+        col_siret_with_polars_expr(SchemasEnum::Hdd),
+        col_siret_ss_with_polars_expr(SchemasEnum::Hdd),
+        col(Hdd::IdSource.as_str()),
+        col(Hdd::Id.as_str()),
+    ]);
+    // The successors of the whole dataset make the chains, after the reference transfers
+    let df_sirets = lf_normalised
+        .clone()
+        .select([col(Hdd::Siret.as_str()), col(Hdd::SiretSuccesseur.as_str())])
+        .collect()?;
+    let siret_actif = col_siret_actif_with_polars_expr(
+        &reference_successor_chains()?,
+        df_sirets.column(Hdd::Siret.as_str())?,
+        df_sirets.column(Hdd::SiretSuccesseur.as_str())?,
+    )?;
+
+    Ok(lf_normalised
+        .with_column(siret_actif)
+        .select(exprs_record_columns())
+        .with_columns([
+            col_raison_sociale_key_with_polars_expr(SchemasEnum::Hdd),
+            col_nom_phonetic_with_polars_expr(SchemasEnum::Hdd),
+        ]))
+}
+
+/// Deduplicates the raw `hdd` table into the configured Postgres table, plus CSV and Parquet copies.
+///
+/// The members and the blocking index are rewritten in the same transaction as the golden
+/// records, so the next incremental run starts from the clusters of this run.
+pub async fn run(pool: &PgPool, recorder: &RunRecorder) -> Result<RunStats, Box<dyn Error>> {
+    let mut df_original = PgSource::from_table(&Hdd::Table.to_string())
+        .read(pool)
+        .await?;
+    let mut stats = RunStats {
+        rows_read: df_original.height() as u64,
+        ..Default::default()
+    };
+    recorder.progress(stats).await?;
+    rename_to_dataset_columns(&mut df_original, SchemasEnum::Hdd)?;
+
+    let deduplicator = hdd_deduplicator();
+    let lf_records = hdd_records(df_original)?;
+
+    // The evidence explains the merges, its matching pairs make the clusters
+    let mut df_evidence = deduplicator.evidence(lf_records.clone())?.collect()?;
+    recorder.checkpoint()?;
+    let df_pairs = df_evidence
+        .clone()
+        .lazy()
+        .filter(col(DECISION).eq(lit(MATCH)))
+        .collect()?;
+    let df_clusters = deduplicator.clusters_of_pairs(lf_records.clone(), &df_pairs)?;
+    let df_members = lf_records
+        .clone()
+        .join(
+            df_clusters.clone().lazy(),
+            [col(Hdd::Id.as_str())],
+            [col(Hdd::Id.as_str())],
+            JoinArgs::new(JoinType::Left),
+        )
+        .collect()?;
+    let df_index = blocking_index(
+        lf_records.clone(),
+        Hdd::Id.as_str(),
+        deduplicator.blocking_keys(),
+    )?
+    .collect()?;
+
+    let mut evidence_file =
+        std::fs::File::create(String::from(FILES_PATH) + "HDD_deduplication_evidence.csv")?;
+    CsvWriter::new(&mut evidence_file).finish(&mut df_evidence)?;
+    std::fs::write(
+        String::from(FILES_PATH) + "HDD_deduplication_clusters.json",
+        clusters_to_json(&df_clusters, &df_pairs, Hdd::Id.as_str())?,
+    )?;
+    std::fs::write(
+        String::from(FILES_PATH) + "HDD_deduplication_clusters.dot",
+        clusters_to_dot(&df_clusters, &df_pairs, Hdd::Id.as_str())?,
+    )?;
+
+    let lf_deduplicated = deduplicator
+        .merge_clusters(lf_records, df_clusters)?
+        .select(exprs_golden_columns());
+
+    let mut df_final = lf_deduplicated.collect()?;
+    recorder.checkpoint()?;
+    info!(
+        "Deduplication: {} records merged into {}, {} pairs compared",
+        stats.rows_read,
+        df_final.height(),
+        df_evidence.height()
+    );
+
+    let mut csv_file =
+        std::fs::File::create(String::from(FILES_PATH) + "HDD_deduplication_transformed_test.csv")?;
+    CsvWriter::new(&mut csv_file).finish(&mut df_final)?;
+
+    let parquet_dir = String::from(FILES_PATH) + "HDD_deduplication_transformed";
+    let files = ParquetSink::new(&parquet_dir)
+        .with_partition_by(&[Hdd::IdSource.as_str()])
+        .write(&mut df_final)?;
+    info!(
+        "{} Parquet partitions written under {}",
+        files.len(),
+        parquet_dir
+    );
+
+    recorder.checkpoint()?;
+
+    let config = Config::load(IO_CONFIG_PATH)?;
+    let golden_table = &config.postgres.hdd.table_name;
+    rename_to_sql_columns(&mut df_final, SchemasEnum::Hdd)?;
+    let mut tx = pool.begin().await?;
+    stats.rows_written = PgSink::from_config(&config.postgres.hdd)
+        .write_in(&mut tx, &df_final)
+        .await?;
+    PgSink::new(&members_table(golden_table))
+        .with_mode(WriteMode::FullRefresh)
+        .write_in(&mut tx, &df_members)
+        .await?;
+    PgSink::new(&blocking_index_table(golden_table))
+        .with_mode(WriteMode::FullRefresh)
+        .write_in(&mut tx, &df_index)
+        .await?;
+    tx.commit().await?;
+
+    Ok(stats)
+}
@@ -0,0 +1,251 @@
+use super::hdd_deduplication::{
+    blocking_index_table, exprs_golden_columns, hdd_deduplicator, hdd_records, members_table,
+};
+use crate::config::{Config, WriteMode, IO_CONFIG_PATH};
+use crate::dedup::blocking::{blocking_index, BLOCK_KEY, BLOCK_VALUE};
+use crate::dedup::cluster::CLUSTER_ID;
+use crate::postgres::{
+    rename_to_dataset_columns, rename_to_sql_columns, sink::PgSink, source::PgSource, table_exists,
+};
+use crate::run::{RunRecorder, RunStats};
+use crate::schemas::hdd::Hdd;
+use crate::schemas::{AsString, SchemasEnum};
+use core::error::Error;
+use log::info;
+use polars::prelude::*;
+use sea_query::Iden;
+use sqlx::PgPool;
+use std::collections::HashSet;
+
+/// Column of the cluster events holding the run they were recorded by.
+const RUN_ID: &str = "run_id";
+/// Text of a whole record, to find the records changed since the previous run.
+const RECORD: &str = "_record";
+
+fn cluster_events_table(golden_table: &str) -> String {
+    format!("{}_cluster_events", golden_table)
+}
+
+fn text_column(df: &DataFrame, column: &str) -> PolarsResult<Vec<String>> {
+    Ok(df
+        .column(column)?
+        .cast(&DataType::String)?
+        .str()?
+        .into_no_null_iter()
+        .map(String::from)
+        .collect())
+}
+
+/// The values of `columns` joined with a separator no value holds, nulls included.
+fn record_expr(columns: &[PlSmallStr]) -> Expr {
+    concat_str(
+        columns
+            .iter()
+            .map(|column| {
+                col(column.as_str())
+                    .cast(DataType::String)
+                    .fill_null(lit("\u{0}"))
+            })
+            .collect::<Vec<_>>(),
+        "\u{1f}",
+        false,
+    )
+    .alias(RECORD)
+}
+
+/// Matches the new or changed records of the raw `hdd` table against the golden records of the
+/// previous runs, instead of deduplicating the whole table again.
+///
+/// The normalised records and their cluster, and their blocking index, are kept next to the
+/// golden records. Only the touched clusters are clustered again, their golden records upserted,
+/// and the clusters merged or split deleted. The cluster changes are appended to the events,
+/// tagged with the run id. The first run, without previous state, deduplicates the whole table.
+/// All the tables are written in a single transaction.
+///
+/// Records deleted from the raw table leave the members and the index. The other records of
+/// their cluster are clustered again as changed records, and a cluster left without records
+/// has its golden record retired.
+pub async fn run(pool: &PgPool, recorder: &RunRecorder) -> Result<RunStats, Box<dyn Error>> {
+    let mut df_original = PgSource::from_table(&Hdd::Table.to_string())
+        .read(pool)
+        .await?;
+    let mut stats = RunStats {
+        rows_read: df_original.height() as u64,
+        ..Default::default()
+    };
+    recorder.progress(stats).await?;
+    rename_to_dataset_columns(&mut df_original, SchemasEnum::Hdd)?;
+
+    let config = Config::load(IO_CONFIG_PATH)?;
+    let golden_table = config.postgres.hdd.table_name.clone();
+    let members_table = members_table(&golden_table);
+    let index_table = blocking_index_table(&golden_table);
+
+    let deduplicator = hdd_deduplicator();
+    let mut lf_records = hdd_records(df_original)?;
+    let record_columns = lf_records
+        .collect_schema()?
+        .iter_names()
+        .cloned()
+        .collect::<Vec<_>>();
+    let has_previous_run = table_exists(pool, &members_table).await?;
+    let (lf_members, lf_index) = if has_previous_run {
+        let df_members = PgSource::from_table(&members_table).read(pool).await?;
+        let df_index = PgSource::from_table(&index_table).read(pool).await?;
+        (df_members.lazy(), df_index.lazy())
+    } else {
+        info!(
+            "No previous clusters in {}, deduplicating every record",
+            members_table
+        );
+        let lf_no_records = lf_records.clone().limit(0);
+        (
+            lf_no_records
+                .clone()
+                .with_column(lit(NULL).cast(DataType::String).alias(CLUSTER_ID)),
+            blocking_index(
+                lf_no_records,
+                Hdd::Id.as_str(),
+                deduplicator.blocking_keys(),
+            )?,
+        )
+    };
+
+    // Records deleted from the raw table since the previous run, with their former cluster
+    let df_deleted = lf_members
+        .clone()
+        .join(
+            lf_records.clone().select([col(Hdd::Id.as_str())]),
+            [col(Hdd::Id.as_str())],
+            [col(Hdd::Id.as_str())],
+            JoinArgs::new(JoinType::Anti),
+        )
+        .select([col(Hdd::Id.as_str()), col(CLUSTER_ID)])
+        .collect()?;
+    let without_deleted = |lf: LazyFrame| {
+        lf.join(
+            df_deleted.clone().lazy().select([col(Hdd::Id.as_str())]),
+            [col(Hdd::Id.as_str())],
+            [col(Hdd::Id.as_str())],
+            JoinArgs::new(JoinType::Anti),
+        )
+    };
+    let lf_members = without_deleted(lf_members);
+    let lf_index = without_deleted(lf_index);
+    let remaining_clusters = text_column(
+        &lf_members
+            .clone()
+            .select([col(CLUSTER_ID)])
+            .unique(None, UniqueKeepStrategy::First)
+            .collect()?,
+        CLUSTER_ID,
+    )?
+    .into_iter()
+    .collect::<HashSet<_>>();
+
+    // A record is changed when its text differs from the one of the previous run
+    let df_changed = lf_records
+        .with_column(record_expr(&record_columns))
+        .join(
+            lf_members
+                .clone()
+                .select([col(Hdd::Id.as_str()), record_expr(&record_columns)]),
+            [col(Hdd::Id.as_str()), col(RECORD)],
+            [col(Hdd::Id.as_str()), col(RECORD)],
+            JoinArgs::new(JoinType::Anti),
+        )
+        .drop([RECORD]);
+    // The clusters that lost a record are clustered again, the deleted one may have linked them
+    let lf_orphaned = lf_members
+        .clone()
+        .join(
+            df_deleted.clone().lazy().select([col(CLUSTER_ID)]),
+            [col(CLUSTER_ID)],
+            [col(CLUSTER_ID)],
+            JoinArgs::new(JoinType::Semi),
+        )
+        .select(
+            record_columns
+                .iter()
+                .map(|column| col(column.as_str()))
+                .collect::<Vec<_>>(),
+        );
+    let df_changed = concat([df_changed, lf_orphaned], UnionArgs::default())?
+        .unique_stable(
+            Some(vec![Hdd::Id.as_str().into()]),
+            UniqueKeepStrategy::First,
+        )
+        .collect()?;
+    let mut cluster_update =
+        deduplicator.update(lf_members, lf_index, df_changed.clone().lazy())?;
+    // The clusters of deleted records only
+    for cluster_id in text_column(&df_deleted, CLUSTER_ID)? {
+        if !remaining_clusters.contains(&cluster_id)
+            && !cluster_update.retired_cluster_ids.contains(&cluster_id)
+        {
+            cluster_update.retired_cluster_ids.push(cluster_id);
+        }
+    }
+    let deleted_ids = text_column(&df_deleted, Hdd::Id.as_str())?;
+    recorder.checkpoint()?;
+    info!(
+        "{} records deleted, {} changed, {} golden records updated, {} retired, {} cluster events",
+        deleted_ids.len(),
+        df_changed.height(),
+        cluster_update.df_golden.height(),
+        cluster_update.retired_cluster_ids.len(),
+        cluster_update.df_events.height()
+    );
+
+    let df_events = cluster_update
+        .df_events
+        .lazy()
+        .with_column(lit(recorder.run_id()).alias(RUN_ID))
+        .collect()?;
+    let mut df_golden = cluster_update
+        .df_golden
+        .lazy()
+        .select(exprs_golden_columns())
+        .collect()?;
+    rename_to_sql_columns(&mut df_golden, SchemasEnum::Hdd)?;
+    let changed_ids = text_column(&df_changed, Hdd::Id.as_str())?;
+
+    recorder.checkpoint()?;
+    // A single transaction, a failed run leaves the previous state whole to be run again.
+    // The members go last, they tell the next run which records are already deduplicated
+    let mut tx = pool.begin().await?;
+    let golden_sink = PgSink::new(&golden_table)
+        .with_mode(WriteMode::Upsert)
+        .with_key_columns(&[Hdd::Id.as_str().to_lowercase()]);
+    golden_sink
+        .delete_in(&mut tx, &cluster_update.retired_cluster_ids)
+        .await?;
+    stats.rows_written = golden_sink.write_in(&mut tx, &df_golden).await?;
+    PgSink::new(&cluster_events_table(&golden_table))
+        .with_mode(WriteMode::Append)
+        .write_in(&mut tx, &df_events)
+        .await?;
+    let index_sink = PgSink::new(&index_table)
+        .with_mode(WriteMode::Upsert)
+        .with_key_columns(&[BLOCK_KEY, BLOCK_VALUE, Hdd::Id.as_str()]);
+    let members_sink = PgSink::new(&members_table)
+        .with_mode(WriteMode::Upsert)
+        .with_key_columns(&[Hdd::Id.as_str()]);
+    if has_previous_run {
+        // The blocks of the changed records before their change, and of the deleted ones
+        let stale_ids = [changed_ids.as_slice(), deleted_ids.as_slice()].concat();
+        index_sink
+            .delete_where_in(&mut tx, Hdd::Id.as_str(), &stale_ids)
+            .await?;
+        members_sink.delete_in(&mut tx, &deleted_ids).await?;
+    }
+    index_sink
+        .write_in(&mut tx, &cluster_update.df_index)
+        .await?;
+    members_sink
+        .write_in(&mut tx, &cluster_update.df_records)
+        .await?;
+    tx.commit().await?;
+
+    Ok(stats)
+}
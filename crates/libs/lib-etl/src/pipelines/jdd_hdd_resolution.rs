@@ -0,0 +1,62 @@
+use crate::config::{Config, WriteMode, FILES_PATH, IO_CONFIG_PATH};
+use crate::postgres::{rename_to_dataset_columns, sink::PgSink, source::PgSource};
+use crate::resolution::{EntityLinker, LinkColumns, LinkMethod};
+use crate::run::{RunRecorder, RunStats};
+use crate::schemas::hdd::Hdd;
+use crate::schemas::jdd::Jdd;
+use crate::schemas::{AsString, SchemasEnum};
+use core::error::Error;
+use log::info;
+use polars::prelude::*;
+use sqlx::PgPool;
+
+fn links_table(hdd_table: &str) -> String {
+    format!("{}_jdd_links", hdd_table)
+}
+
+/// Links the deduplicated HDD contacts to the normalised JDD companies, into a link table
+/// next to the HDD golden records, plus a CSV copy.
+///
+/// The HDD has no postal code, so the contacts are linked by SIRET and by SIREN only,
+/// through their active SIRET, the establishment they were transferred to.
+pub async fn run(pool: &PgPool, recorder: &RunRecorder) -> Result<RunStats, Box<dyn Error>> {
+    let config = Config::load(IO_CONFIG_PATH)?;
+    let mut df_contacts = PgSource::from_table(&config.postgres.hdd.table_name)
+        .read(pool)
+        .await?;
+    let mut df_companies = PgSource::from_table(&config.postgres.jdd.table_name)
+        .read(pool)
+        .await?;
+    let mut stats = RunStats {
+        rows_read: (df_contacts.height() + df_companies.height()) as u64,
+        ..Default::default()
+    };
+    recorder.progress(stats).await?;
+    rename_to_dataset_columns(&mut df_contacts, SchemasEnum::Hdd)?;
+    rename_to_dataset_columns(&mut df_companies, SchemasEnum::Jdd)?;
+
+    let linker = EntityLinker::new(
+        LinkColumns::new(Hdd::Id.as_str()).with_siret(Hdd::SiretActif.as_str()),
+        LinkColumns::new(Jdd::Id.as_str())
+            .with_siret(Jdd::Siret.as_str())
+            .with_siren(Jdd::Siren.as_str()),
+    )
+    .with_method(LinkMethod::Siret)
+    .with_method(LinkMethod::SirenPrefix);
+    let mut df_links = linker
+        .links(df_contacts.lazy(), df_companies.lazy())?
+        .collect()?;
+    recorder.checkpoint()?;
+    info!("{} links between contacts and companies", df_links.height());
+
+    let mut csv_file =
+        std::fs::File::create(String::from(FILES_PATH) + "JDD_HDD_resolution_links.csv")?;
+    CsvWriter::new(&mut csv_file).finish(&mut df_links)?;
+
+    stats.rows_written = PgSink::new(&links_table(&config.postgres.hdd.table_name))
+        .with_mode(WriteMode::FullRefresh)
+        .write(pool, &df_links)
+        .await?;
+
+    Ok(stats)
+}
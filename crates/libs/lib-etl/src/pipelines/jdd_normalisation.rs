@@ -0,0 +1,91 @@
+use crate::config::{Config, Transform, FILES_PATH, IO_CONFIG_PATH};
+use crate::files::parquet::ParquetSink;
+use crate::postgres::{
+    rename_to_dataset_columns, rename_to_sql_columns, sink::PgSink, source::PgSource,
+};
+use crate::run::{RunRecorder, RunStats};
+use crate::schemas::jdd::Jdd;
+use crate::schemas::{AsString, SchemasEnum};
+use crate::transforms::ape::col_ape_with_polars_expr;
+use crate::transforms::code_naf::col_code_naf_with_polars_expr;
+use crate::transforms::col_with_udf_expr;
+use crate::transforms::email::col_email_with_polars_expr;
+use crate::transforms::libelle_naf::col_libelle_naf_with_polars_expr;
+use crate::transforms::nom::col_nom_with_polars_expr;
+use crate::transforms::prenom::col_prenom_with_polars_expr;
+use crate::transforms::raison_sociale::{
+    col_forme_juridique_with_polars_expr, col_raison_sociale_key_with_polars_expr,
+    col_raison_sociale_with_polars_expr,
+};
+use crate::transforms::siren::col_siren_with_polars_expr;
+use crate::transforms::siret::col_siret_with_polars_expr;
+use core::error::Error;
+use log::info;
+use polars::prelude::*;
+use sea_query::Iden;
+use sqlx::PgPool;
+
+/// Normalises the raw `jdd` table into the configured Postgres table, plus CSV and Parquet copies.
+pub async fn run(pool: &PgPool, recorder: &RunRecorder) -> Result<RunStats, Box<dyn Error>> {
+    let mut df = PgSource::from_table(&Jdd::Table.to_string())
+        .read(pool)
+        .await?;
+    let mut stats = RunStats {
+        rows_read: df.height() as u64,
+        ..Default::default()
+    };
+    recorder.progress(stats).await?;
+    rename_to_dataset_columns(&mut df, SchemasEnum::Jdd)?;
+
+    let lf = df
+        .lazy()
+        .with_columns(vec![
+            col_nom_with_polars_expr(SchemasEnum::Jdd),
+            col_prenom_with_polars_expr(SchemasEnum::Jdd),
+            col_with_udf_expr(Jdd::Civilite, Transform::Civilite),
+            col_email_with_polars_expr(SchemasEnum::Jdd),
+            col_with_udf_expr(Jdd::Telephone, Transform::Telephone),
+            col_raison_sociale_with_polars_expr(SchemasEnum::Jdd),
+            col_code_naf_with_polars_expr(),
+            col_ape_with_polars_expr(),
+            col_siret_with_polars_expr(SchemasEnum::Jdd),
+            col_siren_with_polars_expr(),
+            col_libelle_naf_with_polars_expr(),
+        ])
+        // The legal form and the matching key of the normalised raison sociale
+        .with_columns(vec![
+            col_forme_juridique_with_polars_expr(SchemasEnum::Jdd),
+            col_raison_sociale_key_with_polars_expr(SchemasEnum::Jdd),
+        ]);
+
+    let mut df = lf.collect()?;
+    recorder.checkpoint()?;
+    let file_name = String::from(FILES_PATH) + "JDD_normalisation_transformed.csv";
+
+    let mut csv_file = std::fs::File::create(&file_name)?;
+    CsvWriter::new(&mut csv_file).finish(&mut df)?;
+    info!(
+        "A new JDD_normalisation_transformed.csv file has been created at {}",
+        file_name
+    );
+
+    let parquet_dir = String::from(FILES_PATH) + "JDD_normalisation_transformed";
+    let files = ParquetSink::new(&parquet_dir)
+        .with_partition_by(&[Jdd::Region.as_str()])
+        .write(&mut df)?;
+    info!(
+        "{} Parquet partitions written under {}",
+        files.len(),
+        parquet_dir
+    );
+
+    recorder.checkpoint()?;
+
+    let config = Config::load(IO_CONFIG_PATH)?;
+    rename_to_sql_columns(&mut df, SchemasEnum::Jdd)?;
+    stats.rows_written = PgSink::from_config(&config.postgres.jdd)
+        .write(pool, &df)
+        .await?;
+
+    Ok(stats)
+}
@@ -0,0 +1,130 @@
+use crate::schemas::{hdd::Hdd, jdd::Jdd, FieldType, SchemasEnum};
+use crate::transforms::identifier::col_identifiers_as_text_exprs;
+use polars::{error::PolarsResult, frame::DataFrame, lazy::frame::IntoLazy};
+use sea_query::{Alias, ColumnDef, Iden, PostgresQueryBuilder, Table};
+use sqlx::PgPool;
+
+pub mod migration;
+pub mod sink;
+pub mod source;
+
+/// Quotes a Postgres identifier, for the statements sea-query cannot build (e.g. `COPY`).
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Whether `table` exists, e.g. before reading the state left by a previous run.
+pub async fn table_exists(pool: &PgPool, table: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+        .bind(quote_ident(table))
+        .fetch_one(pool)
+        .await
+}
+
+/// `CREATE TABLE IF NOT EXISTS` of the raw `table` of a dataset: the auto-increment `id` the
+/// pipelines read, then one column per field of the dataset.
+pub fn create_raw_table_sql(table: &str, se: SchemasEnum) -> String {
+    let id = match se {
+        SchemasEnum::Jdd => Jdd::Id.to_string(),
+        SchemasEnum::Hdd => Hdd::Id.to_string(),
+    };
+    let mut statement = Table::create();
+    statement.table(Alias::new(table)).if_not_exists().col(
+        ColumnDef::new(Alias::new(id))
+            .integer()
+            .not_null()
+            .auto_increment()
+            .primary_key(),
+    );
+    for field in se.fields() {
+        let mut column = ColumnDef::new(Alias::new(&field.sql_name));
+        match field.field_type {
+            FieldType::String => column.text(),
+            FieldType::Int32 => column.integer(),
+            FieldType::Float64 => column.double(),
+        };
+        statement.col(column);
+    }
+    statement.to_string(PostgresQueryBuilder)
+}
+
+/// Creates the raw `table` of a dataset when it does not exist, see [`create_raw_table_sql`].
+pub async fn create_raw_table(
+    pool: &PgPool,
+    table: &str,
+    se: SchemasEnum,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&create_raw_table_sql(table, se))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Prepares a dataset file read by [`crate::csv::csv_to_dataframe`] for its raw table:
+/// the identifiers as clean text, the columns renamed to their Postgres names.
+pub fn to_raw_table_dataframe(df: DataFrame, se: SchemasEnum) -> PolarsResult<DataFrame> {
+    let mut df = df
+        .lazy()
+        .with_columns(col_identifiers_as_text_exprs(se))
+        .collect()?;
+    rename_to_sql_columns(&mut df, se)?;
+    Ok(df)
+}
+
+/// Renames the dataset columns of `df` (e.g. `CODE POSTALE`) to their Postgres names (e.g. `code_postale`).
+/// Columns outside the dataset schema (e.g. `ID`, `IDS`) are lowercased, with spaces replaced by `_`.
+pub fn rename_to_sql_columns(df: &mut DataFrame, se: SchemasEnum) -> PolarsResult<()> {
+    let fields = se.fields();
+    let column_names = df
+        .get_column_names()
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    for name in column_names {
+        let sql_name = fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.sql_name.clone())
+            .unwrap_or_else(|| name.to_lowercase().replace(' ', "_"));
+        if sql_name != name {
+            df.rename(&name, sql_name.into())?;
+        }
+    }
+    Ok(())
+}
+
+/// Renames the Postgres columns of `df` back to their dataset names, the inverse of [`rename_to_sql_columns`].
+/// Columns outside the dataset schema (e.g. `id`) are uppercased.
+pub fn rename_to_dataset_columns(df: &mut DataFrame, se: SchemasEnum) -> PolarsResult<()> {
+    let fields = se.fields();
+    let column_names = df
+        .get_column_names()
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    for name in column_names {
+        let dataset_name = fields
+            .iter()
+            .find(|field| field.sql_name == name)
+            .map(|field| field.name.to_string())
+            .unwrap_or_else(|| name.to_uppercase());
+        if dataset_name != name {
+            df.rename(&name, dataset_name.into())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_raw_table_sql() {
+        let sql = create_raw_table_sql("hdd", SchemasEnum::Hdd);
+
+        assert!(sql.starts_with(r#"CREATE TABLE IF NOT EXISTS "hdd" ( "id" serial"#));
+        assert!(sql.contains(r#""siret" text"#));
+        assert!(sql.contains(r#""id_source" integer"#));
+    }
+}
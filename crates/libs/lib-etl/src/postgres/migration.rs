@@ -0,0 +1,68 @@
+use super::quote_ident;
+use core::error::Error;
+use log::info;
+use sqlx::PgPool;
+
+const FLOAT_TYPES: [&str; 3] = ["double precision", "real", "numeric"];
+
+/// Converts the `columns` of `table` still stored as floating point numbers into text columns.
+///
+/// Tables created before identifiers were stored as text hold values like `12345678910124`
+/// in `double precision` columns. Going through `numeric` writes them back without decimals or
+/// exponent. Columns already stored as text are left untouched, so the migration can run again.
+/// Returns the converted columns.
+pub async fn migrate_float_columns_to_text<S: AsRef<str>>(
+    pool: &PgPool,
+    table: &str,
+    columns: &[S],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut tx = pool.begin().await?;
+
+    let float_columns: Vec<String> = sqlx::query_scalar(
+        "SELECT column_name::text FROM information_schema.columns \
+         WHERE table_schema = current_schema() AND table_name = $1 AND data_type = ANY($2)",
+    )
+    .bind(table)
+    .bind(&FLOAT_TYPES[..])
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let to_migrate = columns
+        .iter()
+        .map(|column| column.as_ref().to_string())
+        .filter(|column| float_columns.contains(column))
+        .collect::<Vec<_>>();
+
+    for column in &to_migrate {
+        sqlx::query(&alter_column_to_text_sql(table, column))
+            .execute(&mut *tx)
+            .await?;
+        info!("Column {}.{} migrated to text", table, column);
+    }
+
+    tx.commit().await?;
+    Ok(to_migrate)
+}
+
+fn alter_column_to_text_sql(table: &str, column: &str) -> String {
+    let column = quote_ident(column);
+    format!(
+        "ALTER TABLE {} ALTER COLUMN {} TYPE text USING {}::numeric::text",
+        quote_ident(table),
+        column,
+        column
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alter_column_to_text_sql() {
+        assert_eq!(
+            alter_column_to_text_sql("hdd", "siret_successeur"),
+            r#"ALTER TABLE "hdd" ALTER COLUMN "siret_successeur" TYPE text USING "siret_successeur"::numeric::text"#
+        );
+    }
+}
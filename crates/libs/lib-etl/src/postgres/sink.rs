@@ -0,0 +1,562 @@
+use super::quote_ident;
+use crate::config::{PgSinkConfig, WriteMode};
+use core::error::Error;
+use log::info;
+use polars::{datatypes::DataType, error::PolarsResult, frame::DataFrame, prelude::Schema};
+use sea_query::{
+    Alias, ColumnDef, Expr, Index, OnConflict, Order, PostgresQueryBuilder, Query, Table,
+};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+
+const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// Loads DataFrames into a Postgres table.
+///
+/// The target table is created from the DataFrame schema when it does not exist yet.
+/// Rows are streamed with `COPY ... FROM STDIN`:
+/// - [`WriteMode::FullRefresh`] truncates the table and copies the rows into it,
+/// - [`WriteMode::Append`] copies the rows into the table, e.g. for a log of events,
+/// - [`WriteMode::Upsert`] copies the rows into a temporary staging table, then merges them
+///   into the target table with `INSERT ... ON CONFLICT` on the natural key, so re-running a load
+///   updates the existing rows instead of duplicating them. Without natural key, rows are appended.
+///
+/// The natural key of an upsert gets a unique index `NULLS NOT DISTINCT`, which needs Postgres 15
+/// or later. A table already holding rows with the same key, e.g. loaded by an append, fails
+/// with the statement removing the duplicates.
+///
+/// Each write runs in a single transaction, readers never see a partially loaded table.
+/// [`PgSink::write_in`] and [`PgSink::delete_in`] run in a transaction of the caller instead,
+/// to load several tables together, or the same table several times.
+pub struct PgSink {
+    table: String,
+    key_columns: Vec<String>,
+    batch_size: usize,
+    mode: WriteMode,
+}
+
+impl PgSink {
+    pub fn new(table: &str) -> Self {
+        PgSink {
+            table: table.to_string(),
+            key_columns: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            mode: WriteMode::default(),
+        }
+    }
+
+    pub fn from_config(config: &PgSinkConfig) -> Self {
+        PgSink::new(&config.table_name)
+            .with_key_columns(&config.natural_key)
+            .with_mode(config.mode)
+    }
+
+    pub fn with_mode(mut self, mode: WriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_key_columns<S: AsRef<str>>(mut self, key_columns: &[S]) -> Self {
+        self.key_columns = key_columns.iter().map(|c| c.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Number of rows encoded and sent per `COPY` chunk.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn staging_table(&self) -> String {
+        format!("{}_staging", self.table)
+    }
+
+    /// Writes `df` into the target table and returns the number of rows loaded, inserted or updated.
+    /// The column names of `df` must match the table columns.
+    pub async fn write(&self, pool: &PgPool, df: &DataFrame) -> Result<u64, Box<dyn Error>> {
+        let mut tx = pool.begin().await?;
+        let written = self.write_in(&mut tx, df).await?;
+        tx.commit().await?;
+        Ok(written)
+    }
+
+    /// Same as [`PgSink::write`], in a transaction of the caller.
+    pub async fn write_in(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        df: &DataFrame,
+    ) -> Result<u64, Box<dyn Error>> {
+        let conn: &mut PgConnection = tx;
+        let columns = df
+            .get_column_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+
+        sqlx::query(&create_table_sql(&self.table, df.schema())?)
+            .execute(&mut *conn)
+            .await?;
+
+        let written = match self.mode {
+            WriteMode::FullRefresh => {
+                sqlx::query(&truncate_sql(&self.table))
+                    .execute(&mut *conn)
+                    .await?;
+                self.copy(conn, &self.table, &columns, df).await?
+            }
+            WriteMode::Append => self.copy(conn, &self.table, &columns, df).await?,
+            WriteMode::Upsert => {
+                let staging_table = self.staging_table();
+                sqlx::query(&create_staging_sql(&self.table, &staging_table, &columns))
+                    .execute(&mut *conn)
+                    .await?;
+                let copied = self.copy(conn, &staging_table, &columns, df).await?;
+                if !self.key_columns.is_empty() {
+                    self.ensure_key_index(conn).await?;
+                }
+                let merged = sqlx::query(&self.merge_sql(&staging_table, &columns)?)
+                    .execute(&mut *conn)
+                    .await?
+                    .rows_affected();
+                // Dropped right away, a later upsert in the same transaction creates it again
+                sqlx::query(&drop_table_sql(&staging_table))
+                    .execute(&mut *conn)
+                    .await?;
+                info!("Merged {} staged rows into {}", copied, self.table);
+                merged
+            }
+        };
+
+        info!(
+            "{} rows written into {} ({:?})",
+            written, self.table, self.mode
+        );
+
+        Ok(written)
+    }
+
+    /// Deletes the rows whose natural key, of a single column, is one of `keys`,
+    /// and returns the number of rows deleted.
+    pub async fn delete(&self, pool: &PgPool, keys: &[String]) -> Result<u64, Box<dyn Error>> {
+        let mut tx = pool.begin().await?;
+        let deleted = self.delete_in(&mut tx, keys).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// Same as [`PgSink::delete`], in a transaction of the caller.
+    pub async fn delete_in(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        keys: &[String],
+    ) -> Result<u64, Box<dyn Error>> {
+        let key_column = self.single_key_column()?.to_string();
+        self.delete_where_in(tx, &key_column, keys).await
+    }
+
+    /// Deletes the rows whose `column` is one of `values`, in a transaction of the caller,
+    /// and returns the number of rows deleted.
+    ///
+    /// The values are bound as a single array, cast to the type of the column.
+    pub async fn delete_where_in(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        column: &str,
+        values: &[String],
+    ) -> Result<u64, Box<dyn Error>> {
+        if values.is_empty() {
+            return Ok(0);
+        }
+        let column_type: Option<String> = sqlx::query_scalar(
+            "SELECT format_type(atttypid, atttypmod) FROM pg_attribute \
+             WHERE attrelid = to_regclass($1) AND attname = $2 AND NOT attisdropped",
+        )
+        .bind(quote_ident(&self.table))
+        .bind(column)
+        .fetch_optional(&mut **tx)
+        .await?;
+        let column_type =
+            column_type.ok_or_else(|| format!("No column {} in {}", column, self.table))?;
+        let deleted = sqlx::query(&delete_sql(&self.table, column, &column_type))
+            .bind(values)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        info!("{} rows deleted from {}", deleted, self.table);
+        Ok(deleted)
+    }
+
+    async fn copy(
+        &self,
+        conn: &mut PgConnection,
+        table: &str,
+        columns: &[String],
+        df: &DataFrame,
+    ) -> Result<u64, Box<dyn Error>> {
+        let mut copy_in = conn.copy_in_raw(&copy_sql(table, columns)).await?;
+        let mut offset = 0;
+        while offset < df.height() {
+            let batch = df.slice(offset as i64, self.batch_size);
+            copy_in.send(dataframe_to_copy_text(&batch)?).await?;
+            offset += self.batch_size;
+        }
+        Ok(copy_in.finish().await?)
+    }
+
+    fn single_key_column(&self) -> Result<&str, Box<dyn Error>> {
+        let [key_column] = self.key_columns.as_slice() else {
+            return Err(format!(
+                "Deleting from {} needs a natural key of a single column",
+                self.table
+            )
+            .into());
+        };
+        Ok(key_column)
+    }
+
+    /// Creates the unique index of the natural key on the first upsert. It cannot be built over
+    /// rows sharing a key, these are reported with the statement removing them instead.
+    async fn ensure_key_index(&self, conn: &mut PgConnection) -> Result<(), Box<dyn Error>> {
+        let index_exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(quote_ident(&self.key_index_name()))
+            .fetch_one(&mut *conn)
+            .await?;
+        if index_exists {
+            return Ok(());
+        }
+        let duplicated_keys: i64 = sqlx::query_scalar(&self.duplicated_keys_sql())
+            .fetch_one(&mut *conn)
+            .await?;
+        if duplicated_keys > 0 {
+            return Err(format!(
+                "{} natural keys ({}) are held by several rows of {}, the upsert needs them unique. \
+                 Keep the last row of each key with: {}",
+                duplicated_keys,
+                self.key_columns.join(", "),
+                self.table,
+                self.delete_duplicates_sql()
+            )
+            .into());
+        }
+        sqlx::query(&self.key_index_sql())
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    fn key_index_name(&self) -> String {
+        format!("{}_natural_key_idx", self.table)
+    }
+
+    /// Number of natural keys held by several rows, NULLs being equal as in the index.
+    fn duplicated_keys_sql(&self) -> String {
+        format!(
+            "SELECT count(*) FROM (SELECT 1 FROM {} GROUP BY {} HAVING count(*) > 1) AS duplicated",
+            quote_ident(&self.table),
+            quoted_column_list(&self.key_columns)
+        )
+    }
+
+    /// Deletes the rows whose natural key is held by a later row.
+    fn delete_duplicates_sql(&self) -> String {
+        let same_key = self
+            .key_columns
+            .iter()
+            .map(|key| {
+                format!(
+                    "earlier.{key} IS NOT DISTINCT FROM later.{key}",
+                    key = quote_ident(key)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        format!(
+            "DELETE FROM {table} AS earlier USING {table} AS later WHERE earlier.ctid < later.ctid AND {}",
+            same_key,
+            table = quote_ident(&self.table)
+        )
+    }
+
+    /// `ON CONFLICT` needs a unique index on the natural key. NULLs are not distinct,
+    /// so rows with an empty key column are merged too. Needs Postgres 15 or later.
+    fn key_index_sql(&self) -> String {
+        let mut index = Index::create();
+        index
+            .if_not_exists()
+            .unique()
+            .nulls_not_distinct()
+            .name(self.key_index_name())
+            .table(Alias::new(&self.table));
+        for key in &self.key_columns {
+            index.col(Alias::new(key));
+        }
+        index.to_string(PostgresQueryBuilder)
+    }
+
+    fn merge_sql(&self, staging_table: &str, columns: &[String]) -> Result<String, Box<dyn Error>> {
+        let mut select = Query::select();
+        select
+            .columns(columns.iter().map(Alias::new))
+            .from(Alias::new(staging_table));
+        if !self.key_columns.is_empty() {
+            // Keep the last occurrence of a key, a single INSERT cannot update the same row twice
+            select.distinct_on(self.key_columns.iter().map(Alias::new));
+            for key in &self.key_columns {
+                select.order_by(Alias::new(key), Order::Asc);
+            }
+            select.order_by_expr(Expr::cust("ctid"), Order::Desc);
+        }
+
+        let mut insert = Query::insert();
+        insert
+            .into_table(Alias::new(&self.table))
+            .columns(columns.iter().map(Alias::new))
+            .select_from(select)?;
+
+        if !self.key_columns.is_empty() {
+            let update_columns = columns
+                .iter()
+                .filter(|column| !self.key_columns.contains(column))
+                .map(Alias::new)
+                .collect::<Vec<_>>();
+            let mut on_conflict = OnConflict::columns(self.key_columns.iter().map(Alias::new));
+            if update_columns.is_empty() {
+                on_conflict.do_nothing();
+            } else {
+                on_conflict.update_columns(update_columns);
+            }
+            insert.on_conflict(on_conflict);
+        }
+
+        Ok(insert.to_string(PostgresQueryBuilder))
+    }
+}
+
+/// `CREATE TABLE IF NOT EXISTS` with one column per DataFrame column.
+pub fn create_table_sql(table: &str, schema: &Schema) -> Result<String, Box<dyn Error>> {
+    let mut statement = Table::create();
+    statement.table(Alias::new(table)).if_not_exists();
+    for (name, dtype) in schema.iter() {
+        let mut column = ColumnDef::new(Alias::new(name.as_str()));
+        match dtype {
+            DataType::Boolean => column.boolean(),
+            DataType::Int8 | DataType::Int16 | DataType::UInt8 => column.small_integer(),
+            DataType::Int32 | DataType::UInt16 => column.integer(),
+            DataType::Int64 | DataType::UInt32 | DataType::UInt64 => column.big_integer(),
+            DataType::Float32 => column.float(),
+            DataType::Float64 => column.double(),
+            DataType::String => column.text(),
+            DataType::Date => column.date(),
+            DataType::Datetime(_, _) => column.timestamp(),
+            DataType::Time => column.time(),
+            dtype => {
+                return Err(format!(
+                    "Column {} of type {} cannot be written to Postgres",
+                    name, dtype
+                )
+                .into())
+            }
+        };
+        statement.col(column);
+    }
+    Ok(statement.to_string(PostgresQueryBuilder))
+}
+
+/// Deletes the rows whose `column` is in the text array bound to `$1`.
+fn delete_sql(table: &str, column: &str, column_type: &str) -> String {
+    format!(
+        "DELETE FROM {} WHERE {} = ANY(CAST($1 AS {}[]))",
+        quote_ident(table),
+        quote_ident(column),
+        column_type
+    )
+}
+
+fn truncate_sql(table: &str) -> String {
+    Table::truncate()
+        .table(Alias::new(table))
+        .to_string(PostgresQueryBuilder)
+}
+
+fn drop_table_sql(table: &str) -> String {
+    Table::drop()
+        .table(Alias::new(table))
+        .to_string(PostgresQueryBuilder)
+}
+
+fn create_staging_sql(table: &str, staging_table: &str, columns: &[String]) -> String {
+    format!(
+        "CREATE TEMP TABLE {} ON COMMIT DROP AS SELECT {} FROM {} WITH NO DATA",
+        quote_ident(staging_table),
+        quoted_column_list(columns),
+        quote_ident(table)
+    )
+}
+
+fn copy_sql(staging_table: &str, columns: &[String]) -> String {
+    format!(
+        "COPY {} ({}) FROM STDIN",
+        quote_ident(staging_table),
+        quoted_column_list(columns)
+    )
+}
+
+fn quoted_column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| quote_ident(column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Encodes `df` in the `COPY` text format: tab separated columns, `\N` for NULL.
+pub fn dataframe_to_copy_text(df: &DataFrame) -> PolarsResult<Vec<u8>> {
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|column| column.cast(&DataType::String))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let columns = columns
+        .iter()
+        .map(|column| column.str())
+        .collect::<PolarsResult<Vec<_>>>()?;
+
+    let mut buffer = Vec::new();
+    for row in 0..df.height() {
+        for (index, column) in columns.iter().enumerate() {
+            if index > 0 {
+                buffer.push(b'\t');
+            }
+            match column.get(row) {
+                Some(value) => escape_copy_text(value, &mut buffer),
+                None => buffer.extend_from_slice(b"\\N"),
+            }
+        }
+        buffer.push(b'\n');
+    }
+    Ok(buffer)
+}
+
+fn escape_copy_text(value: &str, buffer: &mut Vec<u8>) {
+    for byte in value.bytes() {
+        match byte {
+            b'\\' => buffer.extend_from_slice(b"\\\\"),
+            b'\t' => buffer.extend_from_slice(b"\\t"),
+            b'\n' => buffer.extend_from_slice(b"\\n"),
+            b'\r' => buffer.extend_from_slice(b"\\r"),
+            _ => buffer.push(byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::{df, prelude::Series};
+
+    #[test]
+    fn test_dataframe_to_copy_text() {
+        let df = df![
+            "Nom" => &[Some("SALLANDIER"), None, Some("CARRE\tPlomberie\\")],
+            "Id_source" => &[Some(1), Some(2), None],
+        ]
+        .expect("DataFrame creation failed");
+
+        let text = dataframe_to_copy_text(&df).expect("Encoding failed");
+
+        assert_eq!(
+            String::from_utf8(text).expect("Invalid UTF-8"),
+            "SALLANDIER\t1\n\\N\t2\nCARRE\\tPlomberie\\\\\t\\N\n"
+        );
+    }
+
+    #[test]
+    fn test_create_table_sql_from_dataframe_schema() {
+        let df = df![
+            "id" => &[1i32],
+            "siret successeur" => &["443 169 524 00120"],
+            "score" => &[0.5f64],
+        ]
+        .expect("DataFrame creation failed");
+
+        let sql = create_table_sql("hdd_golden", df.schema()).expect("Create table SQL failed");
+
+        assert_eq!(
+            sql,
+            r#"CREATE TABLE IF NOT EXISTS "hdd_golden" ( "id" integer, "siret successeur" text, "score" double precision )"#
+        );
+    }
+
+    #[test]
+    fn test_create_table_sql_rejects_nested_columns() {
+        let df = df![
+            "ids" => &[Series::new("".into(), &["1", "2"])],
+        ]
+        .expect("DataFrame creation failed");
+
+        assert!(create_table_sql("hdd_golden", df.schema()).is_err());
+    }
+
+    #[test]
+    fn test_merge_sql_upserts_on_natural_key() {
+        let sink = PgSink::new("hdd").with_key_columns(&["id_source"]);
+        let columns = vec!["id_source".to_string(), "nom".to_string()];
+
+        let sql = sink
+            .merge_sql("hdd_staging", &columns)
+            .expect("Merge SQL failed");
+
+        assert!(sql.starts_with(
+            r#"INSERT INTO "hdd" ("id_source", "nom") SELECT DISTINCT ON ("id_source")"#
+        ));
+        assert!(sql.contains(r#"FROM "hdd_staging""#));
+        assert!(sql.contains("ctid DESC"));
+        assert!(sql.contains(r#"ON CONFLICT ("id_source") DO UPDATE SET "nom" = "excluded"."nom""#));
+    }
+
+    #[test]
+    fn test_delete_sql() {
+        let sql = delete_sql("hdd_golden", "id", "integer");
+
+        assert_eq!(
+            sql,
+            r#"DELETE FROM "hdd_golden" WHERE "id" = ANY(CAST($1 AS integer[]))"#
+        );
+        assert_eq!(
+            PgSink::new("hdd_golden")
+                .with_key_columns(&["id"])
+                .single_key_column()
+                .unwrap(),
+            "id"
+        );
+        assert!(PgSink::new("hdd_golden").single_key_column().is_err());
+    }
+
+    #[test]
+    fn test_duplicated_keys_sql() {
+        let sink = PgSink::new("hdd").with_key_columns(&["id_source", "pce"]);
+
+        assert_eq!(
+            sink.duplicated_keys_sql(),
+            r#"SELECT count(*) FROM (SELECT 1 FROM "hdd" GROUP BY "id_source", "pce" HAVING count(*) > 1) AS duplicated"#
+        );
+        assert_eq!(
+            sink.delete_duplicates_sql(),
+            r#"DELETE FROM "hdd" AS earlier USING "hdd" AS later WHERE earlier.ctid < later.ctid AND earlier."id_source" IS NOT DISTINCT FROM later."id_source" AND earlier."pce" IS NOT DISTINCT FROM later."pce""#
+        );
+    }
+
+    #[test]
+    fn test_merge_sql_without_key_appends() {
+        let sink = PgSink::new("jdd");
+        let columns = vec!["siret".to_string()];
+
+        let sql = sink
+            .merge_sql("jdd_staging", &columns)
+            .expect("Merge SQL failed");
+
+        assert_eq!(
+            sql,
+            r#"INSERT INTO "jdd" ("siret") SELECT "siret" FROM "jdd_staging""#
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use super::quote_ident;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use core::error::Error;
+use futures::TryStreamExt;
+use log::{debug, info};
+use polars::{
+    datatypes::{DataType, DatetimeChunked, TimeUnit},
+    frame::DataFrame,
+    prelude::{Column, IntoColumn, IntoSeries, NamedFrom},
+    series::Series,
+};
+use sea_query::{Alias, Asterisk, PostgresQueryBuilder, Query};
+use sqlx::{postgres::PgRow, types::Uuid, Column as _, Executor, PgPool, Row, TypeInfo};
+
+const DEFAULT_BATCH_SIZE: usize = 10_000;
+const CURSOR_NAME: &str = "lib_etl_source_cursor";
+
+/// Reads the result of a query into DataFrames.
+///
+/// The query runs behind a server-side cursor, rows are fetched `batch_size` at a time and
+/// decoded straight into typed column builders, so a table never has to fit in memory as rows
+/// and as a DataFrame at the same time.
+pub struct PgSource {
+    query: String,
+    batch_size: usize,
+}
+
+impl PgSource {
+    pub fn new(query: &str) -> Self {
+        PgSource {
+            query: query.to_string(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Selects every column of `table`.
+    pub fn from_table(table: &str) -> Self {
+        PgSource::new(
+            &Query::select()
+                .column(Asterisk)
+                .from(Alias::new(table))
+                .to_string(PostgresQueryBuilder),
+        )
+    }
+
+    /// Number of rows fetched from the cursor per DataFrame.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Calls `on_batch` with each DataFrame of at most `batch_size` rows and returns the number of rows read.
+    pub async fn for_each_batch<F>(
+        &self,
+        pool: &PgPool,
+        mut on_batch: F,
+    ) -> Result<u64, Box<dyn Error>>
+    where
+        F: FnMut(DataFrame) -> Result<(), Box<dyn Error>>,
+    {
+        let mut tx = pool.begin().await?;
+
+        let describe = (&mut *tx).describe(&self.query).await?;
+        let mut builders = describe
+            .columns()
+            .iter()
+            .map(|column| {
+                ColumnBuilder::new(column.type_info().name())
+                    .map(|builder| (column.name().to_string(), builder))
+                    .ok_or_else(|| {
+                        format!(
+                            "Column {} has unsupported type {}, cast it in the query",
+                            column.name(),
+                            column.type_info().name()
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        sqlx::query(&declare_cursor_sql(&self.query))
+            .execute(&mut *tx)
+            .await?;
+
+        let fetch_sql = fetch_sql(self.batch_size);
+        let mut total = 0;
+        loop {
+            let mut rows = sqlx::query(&fetch_sql).fetch(&mut *tx);
+            let mut fetched: u64 = 0;
+            while let Some(row) = rows.try_next().await? {
+                for (index, (_, builder)) in builders.iter_mut().enumerate() {
+                    builder.push(&row, index)?;
+                }
+                fetched += 1;
+            }
+            drop(rows);
+
+            if fetched == 0 && total > 0 {
+                break;
+            }
+            total += fetched;
+            let columns = builders
+                .iter_mut()
+                .map(|(name, builder)| builder.finish(name))
+                .collect::<Vec<_>>();
+            let batch = DataFrame::new(columns)?;
+            debug!("Fetched a batch of {} rows", batch.height());
+            on_batch(batch)?;
+
+            if fetched < self.batch_size as u64 {
+                break;
+            }
+        }
+
+        sqlx::query(&format!("CLOSE {}", quote_ident(CURSOR_NAME)))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        info!("Read {} rows from Postgres", total);
+
+        Ok(total)
+    }
+
+    /// Reads all the rows into a single DataFrame.
+    pub async fn read(&self, pool: &PgPool) -> Result<DataFrame, Box<dyn Error>> {
+        let mut df: Option<DataFrame> = None;
+        self.for_each_batch(pool, |batch| {
+            match df.as_mut() {
+                Some(df) => {
+                    df.vstack_mut(&batch)?;
+                }
+                None => df = Some(batch),
+            }
+            Ok(())
+        })
+        .await?;
+        let mut df = df.unwrap_or_default();
+        df.as_single_chunk_par();
+        Ok(df)
+    }
+}
+
+fn declare_cursor_sql(query: &str) -> String {
+    format!(
+        "DECLARE {} NO SCROLL CURSOR FOR {}",
+        quote_ident(CURSOR_NAME),
+        query
+    )
+}
+
+fn fetch_sql(batch_size: usize) -> String {
+    format!(
+        "FETCH FORWARD {} FROM {}",
+        batch_size,
+        quote_ident(CURSOR_NAME)
+    )
+}
+
+/// Polars dtype of the columns read from a Postgres type, `None` when the type is not supported.
+pub fn pg_type_to_dtype(pg_type: &str) -> Option<DataType> {
+    ColumnBuilder::new(pg_type).map(|builder| builder.dtype())
+}
+
+enum ColumnBuilder {
+    Boolean(Vec<Option<bool>>),
+    Int16(Vec<Option<i32>>),
+    Int32(Vec<Option<i32>>),
+    Int64(Vec<Option<i64>>),
+    Float32(Vec<Option<f32>>),
+    Float64(Vec<Option<f64>>),
+    String(Vec<Option<String>>),
+    Uuid(Vec<Option<String>>),
+    Date(Vec<Option<NaiveDate>>),
+    Timestamp(Vec<Option<NaiveDateTime>>),
+    /// Stored as UTC timestamps without time zone.
+    Timestamptz(Vec<Option<NaiveDateTime>>),
+}
+
+impl ColumnBuilder {
+    fn new(pg_type: &str) -> Option<Self> {
+        let builder = match pg_type {
+            "BOOL" => ColumnBuilder::Boolean(Vec::new()),
+            "INT2" => ColumnBuilder::Int16(Vec::new()),
+            "INT4" => ColumnBuilder::Int32(Vec::new()),
+            "INT8" => ColumnBuilder::Int64(Vec::new()),
+            "FLOAT4" => ColumnBuilder::Float32(Vec::new()),
+            "FLOAT8" => ColumnBuilder::Float64(Vec::new()),
+            "TEXT" | "VARCHAR" | "CHAR" | "NAME" => ColumnBuilder::String(Vec::new()),
+            "UUID" => ColumnBuilder::Uuid(Vec::new()),
+            "DATE" => ColumnBuilder::Date(Vec::new()),
+            "TIMESTAMP" => ColumnBuilder::Timestamp(Vec::new()),
+            "TIMESTAMPTZ" => ColumnBuilder::Timestamptz(Vec::new()),
+            _ => return None,
+        };
+        Some(builder)
+    }
+
+    fn dtype(&self) -> DataType {
+        match self {
+            ColumnBuilder::Boolean(_) => DataType::Boolean,
+            ColumnBuilder::Int16(_) | ColumnBuilder::Int32(_) => DataType::Int32,
+            ColumnBuilder::Int64(_) => DataType::Int64,
+            ColumnBuilder::Float32(_) => DataType::Float32,
+            ColumnBuilder::Float64(_) => DataType::Float64,
+            ColumnBuilder::String(_) | ColumnBuilder::Uuid(_) => DataType::String,
+            ColumnBuilder::Date(_) => DataType::Date,
+            ColumnBuilder::Timestamp(_) | ColumnBuilder::Timestamptz(_) => {
+                DataType::Datetime(TimeUnit::Microseconds, None)
+            }
+        }
+    }
+
+    fn push(&mut self, row: &PgRow, index: usize) -> Result<(), sqlx::Error> {
+        match self {
+            ColumnBuilder::Boolean(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Int16(values) => {
+                values.push(row.try_get::<Option<i16>, _>(index)?.map(i32::from))
+            }
+            ColumnBuilder::Int32(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Int64(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Float32(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Float64(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::String(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Uuid(values) => values.push(
+                row.try_get::<Option<Uuid>, _>(index)?
+                    .map(|uuid| uuid.to_string()),
+            ),
+            ColumnBuilder::Date(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Timestamp(values) => values.push(row.try_get(index)?),
+            ColumnBuilder::Timestamptz(values) => values.push(
+                row.try_get::<Option<DateTime<Utc>>, _>(index)?
+                    .map(|timestamp| timestamp.naive_utc()),
+            ),
+        }
+        Ok(())
+    }
+
+    /// Builds the column from the values pushed since the last call, and empties the builder.
+    fn finish(&mut self, name: &str) -> Column {
+        let name = name.into();
+        let series = match self {
+            ColumnBuilder::Boolean(values) => Series::new(name, std::mem::take(values)),
+            ColumnBuilder::Int16(values) | ColumnBuilder::Int32(values) => {
+                Series::new(name, std::mem::take(values))
+            }
+            ColumnBuilder::Int64(values) => Series::new(name, std::mem::take(values)),
+            ColumnBuilder::Float32(values) => Series::new(name, std::mem::take(values)),
+            ColumnBuilder::Float64(values) => Series::new(name, std::mem::take(values)),
+            ColumnBuilder::String(values) | ColumnBuilder::Uuid(values) => {
+                Series::new(name, std::mem::take(values))
+            }
+            ColumnBuilder::Date(values) => Series::new(name, std::mem::take(values)),
+            ColumnBuilder::Timestamp(values) | ColumnBuilder::Timestamptz(values) => {
+                DatetimeChunked::from_naive_datetime_options(
+                    name,
+                    std::mem::take(values),
+                    TimeUnit::Microseconds,
+                )
+                .into_series()
+            }
+        };
+        series.into_column()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pg_type_to_dtype() {
+        let cases = vec![
+            ("INT2", Some(DataType::Int32)),
+            ("INT4", Some(DataType::Int32)),
+            ("INT8", Some(DataType::Int64)),
+            ("FLOAT8", Some(DataType::Float64)),
+            ("TEXT", Some(DataType::String)),
+            ("VARCHAR", Some(DataType::String)),
+            ("BOOL", Some(DataType::Boolean)),
+            ("DATE", Some(DataType::Date)),
+            (
+                "TIMESTAMPTZ",
+                Some(DataType::Datetime(TimeUnit::Microseconds, None)),
+            ),
+            ("NUMERIC", None),
+        ];
+
+        for (pg_type, expected) in cases {
+            assert_eq!(pg_type_to_dtype(pg_type), expected, "{}", pg_type);
+        }
+    }
+
+    #[test]
+    fn test_builder_finish_empties_the_builder() {
+        let mut builder = ColumnBuilder::new("FLOAT8").expect("Unsupported type");
+        if let ColumnBuilder::Float64(values) = &mut builder {
+            values.extend([Some(606060606.0), None]);
+        }
+
+        let column = builder.finish("telephone");
+        let empty = builder.finish("telephone");
+
+        assert_eq!(column.dtype(), &DataType::Float64);
+        assert_eq!(column.len(), 2);
+        assert_eq!(column.null_count(), 1);
+        assert_eq!(empty.len(), 0);
+    }
+
+    #[test]
+    fn test_cursor_sql() {
+        assert_eq!(
+            declare_cursor_sql(r#"SELECT * FROM "hdd""#),
+            r#"DECLARE "lib_etl_source_cursor" NO SCROLL CURSOR FOR SELECT * FROM "hdd""#
+        );
+        assert_eq!(
+            fetch_sql(500),
+            r#"FETCH FORWARD 500 FROM "lib_etl_source_cursor""#
+        );
+    }
+}
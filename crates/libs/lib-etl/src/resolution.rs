@@ -0,0 +1,335 @@
+//! Entity resolution between datasets, e.g. the HDD contacts and the JDD companies they work for.
+//!
+//! Each [`LinkMethod`] finds the companies of a contact, and the link table keeps, for each pair
+//! of records, the method with the highest confidence.
+
+use crate::dedup::similarity::{jaro_winkler, similarity_expr};
+use crate::transforms::raison_sociale::raison_sociale_key;
+use crate::transforms::utils::transform_string_series;
+use polars::prelude::*;
+use serde::Deserialize;
+
+/// Column of the link table holding the id of the contact.
+pub const CONTACT_ID: &str = "contact_id";
+/// Column of the link table holding the id of the company.
+pub const COMPANY_ID: &str = "company_id";
+/// Column of the link table holding the method of the link, see [`LinkMethod::name`].
+pub const METHOD: &str = "method";
+/// Column of the link table holding the confidence of the link, from 0 to 1.
+pub const CONFIDENCE: &str = "confidence";
+
+const SIREN_LENGTH: u64 = 9;
+const KEY: &str = "_key";
+const NAME_KEY: &str = "_name_key";
+const COMPANY_NAME_KEY: &str = "_company_name_key";
+
+/// How a contact is linked to a company.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkMethod {
+    /// Same SIRET, the contact works in the establishment. Confidence 1.
+    Siret,
+    /// The SIRET of the contact starts with the SIREN of the company, the contact works
+    /// in another establishment of the company. Confidence 0.9.
+    SirenPrefix,
+    /// Same postal code and raison sociale similar by Jaro-Winkler, compared by their
+    /// canonical key, without legal form nor stop words.
+    /// Confidence 0.8 times the similarity, at least `threshold`.
+    NameAndPostcode { threshold: f64 },
+}
+
+impl LinkMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkMethod::Siret => "siret",
+            LinkMethod::SirenPrefix => "siren_prefix",
+            LinkMethod::NameAndPostcode { .. } => "name_and_postcode",
+        }
+    }
+}
+
+/// Columns of a dataset used by the link methods.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkColumns {
+    pub id: String,
+    pub siret: Option<String>,
+    pub siren: Option<String>,
+    pub raison_sociale: Option<String>,
+    pub postcode: Option<String>,
+}
+
+impl LinkColumns {
+    pub fn new(id: &str) -> Self {
+        LinkColumns {
+            id: id.to_string(),
+            siret: None,
+            siren: None,
+            raison_sociale: None,
+            postcode: None,
+        }
+    }
+
+    pub fn with_siret(mut self, siret: &str) -> Self {
+        self.siret = Some(siret.to_string());
+        self
+    }
+
+    pub fn with_siren(mut self, siren: &str) -> Self {
+        self.siren = Some(siren.to_string());
+        self
+    }
+
+    pub fn with_raison_sociale(mut self, raison_sociale: &str) -> Self {
+        self.raison_sociale = Some(raison_sociale.to_string());
+        self
+    }
+
+    pub fn with_postcode(mut self, postcode: &str) -> Self {
+        self.postcode = Some(postcode.to_string());
+        self
+    }
+}
+
+fn required<'a>(
+    column: &'a Option<String>,
+    name: &str,
+    method: &LinkMethod,
+) -> PolarsResult<&'a str> {
+    column.as_deref().ok_or_else(|| {
+        PolarsError::ComputeError(
+            format!("Link method {} needs the {} column", method.name(), name).into(),
+        )
+    })
+}
+
+fn name_key_expr(column: &str) -> Expr {
+    col(column).map(
+        |series| transform_string_series(&series, |opt_text| opt_text.and_then(raison_sociale_key)),
+        GetOutput::from_type(DataType::String),
+    )
+}
+
+/// Links the records of a contacts dataset to the records of a companies dataset.
+pub struct EntityLinker {
+    contacts: LinkColumns,
+    companies: LinkColumns,
+    methods: Vec<LinkMethod>,
+}
+
+impl EntityLinker {
+    pub fn new(contacts: LinkColumns, companies: LinkColumns) -> Self {
+        EntityLinker {
+            contacts,
+            companies,
+            methods: Vec::new(),
+        }
+    }
+
+    pub fn with_method(mut self, method: LinkMethod) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// `(contact_id, company_id, method, confidence)` of every contact and company linked
+    /// by a method, each pair once with its most confident method.
+    pub fn links(
+        &self,
+        lf_contacts: LazyFrame,
+        lf_companies: LazyFrame,
+    ) -> PolarsResult<LazyFrame> {
+        if self.methods.is_empty() {
+            return Err(PolarsError::ComputeError(
+                "EntityLinker needs at least one link method".into(),
+            ));
+        }
+        let lfs_links = self
+            .methods
+            .iter()
+            .map(|method| self.method_links(method, lf_contacts.clone(), lf_companies.clone()))
+            .collect::<PolarsResult<Vec<_>>>()?;
+
+        Ok(concat(lfs_links, UnionArgs::default())?
+            .sort(
+                [CONFIDENCE],
+                SortMultipleOptions::default().with_order_descending(true),
+            )
+            .group_by_stable([col(CONTACT_ID), col(COMPANY_ID)])
+            .agg([col(METHOD).first(), col(CONFIDENCE).first()]))
+    }
+
+    fn method_links(
+        &self,
+        method: &LinkMethod,
+        lf_contacts: LazyFrame,
+        lf_companies: LazyFrame,
+    ) -> PolarsResult<LazyFrame> {
+        let contact_id = col(self.contacts.id.as_str()).alias(CONTACT_ID);
+        let company_id = col(self.companies.id.as_str()).alias(COMPANY_ID);
+        // The records are joined on their key
+        let (lf_contacts, lf_companies, confidence) = match method {
+            LinkMethod::Siret => {
+                let contact_siret = required(&self.contacts.siret, "contacts SIRET", method)?;
+                let company_siret = required(&self.companies.siret, "companies SIRET", method)?;
+                (
+                    lf_contacts.select([contact_id, col(contact_siret).alias(KEY)]),
+                    lf_companies.select([company_id, col(company_siret).alias(KEY)]),
+                    lit(1.0),
+                )
+            }
+            LinkMethod::SirenPrefix => {
+                let contact_siret = required(&self.contacts.siret, "contacts SIRET", method)?;
+                let company_siren = required(&self.companies.siren, "companies SIREN", method)?;
+                (
+                    lf_contacts.select([
+                        contact_id,
+                        col(contact_siret)
+                            .str()
+                            .slice(lit(0), lit(SIREN_LENGTH))
+                            .alias(KEY),
+                    ]),
+                    lf_companies.select([company_id, col(company_siren).alias(KEY)]),
+                    lit(0.9),
+                )
+            }
+            LinkMethod::NameAndPostcode { threshold } => {
+                let contact_name = required(
+                    &self.contacts.raison_sociale,
+                    "contacts raison sociale",
+                    method,
+                )?;
+                let contact_postcode =
+                    required(&self.contacts.postcode, "contacts postal code", method)?;
+                let company_name = required(
+                    &self.companies.raison_sociale,
+                    "companies raison sociale",
+                    method,
+                )?;
+                let company_postcode =
+                    required(&self.companies.postcode, "companies postal code", method)?;
+                let similarity =
+                    similarity_expr(col(NAME_KEY), col(COMPANY_NAME_KEY), jaro_winkler);
+                (
+                    lf_contacts.select([
+                        contact_id,
+                        col(contact_postcode).cast(DataType::String).alias(KEY),
+                        name_key_expr(contact_name).alias(NAME_KEY),
+                    ]),
+                    lf_companies.select([
+                        company_id,
+                        col(company_postcode).cast(DataType::String).alias(KEY),
+                        name_key_expr(company_name).alias(COMPANY_NAME_KEY),
+                    ]),
+                    when(similarity.clone().gt_eq(lit(*threshold)))
+                        .then(similarity * lit(0.8))
+                        .otherwise(lit(NULL).cast(DataType::Float64)),
+                )
+            }
+        };
+
+        Ok(lf_contacts
+            .filter(col(KEY).is_not_null())
+            .join(
+                lf_companies.filter(col(KEY).is_not_null()),
+                [col(KEY)],
+                [col(KEY)],
+                JoinArgs::new(JoinType::Inner),
+            )
+            .select([
+                col(CONTACT_ID),
+                col(COMPANY_ID),
+                lit(method.name()).alias(METHOD),
+                confidence.cast(DataType::Float64).alias(CONFIDENCE),
+            ])
+            .filter(col(CONFIDENCE).is_not_null()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::df;
+
+    fn df_contacts() -> DataFrame {
+        df![
+            "ID" => &["c1", "c2", "c3", "c4"],
+            "SIRET" => &[Some("44316952400120"), Some("44316952400999"), None, None],
+            "Raison_sociale" => &[None, None, Some("Boulangerie Dupont SARL"), Some("GARAGE MARTIN")],
+            "CP" => &[None, None, Some("75011"), Some("75011")],
+        ]
+        .unwrap()
+    }
+
+    fn df_companies() -> DataFrame {
+        df![
+            "ID" => &["j1", "j2"],
+            "SIRET" => &["44316952400120", "55208131700015"],
+            "SIREN" => &["443169524", "552081317"],
+            "RAISON_SOCIALE" => &["DUPONT", "BOULANGERIE DUPONT"],
+            "CODE POSTALE" => &["69001", "75011"],
+        ]
+        .unwrap()
+    }
+
+    fn linker() -> EntityLinker {
+        EntityLinker::new(
+            LinkColumns::new("ID")
+                .with_siret("SIRET")
+                .with_raison_sociale("Raison_sociale")
+                .with_postcode("CP"),
+            LinkColumns::new("ID")
+                .with_siret("SIRET")
+                .with_siren("SIREN")
+                .with_raison_sociale("RAISON_SOCIALE")
+                .with_postcode("CODE POSTALE"),
+        )
+        .with_method(LinkMethod::Siret)
+        .with_method(LinkMethod::SirenPrefix)
+        .with_method(LinkMethod::NameAndPostcode { threshold: 0.9 })
+    }
+
+    #[test]
+    fn test_links() {
+        let df_links = linker()
+            .links(df_contacts().lazy(), df_companies().lazy())
+            .unwrap()
+            .sort([CONTACT_ID], SortMultipleOptions::default())
+            .collect()
+            .unwrap();
+
+        let values = |column: &str| {
+            df_links
+                .column(column)
+                .unwrap()
+                .str()
+                .unwrap()
+                .into_no_null_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        // c1 has the SIRET of j1, kept over its SIREN, c4 has no similar company
+        assert_eq!(values(CONTACT_ID), vec!["c1", "c2", "c3"]);
+        assert_eq!(values(COMPANY_ID), vec!["j1", "j1", "j2"]);
+        assert_eq!(
+            values(METHOD),
+            vec!["siret", "siren_prefix", "name_and_postcode"]
+        );
+        let confidences = df_links
+            .column(CONFIDENCE)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(confidences, vec![1.0, 0.9, 0.8]);
+    }
+
+    #[test]
+    fn test_links_missing_columns() {
+        let linker = EntityLinker::new(LinkColumns::new("ID"), LinkColumns::new("ID"))
+            .with_method(LinkMethod::Siret);
+
+        assert!(linker
+            .links(df_contacts().lazy(), df_companies().lazy())
+            .is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use core::error::Error;
+use core::fmt;
+use core::future::Future;
+use lib_core::{
+    ctx::Ctx,
+    model::{
+        etl_run::{EtlRunBmc, EtlRunForCreate, EtlRunStatus},
+        ModelManager,
+    },
+};
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Row counts of a pipeline run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunStats {
+    pub rows_read: u64,
+    pub rows_written: u64,
+    pub rows_rejected: u64,
+}
+
+/// Hex SHA-256 of the configuration a pipeline runs with, e.g. the content of `io-config.json`,
+/// so runs with different settings can be told apart.
+pub fn config_hash(config: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(config))
+}
+
+/// Error of a pipeline stopped at a [`RunRecorder::checkpoint`] because its run was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunCancelled {
+    pub run_id: i64,
+}
+
+impl fmt::Display for RunCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Run {} cancelled", self.run_id)
+    }
+}
+
+impl Error for RunCancelled {}
+
+/// Records a pipeline run in the `etl_run` table of lib-core.
+/// The clones share the cancellation of the run.
+#[derive(Clone)]
+pub struct RunRecorder {
+    ctx: Ctx,
+    mm: ModelManager,
+    run_id: i64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RunRecorder {
+    /// Creates the `Running` run, triggered by the user of `ctx`.
+    pub async fn start(
+        ctx: &Ctx,
+        mm: &ModelManager,
+        pipeline_name: &str,
+        config_hash: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let run_id = EtlRunBmc::create(
+            ctx,
+            mm,
+            EtlRunForCreate {
+                pipeline_name: pipeline_name.to_string(),
+                config_hash: config_hash.to_string(),
+            },
+        )
+        .await?;
+        info!("Run {} of {} started", run_id, pipeline_name);
+        Ok(RunRecorder {
+            ctx: ctx.clone(),
+            mm: mm.clone(),
+            run_id,
+            cancelled: Arc::default(),
+        })
+    }
+
+    pub fn run_id(&self) -> i64 {
+        self.run_id
+    }
+
+    /// Asks the pipeline to stop at its next [`RunRecorder::checkpoint`].
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Called by the pipelines between their stages, fails with [`RunCancelled`]
+    /// once the run is cancelled.
+    pub fn checkpoint(&self) -> Result<(), RunCancelled> {
+        if self.is_cancelled() {
+            return Err(RunCancelled {
+                run_id: self.run_id,
+            });
+        }
+        Ok(())
+    }
+
+    /// Records the row counts so far.
+    pub async fn progress(&self, stats: RunStats) -> Result<(), Box<dyn Error>> {
+        EtlRunBmc::update_counts(
+            &self.ctx,
+            &self.mm,
+            self.run_id,
+            stats.rows_read as i64,
+            stats.rows_written as i64,
+            stats.rows_rejected as i64,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Marks the run `Succeeded`, unless it was finished meanwhile, e.g. cancelled.
+    pub async fn succeed(&self, stats: RunStats) -> Result<(), Box<dyn Error>> {
+        self.progress(stats).await?;
+        let finished = EtlRunBmc::finish(
+            &self.ctx,
+            &self.mm,
+            self.run_id,
+            EtlRunStatus::Succeeded,
+            None,
+        )
+        .await?;
+        if !finished {
+            warn!("Run {} was already finished, kept as it is", self.run_id);
+            return Ok(());
+        }
+        info!(
+            "Run {} succeeded: {} rows read, {} written, {} rejected",
+            self.run_id, stats.rows_read, stats.rows_written, stats.rows_rejected
+        );
+        Ok(())
+    }
+
+    /// Marks the run `Failed`, unless it was finished meanwhile, e.g. cancelled.
+    pub async fn fail(&self, error_msg: &str) -> Result<(), Box<dyn Error>> {
+        let finished = EtlRunBmc::finish(
+            &self.ctx,
+            &self.mm,
+            self.run_id,
+            EtlRunStatus::Failed,
+            Some(error_msg.to_string()),
+        )
+        .await?;
+        if !finished {
+            warn!(
+                "Run {} was already finished, kept as it is: {}",
+                self.run_id, error_msg
+            );
+            return Ok(());
+        }
+        error!("Run {} failed: {}", self.run_id, error_msg);
+        Ok(())
+    }
+}
+
+/// Runs `pipeline` as a recorded run: the run is created before it starts, and marked
+/// `Succeeded` with the returned counts, or `Failed` with the error message.
+/// The pipeline gets the recorder to report its progress.
+pub async fn record_run<F, Fut>(
+    ctx: &Ctx,
+    mm: &ModelManager,
+    pipeline_name: &str,
+    config_hash: &str,
+    pipeline: F,
+) -> Result<RunStats, Box<dyn Error>>
+where
+    F: FnOnce(RunRecorder) -> Fut,
+    Fut: Future<Output = Result<RunStats, Box<dyn Error>>>,
+{
+    let recorder = RunRecorder::start(ctx, mm, pipeline_name, config_hash).await?;
+    // The error is kept as a message so the future stays `Send` while the failure is recorded
+    match pipeline(recorder.clone()).await.map_err(|e| e.to_string()) {
+        Ok(stats) => {
+            recorder.succeed(stats).await?;
+            Ok(stats)
+        }
+        Err(error_msg) => {
+            if let Err(record_error) = recorder.fail(&error_msg).await {
+                warn!(
+                    "Failed to record the failure of run {}: {}",
+                    recorder.run_id(),
+                    record_error
+                );
+            }
+            Err(error_msg.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_hash() {
+        assert_eq!(
+            config_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(config_hash(b"{\"a\": 1}"), config_hash(b"{\"a\": 2}"));
+    }
+}
@@ -1,11 +1,85 @@
+use self::{hdd::Hdd, jdd::Jdd};
+use polars::prelude::{DataType, Schema};
+use sea_query::Iden;
+use serde::{Deserialize, Serialize};
+
 pub mod hdd;
 pub mod jdd;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SchemasEnum {
     Jdd,
     Hdd,
 }
 
+impl SchemasEnum {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SchemasEnum::Jdd => Jdd::Table.as_str(),
+            SchemasEnum::Hdd => Hdd::Table.as_str(),
+        }
+    }
+
+    /// Fields of one record of the dataset, in the column order of the source CSV.
+    pub fn fields(&self) -> Vec<FieldDef> {
+        match self {
+            SchemasEnum::Jdd => jdd::jdd_fields(),
+            SchemasEnum::Hdd => hdd::hdd_fields(),
+        }
+    }
+
+    pub fn polars_schema(&self) -> Schema {
+        self.fields()
+            .iter()
+            .map(|field| (field.name.into(), field.field_type.polars_dtype()))
+            .collect()
+    }
+}
+
 pub trait AsString {
     fn as_str(&self) -> &'static str;
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    Int32,
+    Float64,
+}
+
+impl FieldType {
+    pub fn polars_dtype(&self) -> DataType {
+        match self {
+            FieldType::String => DataType::String,
+            FieldType::Int32 => DataType::Int32,
+            FieldType::Float64 => DataType::Float64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDef {
+    /// Column name in the source files and DataFrames.
+    pub name: &'static str,
+    /// Column name in the Postgres table.
+    pub sql_name: String,
+    pub field_type: FieldType,
+    pub nullable: bool,
+}
+
+impl FieldDef {
+    pub fn new<C: AsString + Iden>(column: C, field_type: FieldType) -> Self {
+        FieldDef {
+            name: column.as_str(),
+            sql_name: column.to_string(),
+            field_type,
+            nullable: true,
+        }
+    }
+
+    pub fn not_null(mut self) -> Self {
+        self.nullable = false;
+        self
+    }
+}
@@ -1,4 +1,4 @@
-use super::AsString;
+use super::{AsString, FieldDef, FieldType};
 use sea_query::Iden;
 
 #[derive(Iden)]
@@ -15,6 +15,10 @@ pub enum Hdd {
     Telephone,
     Email,
     Ids,
+    Provenance,
+    SiretActif,
+    FormeJuridique,
+    RaisonSocialeCle,
 }
 
 impl AsString for Hdd {
@@ -32,16 +36,39 @@ impl AsString for Hdd {
             Hdd::Telephone => "Telephone",
             Hdd::Email => "Email",
             Hdd::Ids => "IDS",
+            Hdd::Provenance => "Provenance",
+            Hdd::SiretActif => "SIRET_ACTIF",
+            Hdd::FormeJuridique => "FORME_JURIDIQUE",
+            Hdd::RaisonSocialeCle => "RAISON_SOCIALE_CLE",
         }
     }
 }
+
+/// Columns holding identifiers, kept as text so leading zeros and long digit strings survive.
+pub fn hdd_identifiers() -> [Hdd; 4] {
+    [Hdd::Pce, Hdd::Siret, Hdd::SiretSuccesseur, Hdd::Telephone]
+}
+
+pub fn hdd_fields() -> Vec<FieldDef> {
+    vec![
+        FieldDef::new(Hdd::IdSource, FieldType::Int32),
+        FieldDef::new(Hdd::Pce, FieldType::String),
+        FieldDef::new(Hdd::Siret, FieldType::String),
+        FieldDef::new(Hdd::SiretSuccesseur, FieldType::String),
+        FieldDef::new(Hdd::RaisonSociale, FieldType::String),
+        FieldDef::new(Hdd::Telephone, FieldType::String),
+        FieldDef::new(Hdd::Email, FieldType::String),
+        FieldDef::new(Hdd::Nom, FieldType::String),
+        FieldDef::new(Hdd::Prenom, FieldType::String),
+    ]
+}
 #[derive(Debug, serde::Deserialize, serde::Serialize, sqlx::FromRow)]
 pub struct HddSchema {
     #[serde(rename = "Raison_sociale")]
     pub raison_sociale: Option<String>,
 
     #[serde(rename = "SIRET")]
-    pub siret: Option<f64>,
+    pub siret: Option<String>,
 
     #[serde(rename = "Nom")]
     pub nom: Option<String>,
@@ -50,19 +77,19 @@ pub struct HddSchema {
     pub prenom: Option<String>,
 
     #[serde(rename = "Telephone")]
-    pub telephone: Option<f64>,
+    pub telephone: Option<String>,
 
     #[serde(rename = "Email")]
     pub email: Option<String>,
 
     #[serde(rename = "SIRET successeur")]
-    pub siret_successeur: Option<f64>,
+    pub siret_successeur: Option<String>,
 
     #[serde(rename = "Id_source")]
     pub id_source: Option<i32>,
 
     #[serde(rename = "PCE")]
-    pub pce: Option<f64>,
+    pub pce: Option<String>,
 
     #[serde(rename = "ID")]
     pub id: i32,
@@ -74,7 +101,7 @@ pub struct HddSchemaCSV {
     pub raison_sociale: Option<String>,
 
     #[serde(rename = "SIRET")]
-    pub siret: Option<f64>,
+    pub siret: Option<String>,
 
     #[serde(rename = "Nom")]
     pub nom: Option<String>,
@@ -83,19 +110,19 @@ pub struct HddSchemaCSV {
     pub prenom: Option<String>,
 
     #[serde(rename = "Telephone")]
-    pub telephone: Option<f64>,
+    pub telephone: Option<String>,
 
     #[serde(rename = "Email")]
     pub email: Option<String>,
 
     #[serde(rename = "SIRET successeur")]
-    pub siret_successeur: Option<f64>,
+    pub siret_successeur: Option<String>,
 
     #[serde(rename = "Id_source")]
     pub id_source: Option<i32>,
 
     #[serde(rename = "PCE")]
-    pub pce: Option<f64>,
+    pub pce: Option<String>,
     // #[serde(rename = "ID")]
     // pub id: i32,
 }
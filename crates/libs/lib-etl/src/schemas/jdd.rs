@@ -1,4 +1,4 @@
-use super::AsString;
+use super::{AsString, FieldDef, FieldType};
 use sea_query::Iden;
 
 #[derive(Iden)]
@@ -20,6 +20,8 @@ pub enum Jdd {
     CodePostale,
     Region,
     Pays,
+    FormeJuridique,
+    RaisonSocialeCle,
 }
 
 impl AsString for Jdd {
@@ -42,6 +44,8 @@ impl AsString for Jdd {
             Jdd::CodePostale => "CODE POSTALE",
             Jdd::Region => "REGION",
             Jdd::Pays => "PAYS",
+            Jdd::FormeJuridique => "FORME_JURIDIQUE",
+            Jdd::RaisonSocialeCle => "RAISON_SOCIALE_CLE",
         }
     }
 }
@@ -93,3 +97,26 @@ pub struct JddSchema {
     #[serde(rename = "PAYS")]
     pub pays: Option<String>,
 }
+
+pub fn jdd_fields() -> Vec<FieldDef> {
+    [
+        Jdd::RaisonSociale,
+        Jdd::Siret,
+        Jdd::Siren,
+        Jdd::Ape,
+        Jdd::CodeNaf,
+        Jdd::LibeleNaf,
+        Jdd::Civilite,
+        Jdd::Nom,
+        Jdd::Prenom,
+        Jdd::Telephone,
+        Jdd::Email,
+        Jdd::Address,
+        Jdd::CodePostale,
+        Jdd::Region,
+        Jdd::Pays,
+    ]
+    .into_iter()
+    .map(|column| FieldDef::new(column, FieldType::String))
+    .collect()
+}
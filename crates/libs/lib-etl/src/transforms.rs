@@ -15,9 +15,11 @@ pub mod ape;
 pub mod civilite;
 pub mod code_naf;
 pub mod email;
+pub mod identifier;
 pub mod libelle_naf;
 pub mod nom;
 pub mod pce;
+pub mod phonetic;
 pub mod prenom;
 pub mod raison_sociale;
 pub mod siren;
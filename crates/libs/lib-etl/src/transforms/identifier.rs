@@ -0,0 +1,74 @@
+use crate::schemas::{hdd::hdd_identifiers, AsString, SchemasEnum};
+use polars::{
+    lazy::dsl::{col, lit, Expr},
+    prelude::NULL,
+};
+
+/// Trims the identifier and removes the `.0` left by a float formatter (e.g. `12345678910124.0`).
+fn transform_col_identifier_expr(col_identifier: &str) -> Expr {
+    col(col_identifier)
+        .str()
+        .strip_chars(lit(NULL))
+        .str()
+        .replace(lit(r"^(\d+)\.0+$"), lit("${1}"), false)
+        .alias(col_identifier)
+}
+
+/// Expressions turning the identifier columns of the dataset into clean text.
+pub fn col_identifiers_as_text_exprs(se: SchemasEnum) -> Vec<Expr> {
+    match se {
+        SchemasEnum::Hdd => hdd_identifiers()
+            .iter()
+            .map(|column| transform_col_identifier_expr(column.as_str()))
+            .collect(),
+        SchemasEnum::Jdd => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schemas::hdd::Hdd;
+    use polars::{datatypes::AnyValue, df, lazy::frame::IntoLazy};
+
+    #[test]
+    fn test_col_identifiers_as_text_exprs() {
+        let df = df![
+            Hdd::Pce.as_str() => &[Some("12345678910124.0"), Some("01234567891012"), None],
+            Hdd::Siret.as_str() => &[Some(" 44316952400120 "), None, Some("44316952400120.00")],
+            Hdd::SiretSuccesseur.as_str() => &[None::<&str>, None, None],
+            Hdd::Telephone.as_str() => &[Some("606060606.0"), Some("0606060606"), Some("06.06.06.06.00")],
+        ]
+        .expect("DataFrame creation failed");
+
+        let result_df = df
+            .lazy()
+            .select(col_identifiers_as_text_exprs(SchemasEnum::Hdd))
+            .collect()
+            .expect("DataFrame collection failed");
+
+        let test_cases = vec![
+            (Hdd::Pce, 0, AnyValue::String("12345678910124")),
+            (Hdd::Pce, 1, AnyValue::String("01234567891012")),
+            (Hdd::Pce, 2, AnyValue::Null),
+            (Hdd::Siret, 0, AnyValue::String("44316952400120")),
+            (Hdd::Siret, 2, AnyValue::String("44316952400120")),
+            (Hdd::Telephone, 0, AnyValue::String("606060606")),
+            (Hdd::Telephone, 1, AnyValue::String("0606060606")),
+            (Hdd::Telephone, 2, AnyValue::String("06.06.06.06.00")),
+        ];
+        for (column, row, expected) in test_cases {
+            assert_eq!(
+                result_df
+                    .column(column.as_str())
+                    .expect("Result column not found")
+                    .get(row)
+                    .expect("Row not found"),
+                expected,
+                "{} at row {}",
+                column.as_str(),
+                row
+            );
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::schemas::hdd::Hdd;
+use crate::schemas::jdd::Jdd;
+use crate::schemas::{AsString, SchemasEnum};
+use lazy_static::lazy_static;
+use polars::datatypes::DataType;
+use polars::lazy::dsl::{col, Expr, GetOutput};
+use polars::prelude::Column;
+use regex::Regex;
+
+use super::utils::{strip_accent, transform_string_series};
+
+/// Phonetic encoders tuned for French names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhoneticEncoder {
+    /// See [`phonex`].
+    Phonex,
+    /// See [`soundex_fr`].
+    SoundexFr,
+}
+
+impl PhoneticEncoder {
+    pub fn encode(&self, text: &str) -> String {
+        match self {
+            PhoneticEncoder::Phonex => phonex(text),
+            PhoneticEncoder::SoundexFr => soundex_fr(text),
+        }
+    }
+}
+
+/// Name of the column holding the phonetic key of a column, e.g. `Nom_phonetic`.
+pub fn phonetic_column(column: &str) -> String {
+    format!("{}_phonetic", column)
+}
+
+/// Uppercased letters of the text, without accents, spaces or punctuation.
+fn letters(text: &str) -> String {
+    strip_accent(text)
+        .to_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_uppercase())
+        .collect()
+}
+
+fn dedup_letters(text: &str) -> String {
+    let mut chars = text.chars().collect::<Vec<_>>();
+    chars.dedup();
+    chars.into_iter().collect()
+}
+
+lazy_static! {
+    // Replacements of the Phonex algorithm of Frédéric Brouard, in order.
+    // The digits stand for the sounds AN (1), OI (2), OU (3), IN (4) and CH (5).
+    static ref PHONEX_RULES: Vec<(Regex, &'static str)> = [
+        ("Y", "I"),
+        ("^H|([^CSP])H", "$1"),
+        ("PH", "F"),
+        ("G(AI?[NM])", "K$1"),
+        ("[AE]I[NM]([AEIOU])", "YN$1"),
+        ("EAU", "O"),
+        ("OUA", "2"),
+        ("[AE]I[NM]", "4"),
+        ("AI|EI", "Y"),
+        ("ESS", "YS"),
+        ("E([RTZ])", "Y$1"),
+        ("[AE][NM]([^AEIOU1234]|$)", "1$1"),
+        ("IN([^AEIOU1234]|$)", "4$1"),
+        // Twice, as a vowel ends one match and starts the next one
+        ("([AEIOUY1234])S([AEIOUY1234])", "${1}Z$2"),
+        ("([AEIOUY1234])S([AEIOUY1234])", "${1}Z$2"),
+        ("OE|EU", "E"),
+        ("AU", "O"),
+        ("OI|OY", "2"),
+        ("OU", "3"),
+        ("S?CH|SH", "5"),
+        ("SS|SC", "S"),
+        ("C([EI])", "S$1"),
+        ("QU|GU|C|Q", "K"),
+        ("G([AOY])", "K$1"),
+        ("A", "O"),
+        ("[DP]", "T"),
+        ("J", "G"),
+        ("[BV]", "F"),
+        ("M", "N"),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect();
+
+    // Replacements of the French Soundex (Soundex2), in order.
+    static ref SOUNDEX_FR_PRIMARY_RULES: Vec<(Regex, &'static str)> = [
+        ("GUI", "KI"),
+        ("GUE", "KE"),
+        ("GA", "KA"),
+        ("GO", "KO"),
+        ("GU", "K"),
+        ("CA", "KA"),
+        ("CO", "KO"),
+        ("CU", "KU"),
+        ("Q", "K"),
+        ("CC|CK", "K"),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect();
+    static ref SOUNDEX_FR_PREFIX_RULES: Vec<(Regex, &'static str)> = [
+        ("^MAC", "MCC"),
+        ("^ASA", "AZA"),
+        ("^KN", "NN"),
+        ("^PF", "FF"),
+        ("^SCH", "SSS"),
+        ("^PH", "FF"),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect();
+    static ref SOUNDEX_FR_FINAL_RULES: Vec<(Regex, &'static str)> = [
+        // H is silent, except in CH and SH
+        ("^H|([^CS])H", "$1"),
+        // Y is silent, except in AY
+        ("^Y|([^A])Y", "$1"),
+        ("[ADTS]$", ""),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect();
+}
+
+fn apply_rules(text: String, rules: &[(Regex, &str)]) -> String {
+    rules.iter().fold(text, |text, (re, replacement)| {
+        re.replace_all(&text, *replacement).to_string()
+    })
+}
+
+/// Phonex code of a French name, e.g. "DUPONT" and "DUPOND" are both "TUTON".
+///
+/// This is the letter code of the algorithm, before its conversion to a number,
+/// so the codes stay readable.
+pub fn phonex(text: &str) -> String {
+    let code = apply_rules(letters(text), &PHONEX_RULES);
+    let code = dedup_letters(&code);
+    code.trim_end_matches(['T', 'X']).to_string()
+}
+
+/// French Soundex (Soundex2) code of a name, at most 4 letters,
+/// e.g. "MARTIN" and "MARTAIN" are both "MRTN".
+pub fn soundex_fr(text: &str) -> String {
+    let text = apply_rules(letters(text), &SOUNDEX_FR_PRIMARY_RULES);
+    let mut chars = text.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    // The vowels after the first letter are all A
+    let text = std::iter::once(first)
+        .chain(chars.map(|c| if "EIOU".contains(c) { 'A' } else { c }))
+        .collect::<String>();
+    let text = apply_rules(text, &SOUNDEX_FR_PREFIX_RULES);
+    let text = apply_rules(text, &SOUNDEX_FR_FINAL_RULES);
+
+    let mut chars = text.chars();
+    let code = match chars.next() {
+        Some(first) => std::iter::once(first)
+            .chain(chars.filter(|c| *c != 'A'))
+            .collect::<String>(),
+        None => String::new(),
+    };
+    dedup_letters(&code).chars().take(4).collect()
+}
+
+fn transform_phonetic(opt_text: Option<&str>, encoder: PhoneticEncoder) -> Option<String> {
+    opt_text
+        .map(|text| encoder.encode(text))
+        .filter(|code| !code.is_empty())
+}
+
+/// Phonetic key of `column`, named by [`phonetic_column`], to be used as a dedup blocking key.
+pub fn col_phonetic_expr(column: &str, encoder: PhoneticEncoder) -> Expr {
+    col(column)
+        .map(
+            move |series: Column| {
+                transform_string_series(&series, move |opt_text| {
+                    transform_phonetic(opt_text, encoder)
+                })
+            },
+            GetOutput::from_type(DataType::String),
+        )
+        .alias(phonetic_column(column))
+}
+
+pub fn col_nom_phonetic_with_polars_expr(se: SchemasEnum) -> Expr {
+    match se {
+        SchemasEnum::Jdd => col_phonetic_expr(Jdd::Nom.as_str(), PhoneticEncoder::Phonex),
+        SchemasEnum::Hdd => col_phonetic_expr(Hdd::Nom.as_str(), PhoneticEncoder::Phonex),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::{df, lazy::frame::IntoLazy};
+
+    #[test]
+    fn test_phonex() {
+        let test_cases = vec![
+            ("Dupont", "TUTON"),
+            ("Dupond", "TUTON"),
+            ("Sallandier", "SOL1TIYR"),
+            ("Salandier", "SOL1TIYR"),
+            ("Martin", "NORT4"),
+            ("Martain", "NORT4"),
+            ("Faure", "FORE"),
+            ("Fore", "FORE"),
+            ("", ""),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(phonex(input), expected, "Failed on input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_soundex_fr() {
+        let test_cases = vec![
+            ("Dupont", "DPN"),
+            ("Dupond", "DPN"),
+            ("Martin", "MRTN"),
+            ("Martain", "MRTN"),
+            ("Gauthier", "KTR"),
+            ("Gautier", "KTR"),
+            ("", ""),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(soundex_fr(input), expected, "Failed on input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_col_nom_phonetic_with_polars_expr() {
+        let df = df![
+            Hdd::Nom.as_str() => &[Some("DUPONT"), Some("DUPOND"), Some("--"), None],
+        ]
+        .expect("DataFrame creation failed");
+
+        let result_df = df
+            .lazy()
+            .select(&[col_nom_phonetic_with_polars_expr(SchemasEnum::Hdd)])
+            .collect()
+            .expect("DataFrame collection failed");
+
+        let result = result_df
+            .column(&phonetic_column(Hdd::Nom.as_str()))
+            .expect("Result column not found")
+            .str()
+            .expect("Result column is not a string")
+            .into_iter()
+            .map(|code| code.map(String::from))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                Some("TUTON".to_string()),
+                Some("TUTON".to_string()),
+                None,
+                None
+            ]
+        );
+    }
+}
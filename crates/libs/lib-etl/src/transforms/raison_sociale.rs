@@ -1,9 +1,11 @@
+use lazy_static::lazy_static;
 use polars::{
-    datatypes::StringChunked,
+    datatypes::{DataType, StringChunked},
     error::PolarsResult,
     lazy::dsl::{col, Expr, GetOutput},
     prelude::{Column, IntoColumn},
 };
+use regex::Regex;
 
 use crate::schemas::{hdd::Hdd, jdd::Jdd, AsString, SchemasEnum};
 
@@ -82,6 +84,194 @@ pub fn col_raison_sociale_with_polars_expr(se: SchemasEnum) -> Expr {
     }
 }
 
+lazy_static! {
+    // A letter followed by a dot, e.g. S.A.R.L.
+    static ref DOTTED_LETTER: Regex = Regex::new(r"\b(\w)\.").unwrap();
+}
+
+/// Common abbreviations, expanded before the legal form and the key are looked for.
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("STE", "SOCIETE"),
+    ("STES", "SOCIETES"),
+    ("ETS", "ETABLISSEMENTS"),
+    ("ETB", "ETABLISSEMENTS"),
+    ("CIE", "COMPAGNIE"),
+    ("ASSO", "ASSOCIATION"),
+];
+
+/// Legal forms, spelled out or as their acronym, the longest first.
+const LEGAL_FORMS: &[(&str, &str)] = &[
+    ("ENTREPRISE UNIPERSONNELLE A RESPONSABILITE LIMITEE", "EURL"),
+    ("SOCIETE A RESPONSABILITE LIMITEE UNIPERSONNELLE", "SARLU"),
+    ("SOCIETE A RESPONSABILITE LIMITEE", "SARL"),
+    ("SOCIETE PAR ACTIONS SIMPLIFIEE UNIPERSONNELLE", "SASU"),
+    ("SOCIETE PAR ACTIONS SIMPLIFIEE", "SAS"),
+    ("SOCIETE CIVILE IMMOBILIERE", "SCI"),
+    ("SOCIETE EN NOM COLLECTIF", "SNC"),
+    ("SOCIETE COOPERATIVE ET PARTICIPATIVE", "SCOP"),
+    ("GROUPEMENT D INTERET ECONOMIQUE", "GIE"),
+    ("SOCIETE ANONYME", "SA"),
+    ("ASSOCIATION", "ASSOCIATION"),
+    ("SARLU", "SARLU"),
+    ("SARL", "SARL"),
+    ("SELARL", "SELARL"),
+    ("EURL", "EURL"),
+    ("EIRL", "EIRL"),
+    ("SASU", "SASU"),
+    ("SAS", "SAS"),
+    ("SELAS", "SELAS"),
+    ("SCI", "SCI"),
+    ("SCM", "SCM"),
+    ("SCP", "SCP"),
+    ("SNC", "SNC"),
+    ("SCOP", "SCOP"),
+    ("GIE", "GIE"),
+    ("SA", "SA"),
+];
+
+/// Words left out of the key, they tell nothing about the company.
+const STOP_WORDS: &[&str] = &[
+    "SOCIETE",
+    "SOCIETES",
+    "ETABLISSEMENTS",
+    "COMPAGNIE",
+    "LE",
+    "LA",
+    "LES",
+    "L",
+    "DE",
+    "DU",
+    "DES",
+    "D",
+    "ET",
+    "AU",
+    "AUX",
+    "EN",
+];
+
+/// Uppercased words of a raison sociale, without accents nor punctuation,
+/// the abbreviations expanded.
+fn raison_sociale_words(text: &str) -> Vec<String> {
+    let text = strip_accent(text).to_uppercase();
+    let words = DOTTED_LETTER
+        .replace_all(&text, "$1")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .map(|word| {
+            ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| *abbreviation == word)
+                .map_or(word, |(_, expansion)| *expansion)
+                .to_string()
+        })
+        .collect();
+    words
+}
+
+/// Positions of the words of `phrase` in `words`.
+fn phrase_positions(words: &[String], phrase: &str) -> Vec<usize> {
+    let phrase = phrase.split(' ').collect::<Vec<_>>();
+    if words.len() < phrase.len() {
+        return Vec::new();
+    }
+    (0..=words.len() - phrase.len())
+        .filter(|start| {
+            words[*start..start + phrase.len()]
+                .iter()
+                .zip(&phrase)
+                .all(|(word, phrase_word)| word.as_str() == *phrase_word)
+        })
+        .collect()
+}
+
+/// Legal form of a company from its raison sociale, e.g. "SARL" for "DUPONT S.A.R.L."
+/// or "Dupont société à responsabilité limitée".
+pub fn legal_form(text: &str) -> Option<&'static str> {
+    let words = raison_sociale_words(text);
+    LEGAL_FORMS
+        .iter()
+        .find(|(phrase, _)| !phrase_positions(&words, phrase).is_empty())
+        .map(|(_, form)| *form)
+}
+
+/// Canonical key of a raison sociale to match companies, without the legal forms, the stop words,
+/// accents and punctuation, e.g. "DUPONT" for both "SOCIETE DUPONT SARL" and "Ets Dupont".
+/// A raison sociale made of these words only is kept whole.
+pub fn raison_sociale_key(text: &str) -> Option<String> {
+    let words = raison_sociale_words(text);
+    let mut kept = vec![true; words.len()];
+    for (phrase, _) in LEGAL_FORMS {
+        let length = phrase.split(' ').count();
+        for start in phrase_positions(&words, phrase) {
+            kept[start..start + length].fill(false);
+        }
+    }
+    let key = words
+        .iter()
+        .zip(&kept)
+        .filter(|(word, kept)| **kept && !STOP_WORDS.contains(&word.as_str()))
+        .map(|(word, _)| word.as_str())
+        .collect::<Vec<_>>();
+    let key = if key.is_empty() {
+        words.join(" ")
+    } else {
+        key.join(" ")
+    };
+    (!key.is_empty()).then_some(key)
+}
+
+fn transform_col_legal_form_expr(col_rs: &str, col_legal_form: &str) -> Expr {
+    col(col_rs)
+        .map(
+            |series| {
+                transform_string_series(&series, |opt_text| {
+                    opt_text.and_then(legal_form).map(String::from)
+                })
+            },
+            GetOutput::from_type(DataType::String),
+        )
+        .alias(col_legal_form)
+}
+
+fn transform_col_raison_sociale_key_expr(col_rs: &str, col_key: &str) -> Expr {
+    col(col_rs)
+        .map(
+            |series| {
+                transform_string_series(&series, |opt_text| opt_text.and_then(raison_sociale_key))
+            },
+            GetOutput::from_type(DataType::String),
+        )
+        .alias(col_key)
+}
+
+/// Legal form of the raison sociale, into its own column.
+pub fn col_forme_juridique_with_polars_expr(se: SchemasEnum) -> Expr {
+    match se {
+        SchemasEnum::Jdd => {
+            transform_col_legal_form_expr(Jdd::RaisonSociale.as_str(), Jdd::FormeJuridique.as_str())
+        }
+        SchemasEnum::Hdd => {
+            transform_col_legal_form_expr(Hdd::RaisonSociale.as_str(), Hdd::FormeJuridique.as_str())
+        }
+    }
+}
+
+/// Canonical key of the raison sociale, into its own column, see [`raison_sociale_key`].
+pub fn col_raison_sociale_key_with_polars_expr(se: SchemasEnum) -> Expr {
+    match se {
+        SchemasEnum::Jdd => transform_col_raison_sociale_key_expr(
+            Jdd::RaisonSociale.as_str(),
+            Jdd::RaisonSocialeCle.as_str(),
+        ),
+        SchemasEnum::Hdd => transform_col_raison_sociale_key_expr(
+            Hdd::RaisonSociale.as_str(),
+            Hdd::RaisonSocialeCle.as_str(),
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -121,6 +311,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_legal_form() {
+        let test_cases = vec![
+            ("SOCIETE DUPONT SARL", Some("SARL")),
+            ("DUPONT S.A.R.L.", Some("SARL")),
+            ("Dupont société à responsabilité limitée", Some("SARL")),
+            ("DUPONT SASU", Some("SASU")),
+            ("SAS DUPONT", Some("SAS")),
+            ("Martin EURL", Some("EURL")),
+            ("S.A. DUPONT", Some("SA")),
+            ("SCI LES TILLEULS", Some("SCI")),
+            ("Asso. des Amis du Vélo", Some("ASSOCIATION")),
+            ("SASSENAGE TRANSPORTS", None),
+            ("DUPONT", None),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(legal_form(input), expected, "Failed on input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_raison_sociale_key() {
+        let test_cases = vec![
+            ("SOCIETE DUPONT SARL", Some("DUPONT")),
+            ("DUPONT S.A.R.L.", Some("DUPONT")),
+            ("Sté Dupont", Some("DUPONT")),
+            ("Ets. Dupont & Cie", Some("DUPONT")),
+            ("Boulangerie  Dupont.", Some("BOULANGERIE DUPONT")),
+            ("Crêperie l'Étoile", Some("CREPERIE ETOILE")),
+            ("SCI LES TILLEULS", Some("TILLEULS")),
+            ("SOCIETE ANONYME", Some("SOCIETE ANONYME")),
+            ("--", None),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(
+                raison_sociale_key(input).as_deref(),
+                expected,
+                "Failed on input: {:?}",
+                input
+            );
+        }
+    }
+
     #[test]
     fn test_col_raison_sociale_with_polars_expr() {
         // Create a DataFrame with test data
@@ -1,5 +1,16 @@
 use crate::schemas::{hdd::Hdd, AsString, SchemasEnum};
-use polars::lazy::dsl::{col, lit, Expr};
+use log::warn;
+use polars::lazy::dsl::{col, lit, map_multiple, Expr, GetOutput};
+use polars::prelude::{
+    Column, CsvReadOptions, DataType, IntoColumn, PolarsResult, SerReader, StringChunked,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Column of the establishment transfers holding the SIRET transferred, as in the INSEE
+/// succession links (`StockEtablissementLiensSuccession`).
+pub const PREDECESSOR_COLUMN: &str = "siretEtablissementPredecesseur";
+/// Column of the establishment transfers holding the SIRET it was transferred to.
+pub const SUCCESSOR_COLUMN: &str = "siretEtablissementSuccesseur";
 
 fn transform_col_siret_ss_expr(col_pce: &str) -> Expr {
     col(col_pce)
@@ -15,3 +26,229 @@ pub fn col_siret_ss_with_polars_expr(se: SchemasEnum) -> Expr {
     }
     // Clean the column by removing special characters
 }
+
+/// Successor of each establishment, from the `SIRET successeur` of a dataset or from a
+/// reference file of establishment transfers, e.g. the INSEE succession links.
+/// Following the successors gives the SIRET an establishment was transferred to in the end.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SuccessorChains {
+    successors: HashMap<String, String>,
+}
+
+impl SuccessorChains {
+    pub fn new() -> Self {
+        SuccessorChains::default()
+    }
+
+    /// Adds the transfers from `predecessors` to `successors`, row by row.
+    /// A SIRET keeps its first successor, so the transfers added first take precedence.
+    pub fn with_transfers(
+        mut self,
+        predecessors: &Column,
+        successors: &Column,
+    ) -> PolarsResult<Self> {
+        let predecessors = predecessors.cast(&DataType::String)?;
+        let successors = successors.cast(&DataType::String)?;
+        for (predecessor, successor) in predecessors.str()?.into_iter().zip(successors.str()?) {
+            if let (Some(predecessor), Some(successor)) = (predecessor, successor) {
+                if !predecessor.is_empty() && !successor.is_empty() && predecessor != successor {
+                    self.successors
+                        .entry(predecessor.to_string())
+                        .or_insert_with(|| successor.to_string());
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Adds the transfers of a `,` separated reference file, with the
+    /// [`PREDECESSOR_COLUMN`] and [`SUCCESSOR_COLUMN`] columns.
+    pub fn with_reference_file(self, file_path: &str) -> PolarsResult<Self> {
+        let df = CsvReadOptions::default()
+            .with_has_header(true)
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(file_path.into()))?
+            .finish()?;
+        self.with_transfers(df.column(PREDECESSOR_COLUMN)?, df.column(SUCCESSOR_COLUMN)?)
+    }
+
+    /// Last SIRET of the chain of successors starting at `siret`, `siret` itself when it has
+    /// no successor. None when the chain runs into a cycle.
+    pub fn resolve(&self, siret: &str) -> Option<String> {
+        let mut current = siret;
+        let mut seen = HashSet::from([siret]);
+        while let Some(successor) = self.successors.get(current) {
+            if !seen.insert(successor.as_str()) {
+                return None;
+            }
+            current = successor;
+        }
+        Some(current.to_string())
+    }
+
+    /// Cycles of successors, each from its smallest SIRET, e.g. two establishments
+    /// declared as the successor of each other.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles: Vec<Vec<String>> = Vec::new();
+        for start in self.successors.keys() {
+            let mut path = vec![start.as_str()];
+            let mut current = start.as_str();
+            while let Some(successor) = self.successors.get(current) {
+                if let Some(position) = path.iter().position(|siret| *siret == successor.as_str()) {
+                    let mut cycle = path[position..]
+                        .iter()
+                        .map(|siret| siret.to_string())
+                        .collect::<Vec<_>>();
+                    let smallest = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap_or(0);
+                    cycle.rotate_left(smallest);
+                    if !cycles.contains(&cycle) {
+                        cycles.push(cycle);
+                    }
+                    break;
+                }
+                path.push(successor);
+                current = successor;
+            }
+        }
+        cycles.sort();
+        cycles
+    }
+
+    /// Active SIRET of each record: the end of the chain starting at its SIRET, or at its
+    /// successor when it has no SIRET. Null when the chain runs into a cycle.
+    pub fn col_active_siret_expr(&self, siret_column: &str, successor_column: &str) -> Expr {
+        for cycle in self.cycles() {
+            warn!("Cycle of SIRET successeur: {}", cycle.join(" -> "));
+        }
+        let chains = self.clone();
+        map_multiple(
+            move |columns: &mut [Column]| {
+                let sirets = columns[0].cast(&DataType::String)?;
+                let successors = columns[1].cast(&DataType::String)?;
+                let active = sirets
+                    .str()?
+                    .into_iter()
+                    .zip(successors.str()?)
+                    .map(|(siret, successor)| {
+                        siret
+                            .filter(|siret| !siret.is_empty())
+                            .or(successor.filter(|successor| !successor.is_empty()))
+                            .and_then(|start| chains.resolve(start))
+                    })
+                    .collect::<StringChunked>();
+                Ok(Some(active.into_column()))
+            },
+            [col(siret_column), col(successor_column)],
+            GetOutput::from_type(DataType::String),
+        )
+    }
+}
+
+/// Active SIRET of the HDD records, named [`Hdd::SiretActif`], through the chains of
+/// `SIRET successeur` of the records themselves, then of `chains`.
+pub fn col_siret_actif_with_polars_expr(
+    chains: &SuccessorChains,
+    sirets: &Column,
+    successors: &Column,
+) -> PolarsResult<Expr> {
+    let chains = chains.clone().with_transfers(sirets, successors)?;
+    Ok(chains
+        .col_active_siret_expr(Hdd::Siret.as_str(), Hdd::SiretSuccesseur.as_str())
+        .alias(Hdd::SiretActif.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::{df, lazy::frame::IntoLazy};
+
+    fn chains() -> SuccessorChains {
+        SuccessorChains::new()
+            .with_transfers(
+                &Column::new("from".into(), ["A", "B", "D", "E", "F"]),
+                &Column::new("to".into(), ["B", "C", "E", "D", "D"]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let test_cases = vec![
+            ("A", Some("C")),
+            ("C", Some("C")),
+            ("G", Some("G")),
+            // D and E are the successor of each other, F leads into them
+            ("D", None),
+            ("F", None),
+        ];
+
+        for (input, expected) in test_cases {
+            assert_eq!(
+                chains().resolve(input).as_deref(),
+                expected,
+                "Failed on input: {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_cycles() {
+        assert_eq!(
+            chains().cycles(),
+            vec![vec!["D".to_string(), "E".to_string()]]
+        );
+    }
+
+    #[test]
+    fn test_with_transfers_first_successor_kept() {
+        let chains = chains()
+            .with_transfers(
+                &Column::new("from".into(), ["A", "H"]),
+                &Column::new("to".into(), ["Z", "H"]),
+            )
+            .unwrap();
+
+        assert_eq!(chains.resolve("A").as_deref(), Some("C"));
+        assert_eq!(chains.resolve("H").as_deref(), Some("H"));
+    }
+
+    #[test]
+    fn test_col_siret_actif_with_polars_expr() {
+        let df = df![
+            Hdd::Siret.as_str() => &[Some("44316952400120"), Some("44316952400138"), None, None],
+            Hdd::SiretSuccesseur.as_str() => &[Some("44316952400138"), Some("55208131700015"), Some("44316952400120"), None],
+        ]
+        .expect("DataFrame creation failed");
+
+        let expr = col_siret_actif_with_polars_expr(
+            &SuccessorChains::new(),
+            df.column(Hdd::Siret.as_str()).unwrap(),
+            df.column(Hdd::SiretSuccesseur.as_str()).unwrap(),
+        )
+        .unwrap();
+        let result_df = df
+            .lazy()
+            .select([expr])
+            .collect()
+            .expect("DataFrame collection failed");
+
+        let result = result_df
+            .column(Hdd::SiretActif.as_str())
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|siret| siret.map(String::from))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            result,
+            vec![
+                Some("55208131700015".to_string()),
+                Some("55208131700015".to_string()),
+                Some("55208131700015".to_string()),
+                None
+            ]
+        );
+    }
+}
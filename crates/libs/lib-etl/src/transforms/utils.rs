@@ -1,11 +1,8 @@
-use std::collections::HashMap;
-
-use polars::{
-    datatypes::StringChunked,
-    error::PolarsResult,
-    frame::DataFrame,
-    prelude::{Column, IntoColumn, NamedFrom},
-    series::Series,
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use polars::prelude::{
+    polars_bail, polars_err, BooleanChunked, Column, DataFrame, DataType, DateChunked,
+    DatetimeChunked, Float64Chunked, Int64Chunked, IntoColumn, IntoSeries, NamedFrom, PlSmallStr,
+    PolarsResult, Series, StringChunked, StructChunked, TimeUnit,
 };
 use serde::Serialize;
 use serde_json::Value;
@@ -76,47 +73,318 @@ where
     Ok(Some(transformed.into_column()))
 }
 
-pub fn struct_to_dataframe<T>(input: &[T]) -> DataFrame
+/// Builds a DataFrame from serializable records, one column per field in declaration order.
+///
+/// Column dtypes follow the serialized values: integers, floats, booleans, lists and nested
+/// structs keep their native dtype, strings holding ISO 8601 dates or datetimes (as chrono
+/// serializes them) become `Date`/`Datetime` columns. Columns without any value are strings.
+pub fn struct_to_dataframe<T>(input: &[T]) -> PolarsResult<DataFrame>
 where
     T: Serialize,
 {
-    // Serialize structs to a vector of maps
-    let mut vec_of_maps = Vec::new();
-    for e in input.iter() {
-        let json_value = serde_json::to_value(e).unwrap();
-        if let Value::Object(map) = json_value {
-            vec_of_maps.push(map);
+    let mut names: Vec<String> = Vec::new();
+    let mut columns: Vec<Vec<Value>> = Vec::new();
+    for (row, record) in input.iter().enumerate() {
+        let value = serde_json::to_value(record)
+            .map_err(|e| polars_err!(ComputeError: "cannot serialize record {}: {}", row, e))?;
+        let Value::Object(map) = value else {
+            polars_bail!(ComputeError: "record {} is not a struct: {}", row, value);
+        };
+        for (key, value) in map {
+            let index = match names.iter().position(|name| *name == key) {
+                Some(index) => index,
+                None => {
+                    names.push(key);
+                    columns.push(vec![Value::Null; row]);
+                    names.len() - 1
+                }
+            };
+            columns[index].push(value);
+        }
+        // Fields skipped during serialization are missing values
+        for column in columns.iter_mut() {
+            if column.len() <= row {
+                column.push(Value::Null);
+            }
         }
     }
 
-    // Initialize column vectors
-    let mut columns: HashMap<String, Vec<Option<String>>> = HashMap::new();
-
-    // Populate columns from the vector of maps
-    for map in vec_of_maps.iter() {
-        for (key, value) in map.iter() {
-            let column = columns.entry(key.clone()).or_default();
-            match value {
-                Value::String(s) => column.push(Some(s.clone())),
-                Value::Null => column.push(None),
-                Value::Number(s) => {
-                    if let Some(num) = s.as_f64() {
-                        let num_i32: i64 = num as i64;
-                        column.push(Some(num_i32.to_string().clone()))
-                    } else {
-                        column.push(Some(s.to_string().clone()))
+    let columns = names
+        .into_iter()
+        .zip(columns)
+        .map(|(name, values)| values_to_series(name.into(), &values).map(IntoColumn::into_column))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    DataFrame::new(columns)
+}
+
+fn values_to_series(name: PlSmallStr, values: &[Value]) -> PolarsResult<Series> {
+    let Some(first) = values.iter().find(|value| !value.is_null()) else {
+        return Ok(Series::full_null(name, values.len(), &DataType::String));
+    };
+    let mismatch = |value: &Value| {
+        polars_err!(
+            SchemaMismatch: "column {} mixes values {} and {}", name, first, value
+        )
+    };
+
+    let series = match first {
+        Value::Bool(_) => values
+            .iter()
+            .map(|value| match value {
+                Value::Null => Ok(None),
+                Value::Bool(b) => Ok(Some(*b)),
+                other => Err(mismatch(other)),
+            })
+            .collect::<PolarsResult<BooleanChunked>>()?
+            .with_name(name)
+            .into_series(),
+        Value::Number(_) => {
+            let is_integer = values
+                .iter()
+                .all(|value| !matches!(value, Value::Number(n) if n.as_i64().is_none()));
+            if is_integer {
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Null => Ok(None),
+                        Value::Number(n) => Ok(n.as_i64()),
+                        other => Err(mismatch(other)),
+                    })
+                    .collect::<PolarsResult<Int64Chunked>>()?
+                    .with_name(name)
+                    .into_series()
+            } else {
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Null => Ok(None),
+                        Value::Number(n) => Ok(n.as_f64()),
+                        other => Err(mismatch(other)),
+                    })
+                    .collect::<PolarsResult<Float64Chunked>>()?
+                    .with_name(name)
+                    .into_series()
+            }
+        }
+        Value::String(_) => {
+            let strings = values
+                .iter()
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    Value::String(s) => Ok(Some(s.as_str())),
+                    other => Err(mismatch(other)),
+                })
+                .collect::<PolarsResult<Vec<_>>>()?;
+            strings_to_series(name, &strings)
+        }
+        Value::Array(_) => {
+            let mut flattened = Vec::new();
+            let mut offsets = Vec::with_capacity(values.len());
+            for value in values {
+                match value {
+                    Value::Null => offsets.push(None),
+                    Value::Array(items) => {
+                        offsets.push(Some((flattened.len(), items.len())));
+                        flattened.extend(items.iter().cloned());
+                    }
+                    other => return Err(mismatch(other)),
+                }
+            }
+            let inner = values_to_series(PlSmallStr::EMPTY, &flattened)?;
+            let rows = offsets
+                .into_iter()
+                .map(|offset| offset.map(|(start, len)| inner.slice(start as i64, len)))
+                .collect::<Vec<_>>();
+            Series::new(name, rows).cast(&DataType::List(Box::new(inner.dtype().clone())))?
+        }
+        Value::Object(_) => {
+            let mut keys: Vec<&String> = Vec::new();
+            for value in values {
+                match value {
+                    Value::Null => {}
+                    Value::Object(map) => {
+                        for key in map.keys() {
+                            if !keys.contains(&key) {
+                                keys.push(key);
+                            }
+                        }
                     }
+                    other => return Err(mismatch(other)),
                 }
-                _ => unreachable!(), // assuming all fields are Option<String>
             }
+            let fields = keys
+                .into_iter()
+                .map(|key| {
+                    let field_values = values
+                        .iter()
+                        .map(|value| value.get(key).cloned().unwrap_or(Value::Null))
+                        .collect::<Vec<_>>();
+                    values_to_series(key.as_str().into(), &field_values)
+                })
+                .collect::<PolarsResult<Vec<_>>>()?;
+            StructChunked::from_series(name, values.len(), fields.iter())?.into_series()
+        }
+        Value::Null => {
+            polars_bail!(ComputeError: "column {} has no value to take its type from", name)
+        }
+    };
+    Ok(series)
+}
+
+/// Reads the strings as dates or datetimes when all of them parse as such.
+fn strings_to_series(name: PlSmallStr, strings: &[Option<&str>]) -> Series {
+    let dates = strings
+        .iter()
+        .map(|s| {
+            s.map(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>();
+    if let Ok(dates) = dates {
+        return DateChunked::from_naive_date_options(name, dates).into_series();
+    }
+
+    let datetimes = strings
+        .iter()
+        .map(|s| s.map(parse_datetime).transpose())
+        .collect::<Option<Vec<_>>>();
+    if let Some(datetimes) = datetimes {
+        return DatetimeChunked::from_naive_datetime_options(
+            name,
+            datetimes,
+            TimeUnit::Microseconds,
+        )
+        .into_series();
+    }
+
+    Series::new(name, strings)
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|dt| dt.naive_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::AnyValue;
+
+    #[derive(Serialize)]
+    struct Contact {
+        nom: String,
+        score: f64,
+        telephone: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Etablissement {
+        siret: Option<String>,
+        effectif: Option<i32>,
+        actif: bool,
+        cree_le: NaiveDate,
+        ids: Vec<i32>,
+        contact: Contact,
+    }
+
+    fn etablissements() -> Vec<Etablissement> {
+        vec![
+            Etablissement {
+                siret: Some("44316952400120".to_string()),
+                effectif: Some(12),
+                actif: true,
+                cree_le: NaiveDate::from_ymd_opt(2021, 3, 1).unwrap(),
+                ids: vec![1, 2],
+                contact: Contact {
+                    nom: "SALLANDIER".to_string(),
+                    score: 0.75,
+                    telephone: Some("0606060606".to_string()),
+                },
+            },
+            Etablissement {
+                siret: None,
+                effectif: None,
+                actif: false,
+                cree_le: NaiveDate::from_ymd_opt(2022, 12, 31).unwrap(),
+                ids: vec![],
+                contact: Contact {
+                    nom: "CARRE".to_string(),
+                    score: 1.0,
+                    telephone: None,
+                },
+            },
+        ]
+    }
+
+    #[test]
+    fn test_struct_to_dataframe_keeps_field_order() {
+        let df = struct_to_dataframe(&etablissements()).expect("Conversion failed");
+
+        assert_eq!(
+            df.get_column_names_str(),
+            ["siret", "effectif", "actif", "cree_le", "ids", "contact"]
+        );
+    }
+
+    #[test]
+    fn test_struct_to_dataframe_native_dtypes() {
+        let df = struct_to_dataframe(&etablissements()).expect("Conversion failed");
+
+        let cases = vec![
+            ("siret", DataType::String),
+            ("effectif", DataType::Int64),
+            ("actif", DataType::Boolean),
+            ("cree_le", DataType::Date),
+            ("ids", DataType::List(Box::new(DataType::Int64))),
+        ];
+        for (column, expected) in cases {
+            assert_eq!(
+                df.column(column).expect("Missing column").dtype(),
+                &expected,
+                "{}",
+                column
+            );
         }
+
+        let contact = df
+            .column("contact")
+            .expect("Missing column")
+            .struct_()
+            .expect("Not a struct")
+            .fields_as_series();
+        assert_eq!(contact[1].name().as_str(), "score");
+        assert_eq!(contact[1].get(0).unwrap(), AnyValue::Float64(0.75));
+        assert_eq!(contact[2].get(1).unwrap(), AnyValue::Null);
+    }
+
+    #[test]
+    fn test_struct_to_dataframe_keeps_decimals() {
+        #[derive(Serialize)]
+        struct Mesure {
+            valeur: f64,
+        }
+
+        let df = struct_to_dataframe(&[Mesure { valeur: 2.0 }, Mesure { valeur: 1.5 }])
+            .expect("Conversion failed");
+
+        assert_eq!(
+            df.column("valeur").unwrap().get(1).unwrap(),
+            AnyValue::Float64(1.5)
+        );
     }
 
-    // Create DataFrame
-    let mut df = DataFrame::default();
-    for (key, values) in columns.into_iter() {
-        let series = Series::new(key.into(), values);
-        df.with_column(series).unwrap();
+    #[test]
+    fn test_struct_to_dataframe_rejects_mixed_values() {
+        assert!(struct_to_dataframe(&[
+            serde_json::json!({"id": 1}),
+            serde_json::json!({"id": "1"}),
+        ])
+        .is_err());
+        assert!(struct_to_dataframe(&["not a struct"]).is_err());
     }
-    df
 }
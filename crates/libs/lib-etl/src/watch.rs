@@ -0,0 +1,473 @@
+use crate::{config::WatchConfig, schemas::SchemasEnum};
+use chrono::{Local, Utc};
+use core::error::Error;
+use core::future::Future;
+use glob::Pattern;
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::mpsc;
+
+pub const ARCHIVE_DIR: &str = "archive";
+pub const ERROR_DIR: &str = "error";
+pub const DONE_MARKER_EXTENSION: &str = "done";
+/// JSON Lines file of the drop directory where the outcome of each file is appended.
+pub const JOURNAL_FILE: &str = "watch-journal.jsonl";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Archived,
+    Failed,
+}
+
+/// What happened to a dropped file, appended to the journal.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileOutcome {
+    pub file: PathBuf,
+    pub dataset: Option<SchemasEnum>,
+    pub status: FileStatus,
+    pub rows: Option<u64>,
+    /// Error of the pipeline, then of the move or the journal.
+    pub error: Option<String>,
+    /// `None` when the file could not be moved, it stays in the drop directory.
+    pub moved_to: Option<PathBuf>,
+    pub processed_at: chrono::DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct PendingFile {
+    size: u64,
+    modified: Option<SystemTime>,
+    unchanged_since: Instant,
+}
+
+/// Watches a drop directory and runs a pipeline on each new file once it is fully written.
+///
+/// A file is ready when its size and modification time did not change for `stable_for_secs`,
+/// or when its `<file>.done` marker exists (the only condition with `require_done_marker`).
+/// The dataset comes from the first rule whose pattern matches the file name. Files matching
+/// no rule, or that the pipeline rejects, move to `error/`, the others to `archive/`.
+/// A file that cannot be moved is logged and left in place, and not ingested again until
+/// the watcher restarts.
+pub struct DirectoryWatcher {
+    drop_dir: PathBuf,
+    rules: Vec<(Pattern, SchemasEnum)>,
+    stable_for: Duration,
+    require_done_marker: bool,
+    pending: HashMap<PathBuf, PendingFile>,
+    not_moved: HashSet<PathBuf>,
+}
+
+impl DirectoryWatcher {
+    pub fn new(config: &WatchConfig) -> Result<Self, Box<dyn Error>> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| Ok((Pattern::new(&rule.pattern)?, rule.dataset)))
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        let drop_dir = PathBuf::from(&config.drop_dir);
+        fs::create_dir_all(drop_dir.join(ARCHIVE_DIR))?;
+        fs::create_dir_all(drop_dir.join(ERROR_DIR))?;
+
+        Ok(DirectoryWatcher {
+            drop_dir,
+            rules,
+            stable_for: Duration::from_secs(config.stable_for_secs),
+            require_done_marker: config.require_done_marker,
+            pending: HashMap::new(),
+            not_moved: HashSet::new(),
+        })
+    }
+
+    /// Watches the drop directory until the watcher fails, running `pipeline` on each ready file.
+    /// The pipeline returns the number of rows it ingested.
+    pub async fn run<F, Fut>(&mut self, mut pipeline: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(PathBuf, SchemasEnum) -> Fut,
+        Fut: Future<Output = Result<u64, Box<dyn Error>>>,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+        watcher.watch(&self.drop_dir, RecursiveMode::NonRecursive)?;
+        info!("Watching {}", self.drop_dir.display());
+
+        // Files dropped while the watcher was down
+        self.scan()?;
+        loop {
+            if let Ok(event) = tokio::time::timeout(POLL_INTERVAL, rx.recv()).await {
+                let event = event.ok_or("Directory watcher stopped")??;
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    event.paths.iter().for_each(|path| self.track(path));
+                }
+            }
+            self.process_ready(&mut pipeline).await;
+        }
+    }
+
+    /// Tracks the files already in the drop directory.
+    pub fn scan(&mut self) -> Result<(), Box<dyn Error>> {
+        for entry in fs::read_dir(&self.drop_dir)? {
+            self.track(&entry?.path());
+        }
+        Ok(())
+    }
+
+    fn track(&mut self, path: &Path) {
+        let path = match path.extension().and_then(|extension| extension.to_str()) {
+            // A marker makes its data file ready, which may not have been tracked yet
+            Some(DONE_MARKER_EXTENSION) => path.with_extension(""),
+            _ => path.to_path_buf(),
+        };
+        if !path.is_file()
+            || is_ignored(&path)
+            || self.pending.contains_key(&path)
+            || self.not_moved.contains(&path)
+        {
+            return;
+        }
+        debug!("Tracking {}", path.display());
+        self.pending.insert(
+            path,
+            PendingFile {
+                size: 0,
+                modified: None,
+                unchanged_since: Instant::now(),
+            },
+        );
+    }
+
+    /// Runs `pipeline` on the tracked files that are fully written, and returns their outcome.
+    pub async fn process_ready<F, Fut>(&mut self, pipeline: &mut F) -> Vec<FileOutcome>
+    where
+        F: FnMut(PathBuf, SchemasEnum) -> Fut,
+        Fut: Future<Output = Result<u64, Box<dyn Error>>>,
+    {
+        let mut outcomes = Vec::new();
+        for path in self.ready_files() {
+            self.pending.remove(&path);
+            let outcome = self.process_file(&path, pipeline).await;
+            if outcome.moved_to.is_none() {
+                self.not_moved.insert(path);
+            }
+            outcomes.push(outcome);
+        }
+        outcomes
+    }
+
+    /// Updates the size of the tracked files and returns the ready ones, forgetting deleted files.
+    fn ready_files(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let mut ready = Vec::new();
+        self.pending.retain(|path, pending| {
+            let Ok(metadata) = fs::metadata(path) else {
+                debug!("{} disappeared before being processed", path.display());
+                return false;
+            };
+            if done_marker(path).exists() {
+                ready.push(path.clone());
+                return true;
+            }
+            let modified = metadata.modified().ok();
+            if metadata.len() != pending.size || modified != pending.modified {
+                pending.size = metadata.len();
+                pending.modified = modified;
+                pending.unchanged_since = now;
+            } else if !self.require_done_marker
+                && now.duration_since(pending.unchanged_since) >= self.stable_for
+            {
+                ready.push(path.clone());
+            }
+            true
+        });
+        ready.sort();
+        ready
+    }
+
+    /// Ingests and moves the file, the failures are logged and recorded in the outcome.
+    async fn process_file<F, Fut>(&self, path: &Path, pipeline: &mut F) -> FileOutcome
+    where
+        F: FnMut(PathBuf, SchemasEnum) -> Fut,
+        Fut: Future<Output = Result<u64, Box<dyn Error>>>,
+    {
+        let dataset = self.dataset_for(path);
+        let result = match dataset {
+            Some(dataset) => {
+                info!("Ingesting {} as {}", path.display(), dataset.name());
+                pipeline(path.to_path_buf(), dataset).await
+            }
+            None => Err("No watch rule matches the file name".into()),
+        };
+
+        let (status, rows, mut error, target_dir) = match result {
+            Ok(rows) => (FileStatus::Archived, Some(rows), None, ARCHIVE_DIR),
+            Err(e) => (FileStatus::Failed, None, Some(e.to_string()), ERROR_DIR),
+        };
+        let moved_to = match self.move_file(path, target_dir) {
+            Ok(target) => Some(target),
+            Err(e) => {
+                let e = format!("Failed to move to {}/: {}", target_dir, e);
+                error!("{}: {}", path.display(), e);
+                push_error(&mut error, e);
+                None
+            }
+        };
+        let mut outcome = FileOutcome {
+            file: path.to_path_buf(),
+            dataset,
+            status,
+            rows,
+            error,
+            moved_to,
+            processed_at: Utc::now(),
+        };
+        match (&outcome.error, &outcome.moved_to) {
+            (None, Some(moved_to)) => info!(
+                "{} ingested ({} rows), moved to {}",
+                path.display(),
+                rows.unwrap_or_default(),
+                moved_to.display()
+            ),
+            (Some(e), Some(moved_to)) => error!(
+                "{} failed, moved to {}: {}",
+                path.display(),
+                moved_to.display(),
+                e
+            ),
+            (_, None) => {}
+        }
+        if let Err(e) = self.append_to_journal(&outcome) {
+            let e = format!("Failed to write the journal: {}", e);
+            error!("{}: {}", path.display(), e);
+            push_error(&mut outcome.error, e);
+        }
+        outcome
+    }
+
+    fn dataset_for(&self, path: &Path) -> Option<SchemasEnum> {
+        let file_name = path.file_name()?.to_str()?;
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(file_name))
+            .map(|(_, dataset)| *dataset)
+    }
+
+    /// Moves the file and its marker to `target_dir`, prefixed with a timestamp so redelivered
+    /// files never overwrite the previous ones.
+    fn move_file(&self, path: &Path, target_dir: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format!("{} has no file name", path.display()))?
+            .to_string_lossy();
+        let prefix = Local::now().format("%Y%m%dT%H%M%S%.3f");
+        let target = self
+            .drop_dir
+            .join(target_dir)
+            .join(format!("{}_{}", prefix, file_name));
+        fs::rename(path, &target)?;
+
+        let marker = done_marker(path);
+        if marker.exists() {
+            if let Err(e) = fs::remove_file(&marker) {
+                warn!("Failed to remove {}: {}", marker.display(), e);
+            }
+        }
+        Ok(target)
+    }
+
+    fn append_to_journal(&self, outcome: &FileOutcome) -> Result<(), Box<dyn Error>> {
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.drop_dir.join(JOURNAL_FILE))?;
+        writeln!(journal, "{}", serde_json::to_string(outcome)?)?;
+        Ok(())
+    }
+}
+
+fn push_error(error: &mut Option<String>, e: String) {
+    *error = Some(match error.take() {
+        Some(previous) => format!("{}; {}", previous, e),
+        None => e,
+    });
+}
+
+fn done_marker(path: &Path) -> PathBuf {
+    let mut marker = path.as_os_str().to_owned();
+    marker.push(".");
+    marker.push(DONE_MARKER_EXTENSION);
+    PathBuf::from(marker)
+}
+
+/// Markers, the journal, hidden and partial files are never ingested.
+fn is_ignored(path: &Path) -> bool {
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return true;
+    };
+    file_name.starts_with('.')
+        || file_name == JOURNAL_FILE
+        || [DONE_MARKER_EXTENSION, "tmp", "part", "crdownload"]
+            .iter()
+            .any(|extension| file_name.ends_with(&format!(".{}", extension)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WatchRule;
+
+    fn watcher(drop_dir: &Path, require_done_marker: bool) -> DirectoryWatcher {
+        let config = WatchConfig {
+            drop_dir: drop_dir.to_string_lossy().to_string(),
+            stable_for_secs: 0,
+            require_done_marker,
+            rules: vec![
+                WatchRule {
+                    pattern: "JDD*.csv*".to_string(),
+                    dataset: SchemasEnum::Jdd,
+                },
+                WatchRule {
+                    pattern: "HDD*.csv*".to_string(),
+                    dataset: SchemasEnum::Hdd,
+                },
+            ],
+        };
+        DirectoryWatcher::new(&config).expect("Watcher creation failed")
+    }
+
+    async fn fake_pipeline(path: PathBuf, _: SchemasEnum) -> Result<u64, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        if content.contains("invalid") {
+            return Err("Invalid row".into());
+        }
+        Ok(content.lines().count() as u64 - 1)
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let test_cases = vec![
+            ("JDD_01.csv", false),
+            ("JDD_01.csv.gz", false),
+            ("JDD_01.csv.done", true),
+            ("JDD_01.csv.part", true),
+            (".JDD_01.csv.swp", true),
+            (JOURNAL_FILE, true),
+        ];
+        for (file_name, expected) in test_cases {
+            assert_eq!(is_ignored(Path::new(file_name)), expected, "{}", file_name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_ready_moves_files() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        fs::write(dir.path().join("JDD_01.csv"), "NOM\nDUPONT\nMARTIN\n").unwrap();
+        fs::write(dir.path().join("HDD_01.csv"), "Nom\ninvalid\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "Livraison de mars\n").unwrap();
+        let mut watcher = watcher(dir.path(), false);
+        let mut pipeline = fake_pipeline;
+
+        watcher.scan().expect("Scan failed");
+        // First pass records the sizes, the files are ready once they did not change
+        let first = watcher.process_ready(&mut pipeline).await;
+        let second = watcher.process_ready(&mut pipeline).await;
+
+        assert!(first.is_empty());
+        let outcomes = second
+            .iter()
+            .map(|outcome| {
+                (
+                    outcome.file.file_name().unwrap().to_str().unwrap(),
+                    outcome.dataset,
+                    outcome.status,
+                    outcome.rows,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            [
+                (
+                    "HDD_01.csv",
+                    Some(SchemasEnum::Hdd),
+                    FileStatus::Failed,
+                    None
+                ),
+                (
+                    "JDD_01.csv",
+                    Some(SchemasEnum::Jdd),
+                    FileStatus::Archived,
+                    Some(2)
+                ),
+                ("notes.txt", None, FileStatus::Failed, None),
+            ]
+        );
+        assert_eq!(
+            fs::read_dir(dir.path().join(ARCHIVE_DIR)).unwrap().count(),
+            1
+        );
+        assert_eq!(fs::read_dir(dir.path().join(ERROR_DIR)).unwrap().count(), 2);
+        let journal = fs::read_to_string(dir.path().join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_done_marker_is_required() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let file = dir.path().join("JDD_01.csv");
+        fs::write(&file, "NOM\nDUPONT\n").unwrap();
+        let mut watcher = watcher(dir.path(), true);
+        let mut pipeline = fake_pipeline;
+
+        watcher.scan().unwrap();
+        watcher.process_ready(&mut pipeline).await;
+        let without_marker = watcher.process_ready(&mut pipeline).await;
+        fs::write(done_marker(&file), "").unwrap();
+        let with_marker = watcher.process_ready(&mut pipeline).await;
+
+        assert!(without_marker.is_empty());
+        assert_eq!(with_marker.len(), 1);
+        assert_eq!(with_marker[0].status, FileStatus::Archived);
+        assert!(!done_marker(&file).exists());
+    }
+
+    #[tokio::test]
+    async fn test_failed_move_keeps_watching() {
+        let dir = tempfile::tempdir().expect("Temp dir creation failed");
+        let file = dir.path().join("JDD_01.csv");
+        fs::write(&file, "NOM\nDUPONT\n").unwrap();
+        let mut watcher = watcher(dir.path(), false);
+        let mut pipeline = fake_pipeline;
+        fs::remove_dir(dir.path().join(ARCHIVE_DIR)).unwrap();
+
+        watcher.scan().unwrap();
+        watcher.process_ready(&mut pipeline).await;
+        let outcomes = watcher.process_ready(&mut pipeline).await;
+        watcher.scan().unwrap();
+        let again = watcher.process_ready(&mut pipeline).await;
+
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].status, FileStatus::Archived);
+        assert_eq!(outcomes[0].moved_to, None);
+        assert!(outcomes[0]
+            .error
+            .as_ref()
+            .unwrap()
+            .starts_with("Failed to move"));
+        assert!(file.exists());
+        assert!(again.is_empty());
+        let journal = fs::read_to_string(dir.path().join(JOURNAL_FILE)).unwrap();
+        assert_eq!(journal.lines().count(), 1);
+    }
+}
@@ -24,6 +24,10 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[serde_as]
 #[derive(Debug, From, Serialize, RpcHandlerError)]
 pub enum Error {
+	// -- Etl Runs
+	EtlRunStartFail(String),
+	EtlRunNotRunning { id: i64 },
+
 	// -- App Libs
 	#[from]
 	Model(lib_core::model::Error),
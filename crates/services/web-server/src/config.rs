@@ -14,12 +14,14 @@ pub fn web_config() -> &'static WebConfig {
 #[allow(non_snake_case)]
 pub struct WebConfig {
 	pub WEB_FOLDER: String,
+	pub ETL_DB_URL: String,
 }
 
 impl WebConfig {
 	fn load_from_env() -> lib_utils::envs::Result<WebConfig> {
 		Ok(WebConfig {
 			WEB_FOLDER: get_env("SERVICE_WEB_FOLDER")?,
+			ETL_DB_URL: get_env("SERVICE_ETL_DB_URL")?,
 		})
 	}
 }
@@ -8,6 +8,10 @@ pub enum Error {
 	// -- Modules
 	#[from]
 	Model(model::Error),
+
+	// -- External Modules
+	#[from]
+	Sqlx(sqlx::Error),
 }
 
 // region:    --- Error Boilerplate
@@ -13,10 +13,12 @@ use lib_web::middleware::mw_res_map::mw_reponse_map;
 use lib_web::routes::routes_static;
 
 use crate::web::routes_login;
+use crate::web::rpcs::etl_run_rpc::EtlRunner;
 
 use axum::{middleware, Router};
 use lib_core::_dev_utils;
 use lib_core::model::ModelManager;
+use sqlx::PgPool;
 use tokio::net::TcpListener;
 use tower_cookies::CookieManagerLayer;
 use tracing::info;
@@ -36,9 +38,12 @@ async fn main() -> Result<()> {
 	_dev_utils::init_dev().await;
 
 	let mm = ModelManager::new().await?;
+	// The pipelines run against the ETL database, not the app one.
+	let etl_pool = PgPool::connect_lazy(&web_config().ETL_DB_URL)?;
+	let etl_runner = EtlRunner::new(etl_pool);
 
 	// -- Define Routes
-	let routes_rpc = web::routes_rpc::routes(mm.clone())
+	let routes_rpc = web::routes_rpc::routes(mm.clone(), etl_runner)
 		.route_layer(middleware::from_fn(mw_ctx_require));
 
 	let routes_all = Router::new()
@@ -1,4 +1,5 @@
 use crate::web::rpcs::all_rpc_router_builder;
+use crate::web::rpcs::etl_run_rpc::EtlRunner;
 use axum::routing::post;
 use axum::Router;
 use lib_core::model::ModelManager;
@@ -7,11 +8,12 @@ use lib_web::handlers::handlers_rpc;
 ///  Build the Axum router for '/api/rpc'
 /// Note: This will build the `rpc-router::Router` that will be used by the
 ///       rpc_axum_handler
-pub fn routes(mm: ModelManager) -> Router {
+pub fn routes(mm: ModelManager, etl_runner: EtlRunner) -> Router {
 	// Build the combined Rpc Router (from `rpc-router` crate)
 	let rpc_router = all_rpc_router_builder()
 		// Add the common resources for all rpc calls
 		.append_resource(mm)
+		.append_resource(etl_runner)
 		.build();
 
 	// Build the Axum Router for '/rpc'
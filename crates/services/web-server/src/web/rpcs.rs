@@ -2,6 +2,7 @@
 
 pub mod agent_rpc;
 pub mod conv_rpc;
+pub mod etl_run_rpc;
 
 use rpc_router::{Router, RouterBuilder};
 
@@ -11,4 +12,5 @@ pub fn all_rpc_router_builder() -> RouterBuilder {
 	Router::builder()
 		.extend(agent_rpc::rpc_router_builder())
 		.extend(conv_rpc::rpc_router_builder())
+		.extend(etl_run_rpc::rpc_router_builder())
 }
@@ -0,0 +1,139 @@
+use lib_core::model::etl_run::{EtlRun, EtlRunBmc, EtlRunFilter, EtlRunStatus};
+use lib_etl::config::IO_CONFIG_PATH;
+use lib_etl::pipelines::Pipeline;
+use lib_etl::run::{config_hash, RunRecorder};
+use lib_rpc_core::prelude::*;
+use lib_rpc_core::Error;
+use rpc_router::{IntoParams, RpcResource};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+use tracing::{info, warn};
+
+pub fn rpc_router_builder() -> RouterBuilder {
+    router_builder!(run_pipeline, get_etl_run, list_etl_runs, cancel_etl_run)
+}
+
+/// Resource running the pipelines in background tasks.
+/// Keeps the recorder of every run still running, by run id, to cancel it.
+#[derive(Clone, RpcResource)]
+pub struct EtlRunner {
+    pool: PgPool,
+    tasks: Arc<Mutex<HashMap<i64, RunRecorder>>>,
+}
+
+impl EtlRunner {
+    /// `pool` is the connection pool of the ETL database the pipelines read from and write to.
+    pub fn new(pool: PgPool) -> Self {
+        EtlRunner {
+            pool,
+            tasks: Arc::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ParamsRunPipeline {
+    pub pipeline: Pipeline,
+}
+
+impl IntoParams for ParamsRunPipeline {}
+
+/// Starts the pipeline in a background task and returns the `Running` run right away.
+/// The progress is followed with `get_etl_run`.
+///
+/// The pipelines collect DataFrames and write files synchronously, so the task runs on
+/// the blocking threads, not to stall the workers serving the RPCs.
+pub async fn run_pipeline(
+    ctx: Ctx,
+    mm: ModelManager,
+    runner: EtlRunner,
+    params: ParamsRunPipeline,
+) -> Result<DataRpcResult<EtlRun>> {
+    let ParamsRunPipeline { pipeline } = params;
+    let config_json =
+        std::fs::read(IO_CONFIG_PATH).map_err(|ex| Error::EtlRunStartFail(ex.to_string()))?;
+    let recorder = RunRecorder::start(&ctx, &mm, pipeline.name(), &config_hash(&config_json))
+        .await
+        .map_err(|ex| Error::EtlRunStartFail(ex.to_string()))?;
+    let run_id = recorder.run_id();
+
+    {
+        // Hold the lock while spawning so the task cannot remove its recorder before it is inserted
+        let mut tasks = runner.tasks.lock().unwrap();
+        tasks.insert(run_id, recorder.clone());
+        let task_runner = runner.clone();
+        let handle = Handle::current();
+        tokio::task::spawn_blocking(move || {
+            handle.block_on(async move {
+                let result = pipeline
+                    .run(&task_runner.pool, &recorder)
+                    .await
+                    .map_err(|ex| ex.to_string());
+                let recorded = match result {
+                    Ok(stats) => recorder.succeed(stats).await.map_err(|ex| ex.to_string()),
+                    Err(error_msg) => recorder.fail(&error_msg).await.map_err(|ex| ex.to_string()),
+                };
+                if let Err(ex) = recorded {
+                    warn!("{:<12} - etl run {run_id} not recorded: {ex}", "ETL");
+                }
+                task_runner.tasks.lock().unwrap().remove(&run_id);
+            })
+        });
+    }
+    info!(
+        "{:<12} - etl run {run_id} started ({})",
+        "ETL",
+        pipeline.name()
+    );
+
+    let run = EtlRunBmc::get(&ctx, &mm, run_id).await?;
+    Ok(run.into())
+}
+
+pub async fn get_etl_run(
+    ctx: Ctx,
+    mm: ModelManager,
+    params: ParamsIded,
+) -> Result<DataRpcResult<EtlRun>> {
+    let run = EtlRunBmc::get(&ctx, &mm, params.id).await?;
+    Ok(run.into())
+}
+
+pub async fn list_etl_runs(
+    ctx: Ctx,
+    mm: ModelManager,
+    params: ParamsList<EtlRunFilter>,
+) -> Result<DataRpcResult<Vec<EtlRun>>> {
+    let runs = EtlRunBmc::list(&ctx, &mm, params.filters, params.list_options).await?;
+    Ok(runs.into())
+}
+
+/// Marks a running run `Cancelled` and asks its pipeline to stop at its next stage.
+/// Rows already written by the pipeline are kept.
+pub async fn cancel_etl_run(
+    ctx: Ctx,
+    mm: ModelManager,
+    runner: EtlRunner,
+    params: ParamsIded,
+) -> Result<DataRpcResult<EtlRun>> {
+    let ParamsIded { id } = params;
+    // Only a `Running` run is cancelled, the pipeline may have finished meanwhile
+    if !EtlRunBmc::finish(&ctx, &mm, id, EtlRunStatus::Cancelled, None).await? {
+        EtlRunBmc::get(&ctx, &mm, id).await?;
+        return Err(Error::EtlRunNotRunning { id });
+    }
+
+    // A running run without a task was started by another process, or lost on a restart.
+    let recorder = runner.tasks.lock().unwrap().get(&id).cloned();
+    match recorder {
+        Some(recorder) => recorder.cancel(),
+        None => warn!("{:<12} - etl run {id} has no task in this server", "ETL"),
+    }
+    info!("{:<12} - etl run {id} cancelled", "ETL");
+
+    let run = EtlRunBmc::get(&ctx, &mm, id).await?;
+    Ok(run.into())
+}
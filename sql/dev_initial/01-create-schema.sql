@@ -120,3 +120,31 @@ ON DELETE CASCADE;
 ALTER TABLE conv_user ADD CONSTRAINT fk_conv_user_conv
 FOREIGN KEY (user_id) REFERENCES "user" (id)
 ON DELETE CASCADE;
+
+-- Etl Run
+CREATE TYPE etl_run_status AS ENUM ('Running', 'Succeeded', 'Failed', 'Cancelled');
+
+CREATE TABLE etl_run (
+    -- PK
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+    -- Properties
+    pipeline_name VARCHAR(256) NOT NULL,
+    config_hash VARCHAR(64) NOT NULL,
+    status ETL_RUN_STATUS NOT NULL DEFAULT 'Running',
+    start_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    end_time TIMESTAMP WITH TIME ZONE,
+    rows_read BIGINT NOT NULL DEFAULT 0,
+    rows_written BIGINT NOT NULL DEFAULT 0,
+    rows_rejected BIGINT NOT NULL DEFAULT 0,
+    error_msg TEXT,
+
+    -- Timestamps
+    -- cid is the user who triggered the run
+    cid BIGINT NOT NULL,
+    ctime TIMESTAMP WITH TIME ZONE NOT NULL,
+    mid BIGINT NOT NULL,
+    mtime TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_etl_run_pipeline_name ON etl_run (pipeline_name, start_time DESC);